@@ -1,12 +1,15 @@
 use crate::cli::Cli;
 use crate::config::manager::ConfigManager;
 use crate::executor::task_queue_manager::TaskQueueManager;
+use crate::inventory::host::Host;
 use crate::inventory::manager::InventoryManager;
 use crate::playbook::play::Play;
 use crate::playbook::task::{Action, TaskBuilder};
 use crate::playbook::Playbook;
+use crate::vars::fact_cache::FactCache;
 use crate::vars::manager::VariableManager;
 use crate::vars::variable::Variable::Path;
+use crate::vars::variable::{load_extra_vars, ConflictResolution};
 use anyhow::{anyhow, Result};
 use cogrs_plugins::plugin_type::PluginType;
 use cogrs_plugins::{plugin_loader, plugin_type};
@@ -14,6 +17,7 @@ use log::info;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
 pub struct AdHoc;
@@ -27,6 +31,9 @@ pub struct AdHocOptions {
     pub connection: String,
     pub connection_timeout: Option<u64>,
     pub private_key_file: Option<PathBuf>,
+    pub extra_vars: Vec<String>,
+    pub check: bool,
+    pub limit_expr: Option<String>,
 }
 
 impl Cli for AdHoc {}
@@ -37,7 +44,7 @@ impl AdHoc {
         limit: Option<&str>,
         module_name: &str,
         module_args: Option<String>,
-        inventory_manager: &InventoryManager,
+        inventory_manager: &mut InventoryManager,
         options: &AdHocOptions,
     ) -> Result<()> {
         info!(
@@ -45,8 +52,6 @@ impl AdHoc {
             module_name, module_args
         );
 
-        Self::init().await?;
-
         let task = TaskBuilder::new(
             "AdHoc",
             &options.connection,
@@ -59,7 +64,8 @@ impl AdHoc {
         let tasks = vec![task];
         let roles = [];
 
-        let variable_manager = VariableManager::new(inventory_manager.get_base_dir());
+        let mut variable_manager = VariableManager::new(inventory_manager.get_base_dir());
+        Self::configure_variable_manager(&mut variable_manager, options).await?;
 
         let play = Play::builder("CogRS Ad-Hoc", &roles)
             .use_become(false)
@@ -67,13 +73,64 @@ impl AdHoc {
             .connection(&options.connection)
             .pattern(pattern)
             .limit(limit)
+            .limit_expr(options.limit_expr.as_deref())
             .tasks(&tasks)
             .build();
 
         let _playbook = Playbook::new("__adhoc_playbook__", &[play.clone()]);
 
         let mut tqm = TaskQueueManager::new(Some(options.forks as usize));
-        tqm.run(play, &variable_manager, inventory_manager).await?;
+        tqm.run(play, Arc::new(variable_manager), inventory_manager)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the hosts an ad-hoc run would target, for `--list-hosts`.
+    pub async fn list_hosts(
+        pattern: &str,
+        limit: Option<&str>,
+        inventory_manager: &InventoryManager,
+        options: &AdHocOptions,
+    ) -> Result<Vec<Host>> {
+        let hosts = inventory_manager.filter_hosts(pattern, limit)?;
+        let Some(limit_expr) = &options.limit_expr else {
+            return Ok(hosts);
+        };
+
+        let mut variable_manager = VariableManager::new(inventory_manager.get_base_dir());
+        Self::configure_variable_manager(&mut variable_manager, options).await?;
+
+        inventory_manager.filter_hosts_by_expression(hosts, limit_expr, &variable_manager, None)
+    }
+
+    async fn configure_variable_manager(
+        variable_manager: &mut VariableManager,
+        options: &AdHocOptions,
+    ) -> Result<()> {
+        let config_manager = ConfigManager::instance().lock().await;
+        let mut hash_behaviour = ConflictResolution::default();
+
+        if let Some((value, _)) =
+            config_manager.get_config_value::<String>("DEFAULT_HASH_BEHAVIOUR")?
+        {
+            hash_behaviour = value.parse::<ConflictResolution>()?;
+            variable_manager.set_hash_behaviour(hash_behaviour);
+        }
+
+        if let Some((debug, _)) = config_manager.get_config_value::<bool>("DEFAULT_DEBUG")? {
+            variable_manager.set_track_origins(debug);
+        }
+
+        variable_manager.set_check_mode(options.check);
+        variable_manager.set_fact_cache(FactCache::from_config(&config_manager).await?);
+
+        let extra_vars = load_extra_vars(
+            &options.extra_vars,
+            variable_manager.loader(),
+            &hash_behaviour,
+        )?;
+        variable_manager.set_extra_vars(extra_vars);
 
         Ok(())
     }
@@ -0,0 +1,32 @@
+mod file;
+mod memory;
+
+use anyhow::Result;
+use cogrs_plugins::cache::CachePlugin;
+use cogrs_plugins::plugin_loader::PluginLoader;
+use file::{FileCache, FileFormat};
+use memory::MemoryCache;
+
+/// Returns one of the cache backends shipped with cogrs.
+pub fn get_builtin_cache_plugin(name: &str) -> Option<Box<dyn CachePlugin>> {
+    match name {
+        "memory" => Some(Box::new(MemoryCache::default())),
+        "jsonfile" => Some(Box::new(FileCache::new(FileFormat::Json))),
+        "yaml" => Some(Box::new(FileCache::new(FileFormat::Yaml))),
+        _ => None,
+    }
+}
+
+/// Returns a cache backend by name, built-in backends are looked up first, then the plugins
+/// found by the [`PluginLoader`]. The backend still has to be initialized.
+pub async fn get_cache_plugin(name: &str) -> Result<Box<dyn CachePlugin>> {
+    if let Some(plugin) = get_builtin_cache_plugin(name) {
+        return Ok(plugin);
+    }
+
+    PluginLoader::instance()
+        .lock()
+        .await
+        .get_cache_plugin(name)
+        .await
+}
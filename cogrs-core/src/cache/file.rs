@@ -0,0 +1,225 @@
+use anyhow::{bail, Context, Result};
+use cogrs_plugins::cache::{CachePlugin, CacheSettings};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum FileFormat {
+    Json,
+    Yaml,
+}
+
+impl FileFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            FileFormat::Json => "jsonfile",
+            FileFormat::Yaml => "yaml",
+        }
+    }
+
+    fn dump(&self, value: &Value) -> Result<String> {
+        Ok(match self {
+            FileFormat::Json => serde_json::to_string_pretty(value)?,
+            FileFormat::Yaml => serde_yaml::to_string(value)?,
+        })
+    }
+
+    fn load(&self, content: &str) -> Result<Value> {
+        Ok(match self {
+            FileFormat::Json => serde_json::from_str(content)?,
+            FileFormat::Yaml => serde_yaml::from_str(content)?,
+        })
+    }
+}
+
+/// Stores one file per key in the cache location, entries expire `timeout` seconds after
+/// the file was last written.
+pub(super) struct FileCache {
+    format: FileFormat,
+    dir: PathBuf,
+    prefix: String,
+    timeout: Option<Duration>,
+}
+
+impl FileCache {
+    pub(super) fn new(format: FileFormat) -> Self {
+        FileCache {
+            format,
+            dir: PathBuf::new(),
+            prefix: String::new(),
+            timeout: None,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", self.prefix, key))
+    }
+
+    fn is_fresh(&self, path: &PathBuf) -> bool {
+        let Ok(modified) = fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+            return false;
+        };
+
+        match self.timeout {
+            None => true,
+            Some(timeout) => SystemTime::now()
+                .duration_since(modified)
+                .map_or(true, |age| age <= timeout),
+        }
+    }
+
+    /// Returns the keys of every cache file, expired ones included.
+    fn stored_keys(&self) -> Result<Vec<String>> {
+        let entries = fs::read_dir(&self.dir)
+            .with_context(|| format!("Failed to read cache directory {}", self.dir.display()))?;
+
+        let mut keys = Vec::new();
+        for entry in entries.filter_map(Result::ok) {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if let Some(key) = file_name.strip_prefix(&self.prefix) {
+                keys.push(key.to_string());
+            }
+        }
+        keys.sort();
+
+        Ok(keys)
+    }
+}
+
+impl CachePlugin for FileCache {
+    fn initialize(&mut self, settings: &CacheSettings) -> Result<()> {
+        let Some(location) = &settings.location else {
+            bail!(
+                "The '{}' cache plugin requires the 'CACHE_PLUGIN_CONNECTION' setting",
+                self.format.name()
+            );
+        };
+
+        fs::create_dir_all(location)
+            .with_context(|| format!("Failed to create cache directory {}", location.display()))?;
+
+        self.dir = location.clone();
+        self.prefix = settings.prefix.clone();
+        self.timeout = (settings.timeout > 0).then(|| Duration::from_secs(settings.timeout));
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        let path = self.path(key);
+        if !self.is_fresh(&path) {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read cache file {}", path.display()))?;
+        let value = self
+            .format
+            .load(&content)
+            .with_context(|| format!("Invalid cache file {}", path.display()))?;
+
+        Ok(Some(value))
+    }
+
+    fn set(&self, key: &str, value: &Value) -> Result<()> {
+        let path = self.path(key);
+        fs::write(&path, self.format.dump(value)?)
+            .with_context(|| format!("Failed to write cache file {}", path.display()))
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let mut keys = self.stored_keys()?;
+        keys.retain(|key| self.is_fresh(&self.path(key)));
+        Ok(keys)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key);
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove cache file {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        for key in self.stored_keys()? {
+            self.delete(&key)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn file_cache(format: FileFormat, dir: &std::path::Path, timeout: u64) -> FileCache {
+        let mut cache = FileCache::new(format);
+        cache
+            .initialize(&CacheSettings {
+                location: Some(dir.to_path_buf()),
+                timeout,
+                prefix: "facts_".to_string(),
+            })
+            .unwrap();
+        cache
+    }
+
+    #[test]
+    fn test_file_cache_round_trip() {
+        for format in [FileFormat::Json, FileFormat::Yaml] {
+            let dir = tempfile::tempdir().unwrap();
+            let cache = file_cache(format, dir.path(), 3600);
+            let facts = json!({"os_family": "Debian", "cpus": 4});
+
+            cache.set("web1", &facts).unwrap();
+            assert!(dir.path().join("facts_web1").exists());
+            assert_eq!(cache.get("web1").unwrap(), Some(facts));
+            assert!(cache.contains("web1").unwrap());
+            assert!(!cache.contains("web2").unwrap());
+            assert_eq!(cache.keys().unwrap(), vec!["web1".to_string()]);
+
+            // a new cache reads the entries written by the first one
+            assert!(file_cache(format, dir.path(), 3600)
+                .contains("web1")
+                .unwrap());
+
+            cache.flush().unwrap();
+            assert!(cache.keys().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_file_cache_expires_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = file_cache(FileFormat::Json, dir.path(), 60);
+        cache.set("web1", &json!({})).unwrap();
+
+        let file = fs::File::options()
+            .write(true)
+            .open(dir.path().join("facts_web1"))
+            .unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+
+        assert_eq!(cache.get("web1").unwrap(), None);
+        assert!(cache.keys().unwrap().is_empty());
+
+        // flushing removes expired files too
+        cache.flush().unwrap();
+        assert!(!dir.path().join("facts_web1").exists());
+    }
+
+    #[test]
+    fn test_file_cache_requires_location() {
+        let mut cache = FileCache::new(FileFormat::Yaml);
+        assert!(cache.initialize(&CacheSettings::default()).is_err());
+    }
+}
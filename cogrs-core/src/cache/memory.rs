@@ -0,0 +1,76 @@
+use anyhow::{anyhow, Result};
+use cogrs_plugins::cache::{CachePlugin, CacheSettings};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps entries in memory, they only live as long as the process.
+#[derive(Default)]
+pub(super) struct MemoryCache {
+    timeout: Option<Duration>,
+    entries: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl MemoryCache {
+    fn is_expired(&self, stored_at: &Instant) -> bool {
+        self.timeout
+            .is_some_and(|timeout| stored_at.elapsed() > timeout)
+    }
+}
+
+impl CachePlugin for MemoryCache {
+    fn initialize(&mut self, settings: &CacheSettings) -> Result<()> {
+        self.timeout = (settings.timeout > 0).then(|| Duration::from_secs(settings.timeout));
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow!("Memory cache lock is poisoned"))?;
+
+        Ok(entries
+            .get(key)
+            .filter(|(stored_at, _)| !self.is_expired(stored_at))
+            .map(|(_, value)| value.clone()))
+    }
+
+    fn set(&self, key: &str, value: &Value) -> Result<()> {
+        self.entries
+            .lock()
+            .map_err(|_| anyhow!("Memory cache lock is poisoned"))?
+            .insert(key.to_string(), (Instant::now(), value.clone()));
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<String>> {
+        let entries = self
+            .entries
+            .lock()
+            .map_err(|_| anyhow!("Memory cache lock is poisoned"))?;
+
+        Ok(entries
+            .iter()
+            .filter(|(_, (stored_at, _))| !self.is_expired(stored_at))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.entries
+            .lock()
+            .map_err(|_| anyhow!("Memory cache lock is poisoned"))?
+            .remove(key);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.entries
+            .lock()
+            .map_err(|_| anyhow!("Memory cache lock is poisoned"))?
+            .clear();
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
 use crate::config::manager::ConfigManager;
+use crate::inventory::plugins::load_inventory_plugins;
+use crate::lookup::load_lookup_plugins;
 use anyhow::{anyhow, Result};
 use cogrs_plugins::plugin_loader;
 use cogrs_plugins::plugin_type::PluginType;
@@ -7,6 +9,7 @@ use std::fs;
 use std::path::PathBuf;
 use tokio::sync::Mutex;
 
+#[allow(async_fn_in_trait)]
 pub trait Cli {
     async fn init() -> Result<()> {
         let config_manager = ConfigManager::instance();
@@ -39,12 +42,23 @@ async fn set_plugin_paths(config_manager: &Mutex<ConfigManager>) -> Result<()> {
         get_plugin_paths(config_manager, "DEFAULT_CONNECTION_PLUGIN_PATH").await?;
     let shell_plugin_paths: Vec<PathBuf> =
         get_plugin_paths(config_manager, "DEFAULT_SHELL_PLUGIN_PATH").await?;
+    let lookup_plugin_paths: Vec<PathBuf> =
+        get_plugin_paths(config_manager, "DEFAULT_LOOKUP_PLUGIN_PATH").await?;
+    let cache_plugin_paths: Vec<PathBuf> =
+        get_plugin_paths(config_manager, "DEFAULT_CACHE_PLUGIN_PATH").await?;
+    let inventory_plugin_paths: Vec<PathBuf> =
+        get_plugin_paths(config_manager, "DEFAULT_INVENTORY_PLUGIN_PATH").await?;
 
     plugin_paths.insert(PluginType::Callback, callback_plugin_paths);
     plugin_paths.insert(PluginType::Connection, connection_plugin_paths);
     plugin_paths.insert(PluginType::Shell, shell_plugin_paths);
+    plugin_paths.insert(PluginType::Lookup, lookup_plugin_paths);
+    plugin_paths.insert(PluginType::Cache, cache_plugin_paths);
+    plugin_paths.insert(PluginType::Inventory, inventory_plugin_paths);
 
     loader.init(plugin_paths).await?;
+    load_lookup_plugins(&mut loader).await?;
+    load_inventory_plugins(&mut loader).await?;
 
     Ok(())
 }
@@ -236,6 +236,20 @@ fn parse_config_value<T: DeserializeOwned>(
         }
     }
 
+    let value = match (value_type.as_deref(), &value) {
+        // values coming from the environment are always strings
+        (Some("boolean" | "bool"), Value::String(s)) => match s.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" | "y" | "t" => Value::Bool(true),
+            "false" | "no" | "off" | "0" | "n" | "f" | "" => Value::Bool(false),
+            _ => bail!("Config key '{}' expects a boolean, got '{}'", key, s),
+        },
+        (Some("integer" | "int"), Value::String(s)) => match s.parse::<i64>() {
+            Ok(i) => Value::Number(i.into()),
+            Err(_) => bail!("Config key '{}' expects an integer, got '{}'", key, s),
+        },
+        _ => value,
+    };
+
     match serde_yaml::from_value::<T>(value) {
         Ok(deserialized_value) => Ok(Some((deserialized_value, origin))),
         Err(err) => bail!(
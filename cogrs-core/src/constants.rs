@@ -2,3 +2,5 @@ pub const LOCALHOST: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
 pub const VAULT_HEADER: &str = "$ANSIBLE_VAULT";
 
 pub const DEFAULT_GATHERING: &str = "implicit";
+
+pub const COGRS_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,5 +1,8 @@
+pub mod connection_vars;
 pub mod failed_state;
+pub(crate) mod file_transfer;
 pub mod host_state;
+pub mod inventory_actions;
 pub mod play_iterator;
 pub mod task_executor;
 pub mod task_queue_manager;
@@ -0,0 +1,316 @@
+use crate::inventory::host::Host;
+use crate::playbook::task::Task;
+use crate::template::Templar;
+use crate::vars::variable::{Number, Variable};
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+
+/// Connection settings of a host resolved from its host and group vars. Each setting is
+/// read from its `cogrs_*` variable, or from the `ansible_*` alias when that isn't set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionVars {
+    task_uuid: String,
+    host: String,
+    port: Option<u16>,
+    remote_user: String,
+    connection: String,
+    private_key_file: Option<String>,
+    /// Comma separated jump hosts, like the ssh `ProxyJump` option.
+    proxy_jump: Option<String>,
+    do_become: bool,
+    become_method: Option<String>,
+    become_user: Option<String>,
+    become_exe: Option<String>,
+    become_flags: Option<String>,
+    become_password: Option<String>,
+}
+
+impl ConnectionVars {
+    pub fn resolve(host: &Host, task: &Task, vars: &IndexMap<String, Variable>) -> Result<Self> {
+        let resolver = Resolver {
+            templar: Templar::new(),
+            vars,
+        };
+
+        Ok(ConnectionVars {
+            task_uuid: task.uuid().to_string(),
+            host: resolver
+                .string(&["host"])?
+                .unwrap_or_else(|| host.address().to_string()),
+            port: resolver.port()?,
+            remote_user: resolver
+                .string(&["user"])?
+                .unwrap_or_else(default_remote_user),
+            connection: resolver
+                .string(&["connection"])?
+                .unwrap_or_else(|| task.connection().to_string()),
+            private_key_file: resolver.string(&["ssh_private_key_file", "private_key_file"])?,
+            proxy_jump: resolver.string(&["ssh_proxy_jump"])?,
+            do_become: resolver.bool(&["become"])?.unwrap_or(false),
+            become_method: resolver.string(&["become_method"])?,
+            become_user: resolver.string(&["become_user"])?,
+            become_exe: resolver.string(&["become_exe"])?,
+            become_flags: resolver.string(&["become_flags"])?,
+            become_password: resolver.string(&["become_password", "become_pass"])?,
+        })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn remote_user(&self) -> &str {
+        &self.remote_user
+    }
+
+    /// Name of the connection plugin to use for the host.
+    pub fn connection(&self) -> &str {
+        &self.connection
+    }
+
+    pub fn private_key_file(&self) -> Option<&str> {
+        self.private_key_file.as_deref()
+    }
+
+    pub fn proxy_jump(&self) -> Option<&str> {
+        self.proxy_jump.as_deref()
+    }
+
+    pub fn do_become(&self) -> bool {
+        self.do_become
+    }
+
+    pub fn become_user(&self) -> Option<&str> {
+        self.become_user.as_deref()
+    }
+
+    /// Returns the parameters of the connection plugin, only the resolved settings so
+    /// other vars of the host can't change the connection.
+    pub fn parameters(&self) -> IndexMap<String, Variable> {
+        let mut parameters = IndexMap::new();
+        let mut set = |key: &str, value: Option<Variable>| {
+            if let Some(value) = value {
+                parameters.insert(key.to_string(), value);
+            }
+        };
+        let string = |value: &Option<String>| value.clone().map(Variable::String);
+
+        set("task_uuid", Some(Variable::String(self.task_uuid.clone())));
+        set("host", Some(Variable::String(self.host.clone())));
+        set(
+            "port",
+            self.port
+                .map(|port| Variable::Number(Number::Int(port as i64))),
+        );
+        set(
+            "remote_user",
+            Some(Variable::String(self.remote_user.clone())),
+        );
+        set(
+            "connection",
+            Some(Variable::String(self.connection.clone())),
+        );
+        set("private_key_file", string(&self.private_key_file));
+        set("proxy_jump", string(&self.proxy_jump));
+        set("become", Some(Variable::Bool(self.do_become)));
+        set("become_method", string(&self.become_method));
+        set("become_user", string(&self.become_user));
+        set("become_exe", string(&self.become_exe));
+        set("become_flags", string(&self.become_flags));
+        set("become_password", string(&self.become_password));
+
+        parameters
+    }
+}
+
+/// The user running cogrs, like `ssh` does without a user.
+fn default_remote_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "root".to_string())
+}
+
+struct Resolver<'a> {
+    templar: Templar,
+    vars: &'a IndexMap<String, Variable>,
+}
+
+impl Resolver<'_> {
+    /// Finds the first of `names` set as `cogrs_<name>`, then as `ansible_<name>`,
+    /// and renders it since inventories often template connection vars.
+    fn get(&self, names: &[&str]) -> Result<Option<(String, Variable)>> {
+        for prefix in ["cogrs_", "ansible_"] {
+            for name in names {
+                let key = format!("{prefix}{name}");
+                if let Some(value) = self.vars.get(&key) {
+                    let value = self.templar.template(value, self.vars)?;
+                    return Ok(Some((key, plain(value)?)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn string(&self, names: &[&str]) -> Result<Option<String>> {
+        match self.get(names)? {
+            None | Some((_, Variable::Null)) => Ok(None),
+            Some((_, Variable::String(value))) => Ok(Some(value)),
+            Some((_, Variable::Path(value))) => Ok(Some(value.to_string_lossy().to_string())),
+            Some((_, Variable::Number(Number::Int(value)))) => Ok(Some(value.to_string())),
+            Some((_, Variable::Number(Number::Float(value)))) => Ok(Some(value.to_string())),
+            Some((_, Variable::Bool(value))) => Ok(Some(value.to_string())),
+            Some((key, value)) => bail!("{key} should be a string, got: {:?}", value),
+        }
+    }
+
+    fn bool(&self, names: &[&str]) -> Result<Option<bool>> {
+        match self.get(names)? {
+            None | Some((_, Variable::Null)) => Ok(None),
+            Some((_, Variable::Bool(value))) => Ok(Some(value)),
+            Some((_, Variable::Number(Number::Int(value)))) => Ok(Some(value != 0)),
+            Some((key, Variable::String(value))) => match value.to_lowercase().as_str() {
+                "yes" | "on" | "true" | "1" => Ok(Some(true)),
+                "no" | "off" | "false" | "0" => Ok(Some(false)),
+                _ => bail!("{key} should be a boolean, got: {value}"),
+            },
+            Some((key, value)) => bail!("{key} should be a boolean, got: {:?}", value),
+        }
+    }
+
+    fn port(&self) -> Result<Option<u16>> {
+        match self.get(&["port"])? {
+            None | Some((_, Variable::Null)) => Ok(None),
+            Some((key, Variable::Number(Number::Int(port)))) => match u16::try_from(port) {
+                Ok(port) => Ok(Some(port)),
+                Err(_) => bail!("{key} is not a valid port: {port}"),
+            },
+            Some((key, Variable::String(port))) => match port.trim().parse::<u16>() {
+                Ok(port) => Ok(Some(port)),
+                Err(_) => bail!("{key} is not a valid port: {port}"),
+            },
+            Some((key, value)) => bail!("{key} is not a valid port: {:?}", value),
+        }
+    }
+}
+
+/// Unwraps `!unsafe` values and decrypts `!vault` ones, such as a vaulted become password.
+fn plain(value: Variable) -> Result<Variable> {
+    match value {
+        Variable::Unsafe(value) => plain(*value),
+        Variable::Vault(value) => Ok(Variable::String(value.decrypt()?.to_string())),
+        value => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playbook::task::{Action, TaskBuilder};
+
+    fn vars(entries: &[(&str, Variable)]) -> IndexMap<String, Variable> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect()
+    }
+
+    fn string(value: &str) -> Variable {
+        Variable::String(value.to_string())
+    }
+
+    #[test]
+    fn test_resolve_prefers_cogrs_vars_over_ansible_aliases() {
+        let host = Host::new("web1");
+        let task =
+            TaskBuilder::new("ping", "ssh", Action::Module("ping".to_string(), None)).build();
+        let vars = vars(&[
+            ("ansible_host", string("10.0.0.1")),
+            ("cogrs_host", string("10.0.0.2")),
+            ("ansible_port", Variable::Number(Number::Int(2222))),
+            ("ansible_user", string("deploy")),
+            ("cogrs_connection", string("local")),
+            ("ansible_ssh_private_key_file", string("~/.ssh/deploy")),
+            ("cogrs_ssh_proxy_jump", string("bastion")),
+            ("ansible_become", string("yes")),
+            ("ansible_become_user", string("{{ app_user }}")),
+            ("app_user", string("app")),
+        ]);
+
+        let connection = ConnectionVars::resolve(&host, &task, &vars).unwrap();
+
+        assert_eq!(connection.host(), "10.0.0.2");
+        assert_eq!(connection.port(), Some(2222));
+        assert_eq!(connection.remote_user(), "deploy");
+        assert_eq!(connection.connection(), "local");
+        assert_eq!(connection.private_key_file(), Some("~/.ssh/deploy"));
+        assert_eq!(connection.proxy_jump(), Some("bastion"));
+        assert!(connection.do_become());
+        assert_eq!(connection.become_user(), Some("app"));
+
+        let parameters = connection.parameters();
+        assert_eq!(parameters["task_uuid"], string(task.uuid()));
+        assert_eq!(parameters["host"], string("10.0.0.2"));
+        assert_eq!(parameters["port"], Variable::Number(Number::Int(2222)));
+        assert_eq!(parameters["become"], Variable::Bool(true));
+        assert!(!parameters.contains_key("become_method"));
+        assert!(!parameters.contains_key("app_user"));
+    }
+
+    #[test]
+    fn test_parameters_ignore_unprefixed_vars() {
+        let host = Host::new("web1");
+        let task =
+            TaskBuilder::new("ping", "ssh", Action::Module("ping".to_string(), None)).build();
+        let vars = vars(&[
+            ("port", Variable::Number(Number::Int(8080))),
+            ("host", string("db1")),
+            ("private_key_file", string("~/.ssh/other")),
+            ("proxy_jump", string("gateway")),
+            ("become_user", string("postgres")),
+        ]);
+
+        let parameters = ConnectionVars::resolve(&host, &task, &vars)
+            .unwrap()
+            .parameters();
+
+        assert_eq!(parameters["host"], string("web1"));
+        for key in ["port", "private_key_file", "proxy_jump", "become_user"] {
+            assert!(!parameters.contains_key(key), "{key} should not be set");
+        }
+    }
+
+    #[test]
+    fn test_resolve_defaults() {
+        let host = Host::new("web1");
+        let task =
+            TaskBuilder::new("ping", "ssh", Action::Module("ping".to_string(), None)).build();
+
+        let connection = ConnectionVars::resolve(&host, &task, &IndexMap::new()).unwrap();
+
+        assert_eq!(connection.host(), "web1");
+        assert_eq!(connection.port(), None);
+        assert_eq!(connection.connection(), "ssh");
+        assert!(!connection.do_become());
+    }
+
+    #[test]
+    fn test_resolve_invalid_values() {
+        let host = Host::new("web1");
+        let task =
+            TaskBuilder::new("ping", "ssh", Action::Module("ping".to_string(), None)).build();
+
+        for entry in [
+            ("cogrs_port", string("ssh")),
+            ("ansible_port", Variable::Number(Number::Int(70000))),
+            ("cogrs_become", string("maybe")),
+        ] {
+            let result = ConnectionVars::resolve(&host, &task, &vars(std::slice::from_ref(&entry)));
+            assert!(result.is_err(), "{:?}", entry);
+        }
+    }
+}
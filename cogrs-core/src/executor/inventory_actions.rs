@@ -0,0 +1,272 @@
+use crate::inventory::manager::InventoryManager;
+use crate::parsing::splitter::parse_kv;
+use crate::playbook::task::{Action, Task};
+use crate::vars::variable::Variable;
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+
+const ADD_HOST_NAME_KEYS: [&str; 3] = ["name", "host", "hostname"];
+const ADD_HOST_GROUP_KEYS: [&str; 3] = ["groups", "group", "groupname"];
+
+/// Actions that change the inventory of a run, they run in the strategy instead of on a host.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum InventoryAction {
+    /// Adds a host, and the groups it is in, to the inventory.
+    AddHost {
+        name: String,
+        groups: Vec<String>,
+        vars: IndexMap<String, Variable>,
+    },
+    /// Adds the host the task runs on to a group named after `key`.
+    GroupBy { key: String, parents: Vec<String> },
+}
+
+impl InventoryAction {
+    /// Returns the inventory action of a task with templated arguments, if it is one.
+    pub fn from_task(task: &Task) -> Result<Option<Self>> {
+        let Action::Module(module_name, args) = task.action() else {
+            return Ok(None);
+        };
+
+        let module_name = module_name
+            .strip_prefix("ansible.builtin.")
+            .unwrap_or(module_name);
+        if module_name != "add_host" && module_name != "group_by" {
+            return Ok(None);
+        }
+
+        let mut args = parse_args(args.as_deref().unwrap_or_default())?;
+
+        let action = if module_name == "add_host" {
+            let Some(name) = take_first(&mut args, &ADD_HOST_NAME_KEYS) else {
+                bail!("add_host requires a 'name' argument");
+            };
+            let groups = take_first(&mut args, &ADD_HOST_GROUP_KEYS)
+                .map(|groups| to_list(&groups))
+                .transpose()?
+                .unwrap_or_default();
+
+            InventoryAction::AddHost {
+                name: to_string(&name)?,
+                groups,
+                vars: args,
+            }
+        } else {
+            let Some(key) = args.shift_remove("key") else {
+                bail!("group_by requires a 'key' argument");
+            };
+            let parents = match args.shift_remove("parents") {
+                Some(parents) => to_list(&parents)?,
+                None => vec!["all".to_string()],
+            };
+
+            InventoryAction::GroupBy {
+                key: to_string(&key)?.replace(' ', "-"),
+                parents,
+            }
+        };
+
+        Ok(Some(action))
+    }
+
+    /// `add_host` runs once per task, whatever the number of hosts in the play.
+    pub fn bypass_host_loop(&self) -> bool {
+        matches!(self, InventoryAction::AddHost { .. })
+    }
+
+    /// Whether applying the action for `host_name` would change the inventory, actions
+    /// repeated for hosts already in their groups don't.
+    pub fn changes(&self, host_name: &str, inventory_manager: &InventoryManager) -> bool {
+        let in_group = |group_name: &str, host_name: &str| {
+            inventory_manager
+                .groups()
+                .get(group_name)
+                .is_some_and(|group| group.host_names().contains(host_name))
+        };
+
+        match self {
+            InventoryAction::AddHost { name, groups, vars } => {
+                let Some(host) = inventory_manager.hosts().get(name) else {
+                    return true;
+                };
+                groups.iter().any(|group_name| !in_group(group_name, name))
+                    || vars
+                        .iter()
+                        .any(|(key, value)| host.vars().get(key) != Some(value))
+            }
+            InventoryAction::GroupBy { key, parents } => {
+                !in_group(key, host_name)
+                    || parents.iter().any(|parent_name| {
+                        !inventory_manager
+                            .groups()
+                            .get(parent_name)
+                            .is_some_and(|parent| parent.has_child_group(key))
+                    })
+            }
+        }
+    }
+
+    /// Applies the action for `host_name`, the host the task runs on.
+    pub fn apply(&self, host_name: &str, inventory_manager: &mut InventoryManager) -> Result<()> {
+        match self {
+            InventoryAction::AddHost { name, groups, vars } => {
+                inventory_manager.add_host(name, groups, vars)
+            }
+            InventoryAction::GroupBy { key, parents } => {
+                inventory_manager.group_by(host_name, key, parents)
+            }
+        }
+    }
+}
+
+/// Module arguments are either a YAML/JSON mapping or `key=value` pairs.
+pub(crate) fn parse_args(args: &str) -> Result<IndexMap<String, Variable>> {
+    if let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(args) {
+        if let Variable::Mapping(mapping) = Variable::try_from(&value)? {
+            return Ok(mapping.into_map());
+        }
+    }
+
+    Ok(parse_kv(args)?
+        .into_iter()
+        .map(|(key, value)| (key, Variable::String(value)))
+        .collect())
+}
+
+fn take_first(args: &mut IndexMap<String, Variable>, keys: &[&str]) -> Option<Variable> {
+    keys.iter().find_map(|key| args.shift_remove(*key))
+}
+
+pub(crate) fn to_string(value: &Variable) -> Result<String> {
+    match value {
+        Variable::String(s) => Ok(s.to_string()),
+        Variable::Number(_) | Variable::Bool(_) => Ok(serde_json::to_string(value)?),
+        _ => bail!("Expected a string, got {:?}", value),
+    }
+}
+
+/// Group lists are either sequences or comma separated strings.
+fn to_list(value: &Variable) -> Result<Vec<String>> {
+    let items = match value {
+        Variable::Sequence(items) => items.iter().map(to_string).collect::<Result<Vec<_>>>()?,
+        value => to_string(value)?
+            .split(',')
+            .map(|item| item.to_string())
+            .collect(),
+    };
+
+    Ok(items
+        .iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playbook::task::TaskBuilder;
+    use crate::vars::variable::Number;
+
+    fn task(module_name: &str, args: &str) -> Task {
+        TaskBuilder::new(
+            "test",
+            "ssh",
+            Action::Module(module_name.to_string(), Some(args.to_string())),
+        )
+        .build()
+    }
+
+    #[test]
+    fn test_add_host_from_task() {
+        let action = InventoryAction::from_task(&task(
+            "add_host",
+            r#"{"hostname": "db1", "groups": "db, new", "port": 2222}"#,
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            action,
+            InventoryAction::AddHost {
+                name: "db1".to_string(),
+                groups: vec!["db".to_string(), "new".to_string()],
+                vars: IndexMap::from([("port".to_string(), Variable::Number(Number::Int(2222)))]),
+            }
+        );
+        assert!(action.bypass_host_loop());
+
+        let action = InventoryAction::from_task(&task("add_host", "name=db2 groups=db"))
+            .unwrap()
+            .unwrap();
+        assert!(
+            matches!(action, InventoryAction::AddHost { name, groups, .. } if name == "db2" && groups == ["db"])
+        );
+
+        assert!(InventoryAction::from_task(&task("add_host", "groups=db")).is_err());
+    }
+
+    #[test]
+    fn test_group_by_from_task() {
+        let action = InventoryAction::from_task(&task(
+            "ansible.builtin.group_by",
+            r#"{"key": "os Debian", "parents": ["linux"]}"#,
+        ))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            action,
+            InventoryAction::GroupBy {
+                key: "os-Debian".to_string(),
+                parents: vec!["linux".to_string()],
+            }
+        );
+        assert!(!action.bypass_host_loop());
+
+        let action = InventoryAction::from_task(&task("group_by", "key=web"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            action,
+            InventoryAction::GroupBy {
+                key: "web".to_string(),
+                parents: vec!["all".to_string()],
+            }
+        );
+
+        assert!(InventoryAction::from_task(&task("ping", "data=pong"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut inventory_manager = InventoryManager::new(&std::env::temp_dir());
+        inventory_manager.init_implicit_groups().unwrap();
+        let add_host = InventoryAction::AddHost {
+            name: "db1".to_string(),
+            groups: vec!["db".to_string()],
+            vars: IndexMap::from([("port".to_string(), Variable::Number(Number::Int(2222)))]),
+        };
+        let group_by = InventoryAction::GroupBy {
+            key: "debian".to_string(),
+            parents: vec!["linux".to_string()],
+        };
+
+        assert!(add_host.changes("web1", &inventory_manager));
+        add_host.apply("web1", &mut inventory_manager).unwrap();
+        assert!(!add_host.changes("web1", &inventory_manager));
+
+        assert!(group_by.changes("db1", &inventory_manager));
+        group_by.apply("db1", &mut inventory_manager).unwrap();
+        assert!(!group_by.changes("db1", &inventory_manager));
+
+        let updated_host = InventoryAction::AddHost {
+            name: "db1".to_string(),
+            groups: vec!["db".to_string()],
+            vars: IndexMap::from([("port".to_string(), Variable::Number(Number::Int(22)))]),
+        };
+        assert!(updated_host.changes("web1", &inventory_manager));
+    }
+}
@@ -7,9 +7,10 @@ use crate::playbook::block::{Block, BlockEntry};
 use crate::playbook::handler::Handler;
 use crate::playbook::play::Play;
 use crate::playbook::task::{Action, Task, TaskBuilder};
+use crate::vars::manager::VariableManager;
 use anyhow::Result;
 use log::{debug, info};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct PlayIterator {
     all_tasks: Vec<Task>,
@@ -20,6 +21,8 @@ pub struct PlayIterator {
     end_play: bool,
     cur_task: usize,
     play: Play,
+    gathering: String,
+    cached_facts_hosts: HashSet<String>,
 }
 
 impl PlayIterator {
@@ -33,14 +36,35 @@ impl PlayIterator {
             end_play: false,
             cur_task: 0,
             play,
+            gathering: DEFAULT_GATHERING.to_string(),
+            cached_facts_hosts: HashSet::new(),
         }
     }
 
-    pub fn init(&mut self, inventory_manager: &InventoryManager) -> Result<()> {
+    /// Sets the fact gathering policy (`implicit`, `explicit` or `smart`), must be called
+    /// before `init`.
+    pub fn set_gathering(&mut self, gathering: &str) {
+        self.gathering = gathering.to_string();
+    }
+
+    pub fn init(
+        &mut self,
+        inventory_manager: &InventoryManager,
+        variable_manager: &VariableManager,
+    ) -> Result<()> {
         let mut setup_block = Block::new();
         let batch = inventory_manager.filter_hosts(self.play.pattern(), None)?;
         self.batch_size = batch.len() as u32;
 
+        // with smart gathering, hosts with fresh facts in the cache are not gathered again
+        if self.gathering == "smart" {
+            for host in &batch {
+                if variable_manager.fact_cache().contains(host.name())? {
+                    self.cached_facts_hosts.insert(host.name().to_string());
+                }
+            }
+        }
+
         let mut setup_task_builder = TaskBuilder::new(
             "Gathering Facts",
             self.play.connection(),
@@ -181,6 +205,18 @@ impl PlayIterator {
         false
     }
 
+    /// Returns whether facts are gathered for `host` under the play's gathering policy.
+    fn should_gather_facts(&self, host: &str) -> bool {
+        let implied = self.play.gather_facts().unwrap_or(true);
+
+        match self.gathering.as_str() {
+            "implicit" if implied => true,
+            "explicit" if self.play.gather_facts().is_some_and(|g| g) => true,
+            "smart" if implied => !self.cached_facts_hosts.contains(host),
+            _ => false,
+        }
+    }
+
     fn get_next_task_from_state(&self, host_state: &mut HostState) -> Result<Option<BlockEntry>> {
         // try and find the next task, given the current state.
         let mut task: Option<BlockEntry> = None;
@@ -203,17 +239,8 @@ impl PlayIterator {
                     // the specified host.
                     if !host_state.is_pending_setup() {
                         host_state.set_pending_setup(true);
-                        let gathering = DEFAULT_GATHERING;
-                        let implied = self.play.gather_facts().unwrap_or(true);
-
-                        let should_gather_facts = match gathering {
-                            "implicit" if implied => true,
-                            "explicit" if self.play.gather_facts().is_some_and(|g| g) => true,
-                            "smart" => true, // TODO: handle smart
-                            _ => false,
-                        };
 
-                        if should_gather_facts {
+                        if self.should_gather_facts(host_state.name()) {
                             let setup_block = self.blocks[0].clone();
                             task = setup_block.get_block_entry(0).map(|e| e.clone());
                         }
@@ -438,3 +465,46 @@ impl PlayIterator {
         &self.play
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::variable::Variable;
+    use indexmap::IndexMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_smart_gathering_skips_hosts_with_cached_facts() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/inventory");
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&base_dir);
+        variable_manager
+            .set_host_facts(
+                "one.example.com",
+                IndexMap::from([("os_family".to_string(), Variable::String("Debian".into()))]),
+            )
+            .unwrap();
+
+        let tasks =
+            vec![TaskBuilder::new("Ping", "ssh", Action::Module("ping".to_string(), None)).build()];
+        let play = Play::builder("smart", &[])
+            .pattern("one.example.com,bar.example.com")
+            .tasks(&tasks)
+            .build();
+
+        let mut iterator = PlayIterator::new(play);
+        iterator.set_gathering("smart");
+        iterator
+            .init(&inventory_manager, &variable_manager)
+            .unwrap();
+
+        assert!(!iterator.should_gather_facts("one.example.com"));
+        assert!(iterator.should_gather_facts("bar.example.com"));
+
+        iterator.set_gathering("implicit");
+        assert!(iterator.should_gather_facts("one.example.com"));
+    }
+}
@@ -1,7 +1,10 @@
+use crate::executor::connection_vars::ConnectionVars;
+use crate::executor::file_transfer::FileTransfer;
 use crate::executor::worker_message::WorkerMessage;
 use crate::inventory::host::Host;
 use crate::playbook::task::Task;
 use crate::vars::variable::Variable;
+use crate::vault::secrets::vault_secrets;
 use anyhow::Result;
 use cogrs_modules::action_handler::ActionHandler;
 use cogrs_modules::task_result::TaskResult;
@@ -15,15 +18,21 @@ pub struct TaskExecutor;
 
 #[cfg(feature = "static-plugins")]
 async fn load_plugins(
-    task: &Task,
-    mut task_vars: IndexMap<String, Variable>,
+    connection: &ConnectionVars,
 ) -> Result<(Box<dyn ConnectionPlugin>, Box<dyn ShellPlugin>)> {
     use sh_lib::Sh;
     use ssh_lib::Ssh;
 
+    if connection.connection() != "ssh" {
+        anyhow::bail!(
+            "Connection plugin '{}' is not available, only 'ssh' is built in",
+            connection.connection()
+        );
+    }
+
     let mut connection_plugin = Box::new(Ssh::default());
 
-    let parameters = serde_json::to_string(&task_vars)?;
+    let parameters = serde_json::to_string(&connection.parameters())?;
     connection_plugin.initialize(&parameters)?;
 
     let shell_plugin = Box::new(Sh::default());
@@ -32,15 +41,16 @@ async fn load_plugins(
 
 #[cfg(not(feature = "static-plugins"))]
 async fn load_plugins(
-    task: &Task,
-    mut task_vars: IndexMap<String, Variable>,
+    connection: &ConnectionVars,
 ) -> Result<(Box<dyn ConnectionPlugin>, Box<dyn ShellPlugin>)> {
     let plugin_loader = cogrs_plugins::plugin_loader::PluginLoader::instance();
     let mut loader = plugin_loader.lock().await;
 
     let shell_plugin = loader.get_shell_plugin("sh").await?;
-    let mut connection_plugin = loader.get_connection_plugin(task.connection()).await?;
-    let parameters = serde_json::to_string(&task_vars)?;
+    let mut connection_plugin = loader
+        .get_connection_plugin(connection.connection())
+        .await?;
+    let parameters = serde_json::to_string(&connection.parameters())?;
     connection_plugin.initialize(&parameters)?;
     Ok((connection_plugin, shell_plugin))
 }
@@ -54,7 +64,7 @@ impl TaskExecutor {
         &self,
         host: &Host,
         task: &Task,
-        mut task_vars: IndexMap<String, Variable>,
+        task_vars: IndexMap<String, Variable>,
         sender: &mpsc::Sender<WorkerMessage>,
     ) -> Result<TaskResult> {
         debug!(
@@ -63,26 +73,21 @@ impl TaskExecutor {
             host.name()
         );
 
-        task_vars.insert(
-            String::from("host"),
-            Variable::String(host.address().to_string()),
-        );
-
-        task_vars.insert(
-            String::from("remote_user"),
-            Variable::String("test-user".to_string()),
-        );
+        let connection = ConnectionVars::resolve(host, task, &task_vars)?;
 
         // TODO: handle conditionals
 
-        let result = TaskResult::new(host.name(), task.uuid());
         // TODO: handle with_*
         // TODO: get connection plugin
-        let (connection_plugin, shell_plugin) = load_plugins(task, task_vars).await?;
+        let (connection_plugin, shell_plugin) = load_plugins(&connection).await?;
         let action_handler = ActionHandler::new(connection_plugin, shell_plugin);
-        action_handler.run().await?;
-
-        Ok(result)
+        match FileTransfer::from_task(task)? {
+            Some(transfer) => {
+                transfer.run(&action_handler, &vault_secrets())?;
+                Ok(TaskResult::new(host.name(), task.uuid()))
+            }
+            None => action_handler.run().await,
+        }
     }
 
     fn get_connection(current_connection: &str) {}
@@ -1,3 +1,4 @@
+use crate::config::manager::ConfigManager;
 use crate::executor::play_iterator::PlayIterator;
 use crate::inventory::host::Host;
 use crate::inventory::manager::InventoryManager;
@@ -10,6 +11,7 @@ use cogrs_plugins::callback::{CallbackPlugin, EventType};
 use serde_json::Value;
 use std::cmp::min;
 use std::collections::HashMap;
+use std::mem;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -52,25 +54,44 @@ impl TaskQueueManager {
     pub async fn run(
         &mut self,
         play: Play,
-        variable_manager: &VariableManager,
-        inventory_manager: &InventoryManager,
+        variable_manager: Arc<VariableManager>,
+        inventory_manager: &mut InventoryManager,
     ) -> Result<()> {
         self.load_callbacks().await?;
-        let all_vars = variable_manager.get_vars(Some(&play), None, None, None, true, true);
+        let all_vars = variable_manager.get_vars(Some(&play), None, None, None, true)?;
 
         self.emit_event(EventType::PlaybookOnPlayStart, None).await;
 
         let strategy = *play.strategy();
 
+        let gathering = ConfigManager::instance()
+            .lock()
+            .await
+            .get_config_value::<String>("DEFAULT_GATHERING")?;
+
         let mut play_iterator = PlayIterator::new(play);
-        play_iterator.init(inventory_manager)?;
+        if let Some((gathering, _)) = gathering {
+            play_iterator.set_gathering(&gathering);
+        }
+        play_iterator.init(inventory_manager, &variable_manager)?;
 
         self.forks = min(self.forks, play_iterator.batch_size());
 
         match strategy {
             Strategy::Linear => {
-                let mut strategy = LinearStrategy::new(self, inventory_manager, variable_manager);
-                strategy.run(&mut play_iterator).await?;
+                // the strategy shares the inventory with the `hostvars` of its templates, it
+                // is handed back once the play is done
+                let base_dir = inventory_manager.get_base_dir().clone();
+                let inventory = mem::replace(inventory_manager, InventoryManager::new(&base_dir));
+                let mut strategy = LinearStrategy::new(self, inventory, variable_manager);
+                let result = strategy.run(&mut play_iterator).await;
+                // the inventory is handed back even when the play failed, whose error
+                // comes first
+                let restored = strategy
+                    .into_inventory_manager()
+                    .map(|inventory| *inventory_manager = inventory);
+                result?;
+                restored?;
             }
             Strategy::Free => {
                 todo!()
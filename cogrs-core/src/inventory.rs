@@ -1,7 +1,13 @@
+pub mod builder;
+pub mod cache;
+pub mod export;
 pub mod group;
 pub mod host;
+mod index;
+pub mod lint;
 pub mod manager;
 mod patterns;
+pub mod plugins;
 pub mod utils;
 
 use serde_yaml::Value;
@@ -0,0 +1,168 @@
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::vars::variable::{combine_variables, ConflictResolution, Variable};
+use anyhow::{anyhow, Result};
+use cogrs_plugins::inventory::InventoryData;
+use indexmap::IndexMap;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// [`InventoryData`] over the groups and hosts being parsed, new hosts get `inventory_file`
+/// and `inventory_dir` pointing at the source that defined them.
+pub struct InventoryBuilder<'a> {
+    source: &'a Path,
+    groups: &'a mut IndexMap<String, Group>,
+    hosts: &'a mut IndexMap<String, Host>,
+}
+
+impl<'a> InventoryBuilder<'a> {
+    pub fn new(
+        source: &'a Path,
+        groups: &'a mut IndexMap<String, Group>,
+        hosts: &'a mut IndexMap<String, Host>,
+    ) -> Self {
+        InventoryBuilder {
+            source,
+            groups,
+            hosts,
+        }
+    }
+}
+
+impl InventoryData for InventoryBuilder<'_> {
+    fn add_group(&mut self, group: &str) -> Result<()> {
+        self.groups
+            .entry(group.to_string())
+            .or_insert_with(|| Group::new(group));
+        Ok(())
+    }
+
+    fn add_host(&mut self, host_name: &str, group_name: Option<&str>) -> Result<()> {
+        if !self.hosts.contains_key(host_name) {
+            // like Ansible, the first source a host is found in is its inventory_file
+            let mut host = Host::new(host_name);
+            set_inventory_source_vars(&mut host, self.source)?;
+            self.hosts.insert(host_name.to_string(), host);
+        }
+        let host = self
+            .hosts
+            .get_mut(host_name)
+            .ok_or_else(|| anyhow!("Unknown host: '{host_name}'"))?;
+
+        if let Some(group_name) = group_name {
+            let group = self
+                .groups
+                .entry(group_name.to_string())
+                .or_insert_with(|| Group::new(group_name));
+            group.add_host(host.name());
+            host.add_group(group.name());
+
+            // the group may already be a child of other groups
+            let ancestors = self.groups[group_name].get_ancestors(self.groups, false);
+            if let Some(host) = self.hosts.get_mut(host_name) {
+                host.populate_ancestors(ancestors);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_child(&mut self, group_name: &str, child_group_name: &str) -> Result<()> {
+        self.add_group(group_name)?;
+        self.add_group(child_group_name)?;
+
+        let mut parent_group = self.groups[group_name].clone();
+        let mut child_group = self.groups[child_group_name].clone();
+
+        parent_group.add_child_group(&mut child_group, self.groups, self.hosts)?;
+
+        self.groups
+            .insert(parent_group.name().to_string(), parent_group);
+        self.groups
+            .insert(child_group.name().to_string(), child_group);
+
+        Ok(())
+    }
+
+    fn set_group_variable(&mut self, group_name: &str, key: &str, value: &Value) -> Result<()> {
+        let value = Variable::try_from(value)?;
+        self.groups
+            .get_mut(group_name)
+            .ok_or_else(|| anyhow!("Unknown group: '{group_name}'"))?
+            .set_variable(key, value);
+        Ok(())
+    }
+
+    fn set_host_variable(&mut self, host_name: &str, key: &str, value: &Value) -> Result<()> {
+        let value = Variable::try_from(value)?;
+        self.hosts
+            .get_mut(host_name)
+            .ok_or_else(|| anyhow!("Unknown host: '{host_name}'"))?
+            .set_var(key, &value);
+        Ok(())
+    }
+
+    fn hosts(&self) -> Vec<String> {
+        self.hosts.keys().cloned().collect()
+    }
+
+    fn host_vars(&self, host_name: &str) -> Result<Mapping> {
+        let host = self
+            .hosts
+            .get(host_name)
+            .ok_or_else(|| anyhow!("Unknown host: '{host_name}'"))?;
+
+        let mut group_names: HashSet<String> = HashSet::new();
+        for group_name in host.groups() {
+            if let Some(group) = self.groups.get(group_name) {
+                group_names.extend(group.get_ancestors(self.groups, true));
+            }
+        }
+
+        let mut groups: Vec<&Group> = group_names
+            .iter()
+            .filter_map(|name| self.groups.get(name))
+            .collect();
+        groups.sort_by(|a, b| {
+            a.depth()
+                .cmp(&b.depth())
+                .then(a.priority().cmp(&b.priority()))
+                .then(a.name().cmp(b.name()))
+        });
+
+        let mut vars = IndexMap::new();
+        for group in groups {
+            vars = combine_variables(&vars, group.get_vars(), &ConflictResolution::Replace);
+        }
+        vars = combine_variables(&vars, host.vars(), &ConflictResolution::Replace);
+
+        Ok(vars
+            .iter()
+            .map(|(key, value)| (Value::String(key.to_string()), Value::from(value)))
+            .collect())
+    }
+}
+
+/// Sets `inventory_file` and `inventory_dir` for a host defined in `source`. Hosts that
+/// already have them keep them, the first source a host is found in wins.
+pub(crate) fn set_inventory_source_vars(host: &mut Host, source: &Path) -> Result<()> {
+    if host.vars().contains_key("inventory_file") {
+        return Ok(());
+    }
+
+    let parent = source
+        .parent()
+        .map(|p| p.to_str().unwrap_or(""))
+        .ok_or(anyhow!("Invalid inventory source path: {:?}", source))?;
+
+    if let Some(source) = source.to_str() {
+        host.set_var("inventory_file", &Variable::String(source.to_string()));
+        host.set_var("inventory_dir", &Variable::String(parent.to_string()));
+    } else {
+        host.set_var("inventory_file", &Variable::Null);
+        host.set_var("inventory_dir", &Variable::Null);
+    }
+
+    Ok(())
+}
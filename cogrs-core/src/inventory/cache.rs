@@ -0,0 +1,402 @@
+use crate::cache::get_cache_plugin;
+use crate::config::manager::ConfigManager;
+use crate::inventory::builder::InventoryBuilder;
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use anyhow::{bail, Result};
+use cogrs_plugins::cache::{CachePlugin, CacheSettings};
+use cogrs_plugins::inventory::InventoryData;
+use indexmap::IndexMap;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use serde_yaml::value::{Tag, TaggedValue};
+use serde_yaml::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CACHE_PLUGIN: &str = "jsonfile";
+const DEFAULT_CACHE_PREFIX: &str = "cogrs_inventory_";
+const DEFAULT_CACHE_TIMEOUT: u64 = 3600;
+
+/// Groups and hosts parsed from dynamic inventory sources (executable scripts and inventory
+/// plugins), stored in a cache backend so the next runs restore them without running the
+/// source again.
+pub struct InventoryCache {
+    plugin: Box<dyn CachePlugin>,
+}
+
+impl InventoryCache {
+    pub fn new(plugin: Box<dyn CachePlugin>) -> Self {
+        InventoryCache { plugin }
+    }
+
+    /// Creates the cache backend from the `INVENTORY_CACHE_*` settings.
+    pub async fn from_config(config_manager: &ConfigManager) -> Result<Self> {
+        let name = config_manager
+            .get_config_value::<String>("INVENTORY_CACHE_PLUGIN")?
+            .map_or_else(|| DEFAULT_CACHE_PLUGIN.to_string(), |(name, _)| name);
+
+        let settings = CacheSettings {
+            location: config_manager
+                .get_config_value::<PathBuf>("INVENTORY_CACHE_PLUGIN_CONNECTION")?
+                .map(|(location, _)| location),
+            timeout: config_manager
+                .get_config_value::<u64>("INVENTORY_CACHE_TIMEOUT")?
+                .map_or(DEFAULT_CACHE_TIMEOUT, |(timeout, _)| timeout),
+            prefix: config_manager
+                .get_config_value::<String>("INVENTORY_CACHE_PLUGIN_PREFIX")?
+                .map_or_else(|| DEFAULT_CACHE_PREFIX.to_string(), |(prefix, _)| prefix),
+        };
+
+        let mut plugin = get_cache_plugin(&name).await?;
+        plugin.initialize(&settings)?;
+
+        Ok(InventoryCache { plugin })
+    }
+
+    /// Parses a source with `parse`, or restores it from the cache. Entries are keyed by the
+    /// source path and the `arguments` it is parsed with, like the options of a plugin.
+    pub(crate) fn parse(
+        &self,
+        source: &Path,
+        arguments: &str,
+        groups: &mut IndexMap<String, Group>,
+        hosts: &mut IndexMap<String, Host>,
+        parse: impl FnOnce(&mut IndexMap<String, Group>, &mut IndexMap<String, Host>) -> Result<()>,
+    ) -> Result<()> {
+        let key = cache_key(source, arguments);
+
+        match self.get(&key) {
+            Ok(Some(cached)) => {
+                debug!("Using cached inventory of {}", source.display());
+                return cached.restore(source, groups, hosts);
+            }
+            Ok(None) => {}
+            Err(e) => warn!(
+                "Ignoring the cached inventory of {}: {e:#}",
+                source.display()
+            ),
+        }
+
+        // sources are parsed on their own, so the cache only holds what they define
+        let mut source_groups = IndexMap::new();
+        let mut source_hosts = IndexMap::new();
+        parse(&mut source_groups, &mut source_hosts)?;
+
+        let cached = CachedInventory::new(&source_groups, &source_hosts);
+        if let Err(e) = self.set(&key, &cached) {
+            warn!(
+                "Unable to cache the inventory of {}: {e:#}",
+                source.display()
+            );
+        }
+
+        cached.restore(source, groups, hosts)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<CachedInventory>> {
+        match self.plugin.get(key)? {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn set(&self, key: &str, cached: &CachedInventory) -> Result<()> {
+        self.plugin.set(key, &serde_json::to_value(cached)?)
+    }
+
+    /// Removes every cached inventory, sources are parsed again on the next run.
+    pub fn flush(&self) -> Result<()> {
+        self.plugin.flush()
+    }
+}
+
+fn cache_key(source: &Path, arguments: &str) -> String {
+    let source = source
+        .canonicalize()
+        .unwrap_or_else(|_| source.to_path_buf());
+
+    let mut hasher = Sha256::new();
+    hasher.update(source.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(arguments.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// The groups and hosts of a source, with variables in JSON. Vault encrypted and unsafe
+/// values are stored as `{"__ansible_vault": ...}` and `{"__ansible_unsafe": ...}`, so
+/// secrets never end up decrypted in the cache.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedInventory {
+    groups: IndexMap<String, CachedGroup>,
+    hosts: IndexMap<String, IndexMap<String, JsonValue>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedGroup {
+    #[serde(default)]
+    hosts: Vec<String>,
+    #[serde(default)]
+    children: Vec<String>,
+    #[serde(default)]
+    vars: IndexMap<String, JsonValue>,
+}
+
+impl CachedInventory {
+    fn new(groups: &IndexMap<String, Group>, hosts: &IndexMap<String, Host>) -> Self {
+        let groups = groups
+            .iter()
+            .map(|(name, group)| {
+                let cached = CachedGroup {
+                    hosts: group.get_hosts(groups, false).unwrap_or_default(),
+                    children: group.child_groups.clone(),
+                    vars: group
+                        .get_vars()
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), to_json(&Value::from(value))))
+                        .collect(),
+                };
+                (name.to_string(), cached)
+            })
+            .collect();
+
+        let hosts = hosts
+            .iter()
+            .map(|(name, host)| {
+                let vars = host
+                    .vars()
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), to_json(&Value::from(value))))
+                    .collect();
+                (name.to_string(), vars)
+            })
+            .collect();
+
+        CachedInventory { groups, hosts }
+    }
+
+    /// Adds the cached groups and hosts to the inventory, the same way the source would.
+    fn restore(
+        &self,
+        source: &Path,
+        groups: &mut IndexMap<String, Group>,
+        hosts: &mut IndexMap<String, Host>,
+    ) -> Result<()> {
+        let mut inventory = InventoryBuilder::new(source, groups, hosts);
+
+        for (group_name, group) in &self.groups {
+            inventory.add_group(group_name)?;
+            for (key, value) in &group.vars {
+                inventory.set_group_variable(group_name, key, &from_json(value))?;
+            }
+        }
+
+        let mut host_groups: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (group_name, group) in &self.groups {
+            for host_name in &group.hosts {
+                host_groups
+                    .entry(host_name.as_str())
+                    .or_default()
+                    .push(group_name.as_str());
+            }
+        }
+
+        for (host_name, vars) in &self.hosts {
+            match host_groups.get(host_name.as_str()) {
+                Some(group_names) => {
+                    for group_name in group_names {
+                        inventory.add_host(host_name, Some(group_name))?;
+                    }
+                }
+                None => inventory.add_host(host_name, None)?,
+            }
+            for (key, value) in vars {
+                inventory.set_host_variable(host_name, key, &from_json(value))?;
+            }
+        }
+
+        for (group_name, group) in &self.groups {
+            for child_name in &group.children {
+                if !self.groups.contains_key(child_name) {
+                    bail!("Cached group '{group_name}' has an unknown child group '{child_name}'");
+                }
+                inventory.add_child(group_name, child_name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Bool(*b),
+        Value::Number(n) => serde_json::to_value(n).unwrap_or(JsonValue::Null),
+        Value::String(s) => JsonValue::String(s.to_string()),
+        Value::Sequence(s) => JsonValue::Array(s.iter().map(to_json).collect()),
+        Value::Mapping(m) => JsonValue::Object(
+            m.iter()
+                .filter_map(|(key, value)| Some((key.as_str()?.to_string(), to_json(value))))
+                .collect(),
+        ),
+        Value::Tagged(tagged) => JsonValue::Object(serde_json::Map::from_iter([(
+            format!(
+                "__ansible_{}",
+                tagged.tag.to_string().trim_start_matches('!')
+            ),
+            to_json(&tagged.value),
+        )])),
+    }
+}
+
+fn from_json(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Bool(*b),
+        JsonValue::Number(n) => serde_yaml::to_value(n).unwrap_or(Value::Null),
+        JsonValue::String(s) => Value::String(s.to_string()),
+        JsonValue::Array(a) => Value::Sequence(a.iter().map(from_json).collect()),
+        JsonValue::Object(o) => match o.iter().next() {
+            Some((key, value)) if o.len() == 1 && key.starts_with("__ansible_") => {
+                Value::Tagged(Box::new(TaggedValue {
+                    tag: Tag::new(key.trim_start_matches("__ansible_")),
+                    value: from_json(value),
+                }))
+            }
+            _ => Value::Mapping(
+                o.iter()
+                    .map(|(key, value)| (Value::String(key.to_string()), from_json(value)))
+                    .collect(),
+            ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::get_builtin_cache_plugin;
+    use crate::inventory::manager::InventoryManager;
+    use crate::vars::variable::{Number, Variable};
+    use crate::vault::secrets::VaultSecret;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn inventory_cache(dir: &Path) -> InventoryCache {
+        let mut plugin = get_builtin_cache_plugin("jsonfile").unwrap();
+        plugin
+            .initialize(&CacheSettings {
+                location: Some(dir.join("cache")),
+                timeout: 3600,
+                prefix: DEFAULT_CACHE_PREFIX.to_string(),
+            })
+            .unwrap();
+        InventoryCache::new(plugin)
+    }
+
+    fn write_script(path: &Path, calls: &Path) {
+        let script = format!(
+            r#"#!/bin/sh
+echo run >> {}
+cat <<'EOF'
+{{
+  "web": {{"hosts": ["web1", "web2"], "vars": {{"http_port": 8080}}}},
+  "datacenter": {{"children": ["web"], "vars": {{"dc": "eu-1"}}}},
+  "_meta": {{"hostvars": {{"web1": {{"weight": 10}}}}}}
+}}
+EOF
+"#,
+            calls.display()
+        );
+        std::fs::write(path, script).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    fn parse(dir: &Path, source: &Path) -> InventoryManager {
+        let mut inventory = InventoryManager::new(&dir.to_path_buf());
+        inventory.set_cache(Some(inventory_cache(dir)));
+        inventory
+            .parse_sources(Some(&[source.to_str().unwrap().to_string()]))
+            .unwrap();
+        inventory
+    }
+
+    #[test]
+    fn test_script_inventory_is_restored_from_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("inventory.sh");
+        let calls = dir.path().join("calls");
+        write_script(&script, &calls);
+
+        let first = parse(dir.path(), &script);
+        let second = parse(dir.path(), &script);
+
+        // the script only ran for the first inventory
+        assert_eq!(std::fs::read_to_string(&calls).unwrap().lines().count(), 1);
+
+        for inventory in [&first, &second] {
+            let groups = inventory.groups();
+            assert_eq!(
+                groups["web"].get_hosts(groups, false).unwrap(),
+                vec!["web1", "web2"]
+            );
+            assert!(groups["datacenter"].has_child_group("web"));
+            assert!(groups["all"].has_child_group("datacenter"));
+            assert_eq!(
+                groups["web"].get_vars()["http_port"],
+                Variable::Number(Number::Int(8080))
+            );
+            assert_eq!(
+                inventory.hosts()["web1"].vars()["weight"],
+                Variable::Number(Number::Int(10))
+            );
+            assert!(inventory.hosts()["web2"]
+                .groups()
+                .contains(&"datacenter".to_string()));
+        }
+
+        inventory_cache(dir.path()).flush().unwrap();
+        parse(dir.path(), &script);
+        assert_eq!(std::fs::read_to_string(&calls).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_static_sources_are_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("hosts.ini");
+        std::fs::write(&source, "[web]\nweb1\n").unwrap();
+
+        parse(dir.path(), &source);
+        std::fs::write(&source, "[web]\nweb2\n").unwrap();
+        let inventory = parse(dir.path(), &source);
+
+        assert!(inventory.hosts().contains_key("web2"));
+        assert!(!inventory.hosts().contains_key("web1"));
+    }
+
+    #[test]
+    fn test_cached_values_keep_tags() {
+        let secret = VaultSecret::new("default", "secret");
+        let vaulted = Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("vault"),
+            value: Value::String(secret.encrypt("hunter2").unwrap()),
+        }));
+        let unsafe_value = Value::Tagged(Box::new(TaggedValue {
+            tag: Tag::new("unsafe"),
+            value: Value::String("{{ not_templated }}".to_string()),
+        }));
+
+        for value in [vaulted, unsafe_value] {
+            let json = to_json(&value);
+            assert!(!json.to_string().contains("hunter2"));
+            assert_eq!(from_json(&json), value);
+        }
+    }
+}
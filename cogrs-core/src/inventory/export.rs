@@ -0,0 +1,637 @@
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::inventory::manager::InventoryManager;
+use crate::vars::manager::VariableManager;
+use crate::vars::variable::{combine_variables, ConflictResolution, Variable, VarsEntity};
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use toml_edit::{Array, DocumentMut, InlineTable, Item, Table};
+
+/// Output formats of [`InventoryExporter`] values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// Renders the parsed inventory like `ansible-inventory` does: the `--list` JSON with
+/// `_meta.hostvars`, the variables of a host, the `--graph` tree and YAML or TOML inventories.
+///
+/// Hosts get their variables as seen by a play, from every group they belong to and from
+/// `group_vars`/`host_vars`. When exporting, groups keep their own variables and hosts only
+/// get theirs, so the result can be used as an inventory source.
+pub struct InventoryExporter<'a> {
+    inventory: &'a InventoryManager,
+    variable_manager: &'a VariableManager,
+    export: bool,
+}
+
+impl<'a> InventoryExporter<'a> {
+    pub fn new(inventory: &'a InventoryManager, variable_manager: &'a VariableManager) -> Self {
+        InventoryExporter {
+            inventory,
+            variable_manager,
+            export: false,
+        }
+    }
+
+    pub fn export(mut self, export: bool) -> Self {
+        self.export = export;
+        self
+    }
+
+    /// The `--list` output, one entry per group and the host variables under `_meta`.
+    pub fn list(&self) -> Result<Value> {
+        let mut hostvars = Mapping::new();
+        for (host_name, host) in self.inventory.hosts() {
+            let vars = self.host_vars(host)?;
+            if !vars.is_empty() {
+                hostvars.insert(Value::from(host_name.as_str()), vars_to_value(&vars));
+            }
+        }
+
+        let mut results = Mapping::new();
+        results.insert(
+            Value::from("_meta"),
+            Value::Mapping(Mapping::from_iter([(
+                Value::from("hostvars"),
+                Value::Mapping(hostvars),
+            )])),
+        );
+
+        let mut seen = HashSet::new();
+        self.list_group(self.group("all")?, &mut seen, &mut results)?;
+
+        Ok(Value::Mapping(results))
+    }
+
+    fn list_group(
+        &self,
+        group: &Group,
+        seen: &mut HashSet<String>,
+        results: &mut Mapping,
+    ) -> Result<()> {
+        if !seen.insert(group.name().to_string()) {
+            return Ok(());
+        }
+
+        let mut entry = Mapping::new();
+        let hosts = group.get_hosts(self.inventory.groups(), false)?;
+        if !hosts.is_empty() {
+            entry.insert(Value::from("hosts"), strings_to_value(&hosts));
+        }
+
+        let children = self.child_groups(group);
+        if !children.is_empty() {
+            let names: Vec<&str> = children.iter().map(|child| child.name()).collect();
+            entry.insert(Value::from("children"), strings_to_value(&names));
+        }
+
+        if self.export {
+            let vars = self.group_vars(group)?;
+            if !vars.is_empty() {
+                entry.insert(Value::from("vars"), vars_to_value(&vars));
+            }
+        }
+
+        if !entry.is_empty() {
+            results.insert(Value::from(group.name()), Value::Mapping(entry));
+        }
+
+        for child in children {
+            self.list_group(child, seen, results)?;
+        }
+
+        Ok(())
+    }
+
+    /// The variables of a single host.
+    pub fn host(&self, host_name: &str) -> Result<Value> {
+        let host = self
+            .inventory
+            .hosts()
+            .get(host_name)
+            .ok_or_else(|| anyhow!("Could not find host: '{host_name}'"))?;
+
+        Ok(vars_to_value(&self.host_vars(host)?))
+    }
+
+    /// The inventory in the nested YAML inventory layout, starting at `all`. Hosts found in
+    /// several groups only get their variables the first time they are listed.
+    pub fn inventory(&self) -> Result<Value> {
+        let mut seen = HashSet::new();
+        let all = self.group("all")?;
+
+        Ok(Value::Mapping(Mapping::from_iter([(
+            Value::from(all.name()),
+            self.inventory_group(all, &mut seen)?,
+        )])))
+    }
+
+    fn inventory_group(&self, group: &Group, seen: &mut HashSet<String>) -> Result<Value> {
+        let mut entry = Mapping::new();
+
+        let mut children = Mapping::new();
+        for child in self.child_groups(group) {
+            children.insert(
+                Value::from(child.name()),
+                self.inventory_group(child, seen)?,
+            );
+        }
+        if !children.is_empty() {
+            entry.insert(Value::from("children"), Value::Mapping(children));
+        }
+
+        let hosts = self.group_hosts(group, seen)?;
+        if !hosts.is_empty() {
+            entry.insert(Value::from("hosts"), Value::Mapping(hosts));
+        }
+
+        if self.export {
+            let vars = self.group_vars(group)?;
+            if !vars.is_empty() {
+                entry.insert(Value::from("vars"), vars_to_value(&vars));
+            }
+        }
+
+        Ok(match entry.is_empty() {
+            true => Value::Null,
+            false => Value::Mapping(entry),
+        })
+    }
+
+    /// The inventory in the flat TOML inventory layout, one table per group listing the names
+    /// of its children.
+    pub fn flat_inventory(&self) -> Result<Value> {
+        let mut seen_groups = HashSet::new();
+        let mut seen_hosts = HashSet::new();
+        let mut results = Mapping::new();
+
+        self.flat_group(
+            self.group("all")?,
+            &mut seen_groups,
+            &mut seen_hosts,
+            &mut results,
+        )?;
+
+        Ok(Value::Mapping(results))
+    }
+
+    fn flat_group(
+        &self,
+        group: &Group,
+        seen_groups: &mut HashSet<String>,
+        seen_hosts: &mut HashSet<String>,
+        results: &mut Mapping,
+    ) -> Result<()> {
+        if !seen_groups.insert(group.name().to_string()) {
+            return Ok(());
+        }
+
+        let mut entry = Mapping::new();
+        let children = self.child_groups(group);
+        if group.name() != "all" && !children.is_empty() {
+            let names: Vec<&str> = children.iter().map(|child| child.name()).collect();
+            entry.insert(Value::from("children"), strings_to_value(&names));
+        }
+
+        let hosts = self.group_hosts(group, seen_hosts)?;
+        if !hosts.is_empty() {
+            entry.insert(Value::from("hosts"), Value::Mapping(hosts));
+        }
+
+        if self.export {
+            let vars = self.group_vars(group)?;
+            if !vars.is_empty() {
+                entry.insert(Value::from("vars"), vars_to_value(&vars));
+            }
+        }
+
+        if !entry.is_empty() {
+            results.insert(Value::from(group.name()), Value::Mapping(entry));
+        }
+
+        for child in children {
+            self.flat_group(child, seen_groups, seen_hosts, results)?;
+        }
+
+        Ok(())
+    }
+
+    /// The `--graph` tree of a group, with the variables of its hosts and groups when `show_vars`
+    /// is set.
+    pub fn graph(&self, group_name: &str, show_vars: bool) -> Result<String> {
+        let group = self
+            .inventory
+            .groups()
+            .get(group_name)
+            .ok_or_else(|| anyhow!("Pattern must be a valid group name when using --graph, could not find: '{group_name}'"))?;
+
+        // every group below the one graphed, listed under each of its parents in the tree
+        let groups = self.inventory.groups();
+        let subtree = group.walk_relationships(groups, false, true);
+        let mut children: HashMap<&str, Vec<&Group>> = HashMap::new();
+        for child in subtree.iter().filter_map(|name| groups.get(name)) {
+            for parent in child.parent_groups() {
+                if subtree.contains(parent) {
+                    children.entry(parent.as_str()).or_default().push(child);
+                }
+            }
+        }
+        for kids in children.values_mut() {
+            kids.sort_by(|a, b| a.name().cmp(b.name()));
+        }
+
+        let mut lines = Vec::new();
+        self.graph_group(group, &children, 0, show_vars, &mut lines)?;
+
+        Ok(lines.join("\n"))
+    }
+
+    fn graph_group(
+        &self,
+        group: &Group,
+        children: &HashMap<&str, Vec<&Group>>,
+        depth: usize,
+        show_vars: bool,
+        lines: &mut Vec<String>,
+    ) -> Result<()> {
+        lines.push(graph_name(&format!("@{}:", group.name()), depth));
+
+        for child in children.get(group.name()).into_iter().flatten() {
+            self.graph_group(child, children, depth + 1, show_vars, lines)?;
+        }
+
+        if group.name() != "all" {
+            for host_name in group.get_hosts(self.inventory.groups(), false)? {
+                lines.push(graph_name(&host_name, depth + 1));
+                if show_vars {
+                    if let Some(host) = self.inventory.hosts().get(&host_name) {
+                        graph_vars(&self.host_vars(host)?, depth + 2, lines)?;
+                    }
+                }
+            }
+        }
+
+        if show_vars {
+            graph_vars(&self.group_vars(group)?, depth + 1, lines)?;
+        }
+
+        Ok(())
+    }
+
+    fn group(&self, group_name: &str) -> Result<&Group> {
+        self.inventory
+            .groups()
+            .get(group_name)
+            .ok_or_else(|| anyhow!("Could not find '{group_name}' group"))
+    }
+
+    /// Direct children of a group, sorted by name.
+    fn child_groups(&self, group: &Group) -> Vec<&Group> {
+        let mut children: Vec<&Group> = group
+            .child_groups
+            .iter()
+            .filter_map(|name| self.inventory.groups().get(name))
+            .collect();
+        children.sort_by(|a, b| a.name().cmp(b.name()));
+        children
+    }
+
+    /// Direct hosts of a group, only those not seen yet get their variables.
+    fn group_hosts(&self, group: &Group, seen: &mut HashSet<String>) -> Result<Mapping> {
+        let mut hosts = Mapping::new();
+        if group.name() == "all" {
+            return Ok(hosts);
+        }
+
+        for host_name in group.get_hosts(self.inventory.groups(), false)? {
+            let vars = match self.inventory.hosts().get(&host_name) {
+                Some(host) if seen.insert(host_name.to_string()) => self.host_vars(host)?,
+                _ => IndexMap::new(),
+            };
+            hosts.insert(Value::from(host_name), vars_to_value(&vars));
+        }
+
+        Ok(hosts)
+    }
+
+    fn host_vars(&self, host: &Host) -> Result<IndexMap<String, Variable>> {
+        if !self.export {
+            return self
+                .variable_manager
+                .get_inventory_vars(host, self.inventory);
+        }
+
+        let file_vars = self
+            .variable_manager
+            .get_vars_files(VarsEntity::Host(host.name()), self.inventory)?;
+        Ok(combine_variables(
+            host.vars(),
+            &file_vars,
+            &ConflictResolution::Replace,
+        ))
+    }
+
+    fn group_vars(&self, group: &Group) -> Result<IndexMap<String, Variable>> {
+        let file_vars = self
+            .variable_manager
+            .get_vars_files(VarsEntity::Group(group.name()), self.inventory)?;
+        Ok(combine_variables(
+            group.get_vars(),
+            &file_vars,
+            &ConflictResolution::Replace,
+        ))
+    }
+}
+
+fn graph_name(name: &str, depth: usize) -> String {
+    match depth {
+        0 => name.to_string(),
+        _ => format!("{}--{name}", "  |".repeat(depth)),
+    }
+}
+
+fn graph_vars(
+    vars: &IndexMap<String, Variable>,
+    depth: usize,
+    lines: &mut Vec<String>,
+) -> Result<()> {
+    let mut names: Vec<&String> = vars.keys().collect();
+    names.sort();
+
+    for name in names {
+        let value = serde_json::to_string(&to_json(&Value::from(&vars[name])))?;
+        lines.push(graph_name(&format!("{{{name} = {value}}}"), depth));
+    }
+
+    Ok(())
+}
+
+fn vars_to_value(vars: &IndexMap<String, Variable>) -> Value {
+    Value::Mapping(
+        vars.iter()
+            .map(|(key, value)| (Value::from(key.as_str()), Value::from(value)))
+            .collect(),
+    )
+}
+
+fn strings_to_value<S: AsRef<str>>(items: &[S]) -> Value {
+    Value::Sequence(
+        items
+            .iter()
+            .map(|item| Value::from(item.as_ref()))
+            .collect(),
+    )
+}
+
+/// Renders an exported value, vault encrypted values are never decrypted.
+pub fn render(value: &Value, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&to_json(value))?),
+        ExportFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+        ExportFormat::Toml => to_toml(value),
+    }
+}
+
+/// Converts to JSON like `ansible-inventory`, vault values as `{"__ansible_vault": ...}`.
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Bool(b) => serde_json::Value::Bool(*b),
+        Value::Number(n) => serde_json::to_value(n).unwrap_or(serde_json::Value::Null),
+        Value::String(s) => serde_json::Value::String(s.to_string()),
+        Value::Sequence(s) => serde_json::Value::Array(s.iter().map(to_json).collect()),
+        Value::Mapping(m) => serde_json::Value::Object(
+            m.iter()
+                .map(|(key, value)| (key_to_string(key), to_json(value)))
+                .collect(),
+        ),
+        Value::Tagged(tagged) if tagged.tag == "vault" => serde_json::Value::Object(
+            serde_json::Map::from_iter([("__ansible_vault".to_string(), to_json(&tagged.value))]),
+        ),
+        Value::Tagged(tagged) => to_json(&tagged.value),
+    }
+}
+
+fn key_to_string(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.to_string(),
+        key => serde_yaml::to_string(key)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Converts to TOML, which has no null: null values are left out.
+fn to_toml(value: &Value) -> Result<String> {
+    let Some(Item::Table(table)) = toml_item(value) else {
+        return Err(anyhow!("Only dictionaries can be rendered as TOML"));
+    };
+
+    let mut document = DocumentMut::new();
+    for (key, item) in table {
+        document.insert(&key, item);
+    }
+
+    Ok(document.to_string())
+}
+
+fn toml_item(value: &Value) -> Option<Item> {
+    match value {
+        Value::Mapping(mapping) => {
+            let mut table = Table::new();
+            for (key, value) in mapping {
+                if let Some(item) = toml_item(value) {
+                    table.insert(&key_to_string(key), item);
+                }
+            }
+            // only show headers of tables with values, or of empty ones like hosts without vars
+            table.set_implicit(!table.is_empty() && table.iter().all(|(_, item)| item.is_table()));
+            Some(Item::Table(table))
+        }
+        value => toml_value(value).map(Item::Value),
+    }
+}
+
+fn toml_value(value: &Value) -> Option<toml_edit::Value> {
+    match value {
+        Value::Null => None,
+        Value::Bool(b) => Some((*b).into()),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Some(i.into()),
+            (None, Some(f)) => Some(f.into()),
+            _ => None,
+        },
+        Value::String(s) => Some(s.as_str().into()),
+        Value::Sequence(s) => Some(toml_edit::Value::Array(
+            s.iter().filter_map(toml_value).collect::<Array>(),
+        )),
+        Value::Mapping(m) => {
+            let mut table = InlineTable::new();
+            for (key, value) in m {
+                if let Some(value) = toml_value(value) {
+                    table.insert(key_to_string(key), value);
+                }
+            }
+            Some(toml_edit::Value::InlineTable(table))
+        }
+        Value::Tagged(tagged) => toml_value(&tagged.value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    const HOSTS: &str = "\
+[web]
+web1 http_port=8080
+web2
+
+[db]
+db1
+
+[datacenter:children]
+web
+db
+
+[datacenter:vars]
+dc=eu-1
+
+[web:vars]
+role=frontend
+";
+
+    fn parse(dir: &std::path::Path) -> (InventoryManager, VariableManager) {
+        let path = dir.join("hosts.ini");
+        std::fs::write(&path, HOSTS).unwrap();
+        std::fs::create_dir(dir.join("host_vars")).unwrap();
+        std::fs::write(dir.join("host_vars/db1.yml"), "backup: true\n").unwrap();
+
+        let base_dir = PathBuf::from(dir);
+        let mut inventory = InventoryManager::new(&base_dir);
+        inventory
+            .parse_sources(Some(&[path.to_str().unwrap().to_string()]))
+            .unwrap();
+
+        (inventory, VariableManager::new(&base_dir))
+    }
+
+    #[test]
+    fn test_list_and_host() {
+        let dir = tempdir().unwrap();
+        let (inventory, variable_manager) = parse(dir.path());
+        let exporter = InventoryExporter::new(&inventory, &variable_manager);
+
+        let list = to_json(&exporter.list().unwrap());
+        assert_eq!(
+            list["all"]["children"],
+            serde_json::json!(["datacenter", "ungrouped"])
+        );
+        assert_eq!(
+            list["datacenter"]["children"],
+            serde_json::json!(["db", "web"])
+        );
+        assert_eq!(list["web"]["hosts"], serde_json::json!(["web1", "web2"]));
+        assert!(list["web"].get("vars").is_none());
+
+        let web1 = &list["_meta"]["hostvars"]["web1"];
+        assert_eq!(web1["http_port"], 8080);
+        assert_eq!(web1["role"], "frontend");
+        assert_eq!(web1["dc"], "eu-1");
+        assert!(web1.get("inventory_hostname").is_none());
+        assert_eq!(list["_meta"]["hostvars"]["db1"]["backup"], true);
+
+        let db1 = to_json(&exporter.host("db1").unwrap());
+        assert_eq!(db1["dc"], "eu-1");
+        assert!(db1.get("role").is_none());
+        assert!(exporter.host("missing").is_err());
+
+        let exporter = exporter.export(true);
+        let list = to_json(&exporter.list().unwrap());
+        assert_eq!(list["web"]["vars"]["role"], "frontend");
+        assert_eq!(list["_meta"]["hostvars"]["web1"]["http_port"], 8080);
+        assert!(list["_meta"]["hostvars"]["web1"].get("role").is_none());
+    }
+
+    #[test]
+    fn test_graph() {
+        let dir = tempdir().unwrap();
+        let (inventory, variable_manager) = parse(dir.path());
+        let exporter = InventoryExporter::new(&inventory, &variable_manager);
+
+        assert_eq!(
+            exporter.graph("all", false).unwrap(),
+            "\
+@all:
+  |--@datacenter:
+  |  |--@db:
+  |  |  |--db1
+  |  |--@web:
+  |  |  |--web1
+  |  |  |--web2
+  |--@ungrouped:"
+        );
+
+        assert_eq!(
+            exporter.graph("web", true).unwrap(),
+            "\
+@web:
+  |--web1
+  |  |--{dc = \"eu-1\"}
+  |  |--{http_port = 8080}
+  |  |--{inventory_dir = \"DIR\"}
+  |  |--{inventory_file = \"DIR/hosts.ini\"}
+  |  |--{role = \"frontend\"}
+  |--web2
+  |  |--{dc = \"eu-1\"}
+  |  |--{inventory_dir = \"DIR\"}
+  |  |--{inventory_file = \"DIR/hosts.ini\"}
+  |  |--{role = \"frontend\"}
+  |--{role = \"frontend\"}"
+                .replace("DIR", dir.path().to_str().unwrap())
+        );
+        assert!(exporter.graph("missing", false).is_err());
+    }
+
+    #[test]
+    fn test_yaml_and_toml_inventories() {
+        let dir = tempdir().unwrap();
+        let (inventory, variable_manager) = parse(dir.path());
+        let exporter = InventoryExporter::new(&inventory, &variable_manager).export(true);
+
+        let yaml = render(&exporter.inventory().unwrap(), ExportFormat::Yaml).unwrap();
+        let value: Value = serde_yaml::from_str(&yaml).unwrap();
+        let datacenter = &value["all"]["children"]["datacenter"];
+        assert_eq!(datacenter["vars"]["dc"], Value::from("eu-1"));
+        assert_eq!(
+            datacenter["children"]["web"]["hosts"]["web1"]["http_port"],
+            Value::from(8080)
+        );
+        assert_eq!(value["all"]["children"]["ungrouped"], Value::Null);
+
+        let toml = render(&exporter.flat_inventory().unwrap(), ExportFormat::Toml).unwrap();
+        let document: DocumentMut = toml.parse().unwrap();
+        assert_eq!(
+            document["datacenter"]["children"].to_string().trim(),
+            "[\"db\", \"web\"]"
+        );
+        assert_eq!(document["datacenter"]["vars"]["dc"].as_str(), Some("eu-1"));
+        assert_eq!(
+            document["web"]["hosts"]["web1"]["http_port"].as_integer(),
+            Some(8080)
+        );
+        assert!(document["web"]["hosts"]["web2"].is_table());
+        assert_eq!(
+            document["db"]["hosts"]["db1"]["backup"].as_bool(),
+            Some(true)
+        );
+    }
+}
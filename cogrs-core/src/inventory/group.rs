@@ -12,7 +12,7 @@ pub struct Group {
     depth: u32,
     priority: i64,
     vars: IndexMap<String, Variable>,
-    hosts: Vec<String>,
+    hosts: IndexSet<String>,
     pub child_groups: Vec<String>,
     parent_groups: Vec<String>,
 }
@@ -24,7 +24,7 @@ impl Group {
             depth: 0,
             priority: 1,
             vars: IndexMap::new(),
-            hosts: Vec::new(),
+            hosts: IndexSet::new(),
             child_groups: Vec::new(),
             parent_groups: Vec::new(),
         }
@@ -35,17 +35,18 @@ impl Group {
     }
 
     pub fn add_host(&mut self, host_name: &str) {
-        let name = host_name.to_string();
-        if !self.hosts.contains(&name) {
-            self.hosts.push(name);
+        if !self.hosts.contains(host_name) {
+            self.hosts.insert(host_name.to_string());
         }
     }
 
     pub fn remove_host(&mut self, host_name: &str) {
-        let name = host_name.to_string();
-        if self.hosts.contains(&name) {
-            self.hosts.retain(|h| h != &name);
-        }
+        self.hosts.shift_remove(host_name);
+    }
+
+    /// The hosts directly in the group, in the order they were added.
+    pub(crate) fn host_names(&self) -> &IndexSet<String> {
+        &self.hosts
     }
 
     pub fn get_hosts(
@@ -54,17 +55,17 @@ impl Group {
         include_children: bool,
     ) -> Result<Vec<String>> {
         if !include_children {
-            return Ok(self.hosts.clone());
+            return Ok(self.hosts.iter().cloned().collect());
         }
 
         let mut hosts: Vec<String> = Vec::new();
-        let seen: HashSet<String> = HashSet::new();
+        let mut seen: HashSet<&str> = HashSet::new();
         for descendent in self.get_descendants(groups, true) {
             let group = groups
                 .get(&descendent)
                 .ok_or(anyhow::format_err!("Could not find {descendent} group"))?;
-            for host in group.get_hosts(groups, false)? {
-                if !seen.contains(&host) {
+            for host in &group.hosts {
+                if seen.insert(host) {
                     hosts.push(host.to_string());
                 }
             }
@@ -80,6 +81,18 @@ impl Group {
         self.priority = priority;
     }
 
+    pub fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn parent_groups(&self) -> &[String] {
+        &self.parent_groups
+    }
+
     pub fn walk_relationships(
         &self,
         groups: &IndexMap<String, Group>,
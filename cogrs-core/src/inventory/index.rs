@@ -0,0 +1,150 @@
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::vars::variable::Mapping;
+use bit_set::BitSet;
+use indexmap::IndexMap;
+use std::sync::{Arc, OnceLock};
+
+/// Hosts are identified by their position in the inventory. Hosts are never removed, so the
+/// ID of a host doesn't change as the inventory grows.
+pub(crate) type HostId = usize;
+
+/// A set of hosts. `&` and `!` patterns are set operations on it.
+pub(crate) type HostSet = BitSet;
+
+/// Hosts in the order they were added, without duplicates. The set is kept next to the
+/// order for set operations, which keep the order of the remaining hosts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HostList {
+    ids: Vec<HostId>,
+    set: HostSet,
+}
+
+impl HostList {
+    pub fn ids(&self) -> &[HostId] {
+        &self.ids
+    }
+
+    pub fn set(&self) -> &HostSet {
+        &self.set
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn push(&mut self, host_id: HostId) {
+        if self.set.insert(host_id) {
+            self.ids.push(host_id);
+        }
+    }
+
+    /// Adds the hosts of `other` missing from the list, at the end.
+    pub fn union_with(&mut self, other: &HostList) {
+        for host_id in &other.ids {
+            self.push(*host_id);
+        }
+    }
+
+    /// Keeps the hosts which are also in `other`.
+    pub fn intersect_with(&mut self, other: &HostSet) {
+        self.set.intersect_with(other);
+        self.retain_set();
+    }
+
+    /// Removes the hosts which are in `other`.
+    pub fn difference_with(&mut self, other: &HostSet) {
+        self.set.difference_with(other);
+        self.retain_set();
+    }
+
+    fn retain_set(&mut self) {
+        let set = &self.set;
+        self.ids.retain(|host_id| set.contains(*host_id));
+    }
+}
+
+impl FromIterator<HostId> for HostList {
+    fn from_iter<T: IntoIterator<Item = HostId>>(iter: T) -> Self {
+        let mut hosts = HostList::default();
+        for host_id in iter {
+            hosts.push(host_id);
+        }
+        hosts
+    }
+}
+
+/// The group membership of an inventory, computed the first time each part is needed. The
+/// inventory drops it whenever its groups or hosts change, the `groups` and `hosts` given
+/// to its methods must be the ones of that inventory.
+pub(crate) struct InventoryIndex {
+    /// The hosts of each group and of its descendants, by group position.
+    group_hosts: Vec<OnceLock<HostList>>,
+    groups_dict: OnceLock<IndexMap<String, Vec<String>>>,
+    groups_mapping: OnceLock<Arc<Mapping>>,
+}
+
+impl InventoryIndex {
+    pub fn new(groups: &IndexMap<String, Group>) -> Self {
+        InventoryIndex {
+            group_hosts: groups.keys().map(|_| OnceLock::new()).collect(),
+            groups_dict: OnceLock::new(),
+            groups_mapping: OnceLock::new(),
+        }
+    }
+
+    /// The hosts of the group at `group_id` and of its descendants, in the same order as
+    /// [`Group::get_hosts`].
+    pub fn group_hosts(
+        &self,
+        group_id: usize,
+        groups: &IndexMap<String, Group>,
+        hosts: &IndexMap<String, Host>,
+    ) -> &HostList {
+        self.group_hosts[group_id].get_or_init(|| {
+            let group = &groups[group_id];
+            group
+                .get_descendants(groups, true)
+                .iter()
+                .filter_map(|group_name| groups.get(group_name))
+                .flat_map(|group| group.host_names())
+                .filter_map(|host_name| hosts.get_index_of(host_name))
+                .collect()
+        })
+    }
+
+    /// The host names of every group.
+    pub fn groups_dict(
+        &self,
+        groups: &IndexMap<String, Group>,
+        hosts: &IndexMap<String, Host>,
+    ) -> &IndexMap<String, Vec<String>> {
+        self.groups_dict.get_or_init(|| {
+            groups
+                .keys()
+                .enumerate()
+                .map(|(group_id, group_name)| {
+                    let host_names = self
+                        .group_hosts(group_id, groups, hosts)
+                        .ids()
+                        .iter()
+                        .filter_map(|host_id| hosts.get_index(*host_id))
+                        .map(|(host_name, _)| host_name.to_string())
+                        .collect();
+                    (group_name.to_string(), host_names)
+                })
+                .collect()
+        })
+    }
+
+    /// [`InventoryIndex::groups_dict`] as the `groups` magic variable.
+    pub fn groups_mapping(
+        &self,
+        groups: &IndexMap<String, Group>,
+        hosts: &IndexMap<String, Host>,
+    ) -> Arc<Mapping> {
+        self.groups_mapping
+            .get_or_init(|| Arc::new(self.groups_dict(groups, hosts).clone().into()))
+            .clone()
+    }
+}
@@ -0,0 +1,347 @@
+use crate::inventory::host::Host;
+use crate::inventory::manager::InventoryManager;
+use crate::parsing::host_list::is_host_list;
+use crate::parsing::parser::InventoryParser;
+use crate::vars::variable::Variable;
+use anyhow::Result;
+use indexmap::IndexMap;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// Variables the source defining a host sets on it, they differ between sources.
+const SOURCE_VARS: [&str; 2] = ["inventory_file", "inventory_dir"];
+
+/// Groups every inventory has, they may be empty.
+const IMPLICIT_GROUPS: [&str; 2] = ["all", "ungrouped"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// A problem found in an inventory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintMessage {
+    pub severity: Severity,
+    /// The inventory file the problem comes from, when it is known.
+    pub source: Option<String>,
+    pub message: String,
+}
+
+impl LintMessage {
+    fn new(severity: Severity, source: Option<&str>, message: String) -> Self {
+        LintMessage {
+            severity,
+            source: source.map(str::to_string),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "[{}] {}: {}", self.severity, source, self.message),
+            None => write!(f, "[{}] {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Parses inventory sources one file at a time, carrying on past files that fail to parse,
+/// and reports problems `parse_sources` either stops at or lets through:
+///
+/// - sources that don't exist or fail to parse, such as recursive group loops or invalid
+///   host ranges
+/// - hosts defined in several files with conflicting variables
+/// - groups without any host
+/// - host names that look like host ranges that were not expanded
+pub struct InventoryLinter<'a> {
+    inventory: &'a mut InventoryManager,
+    messages: Vec<LintMessage>,
+    /// The file each group was first found in.
+    group_sources: IndexMap<String, String>,
+}
+
+impl<'a> InventoryLinter<'a> {
+    pub fn new(inventory: &'a mut InventoryManager) -> Self {
+        InventoryLinter {
+            inventory,
+            messages: Vec::new(),
+            group_sources: IndexMap::new(),
+        }
+    }
+
+    /// Parses `sources` into the inventory and returns the problems found.
+    pub fn lint(mut self, sources: &[String]) -> Result<Vec<LintMessage>> {
+        self.inventory.init_implicit_groups()?;
+
+        for source in sources {
+            for file in expand_source(source)? {
+                self.lint_source(&file);
+            }
+        }
+
+        if let Err(e) = self.inventory.reconcile_inventory() {
+            self.push(Severity::Error, None, format!("{e:#}"));
+        }
+
+        self.lint_groups()?;
+        self.lint_hosts()?;
+
+        Ok(self.messages)
+    }
+
+    fn lint_source(&mut self, source: &str) {
+        if !is_host_list(source) && !Path::new(source).exists() {
+            self.push(
+                Severity::Error,
+                Some(source),
+                "the inventory source does not exist".to_string(),
+            );
+            return;
+        }
+
+        let hosts_before = self.inventory.hosts().clone();
+        let groups_before: HashSet<String> = self.inventory.groups().keys().cloned().collect();
+
+        if let Err(e) = self.inventory.parse_source(source) {
+            self.push(Severity::Error, Some(source), format!("{e:#}"));
+        }
+
+        for group_name in self.inventory.groups().keys() {
+            if !groups_before.contains(group_name) {
+                self.group_sources
+                    .entry(group_name.to_string())
+                    .or_insert_with(|| source.to_string());
+            }
+        }
+
+        let mut conflicts = Vec::new();
+        for (host_name, before) in &hosts_before {
+            let Some(after) = self.inventory.get_host(host_name) else {
+                continue;
+            };
+
+            for (key, value) in before.vars() {
+                if SOURCE_VARS.contains(&key.as_str()) {
+                    continue;
+                }
+
+                if after
+                    .vars()
+                    .get(key)
+                    .is_some_and(|new_value| new_value != value)
+                {
+                    conflicts.push(format!(
+                        "host '{host_name}' sets '{key}' to a value conflicting with {}",
+                        defined_in(before)
+                    ));
+                }
+            }
+        }
+
+        for conflict in conflicts {
+            self.push(Severity::Warning, Some(source), conflict);
+        }
+    }
+
+    fn lint_groups(&mut self) -> Result<()> {
+        let groups = self.inventory.groups();
+        let mut empty_groups = Vec::new();
+
+        for (group_name, group) in groups {
+            if IMPLICIT_GROUPS.contains(&group_name.as_str()) {
+                continue;
+            }
+
+            if group.get_hosts(groups, true)?.is_empty() {
+                empty_groups.push(group_name.to_string());
+            }
+        }
+
+        for group_name in empty_groups {
+            let source = self.group_sources.get(&group_name).cloned();
+            self.push(
+                Severity::Warning,
+                source.as_deref(),
+                format!("group '{group_name}' has no hosts"),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn lint_hosts(&mut self) -> Result<()> {
+        let range = Regex::new(r"\[[^\]]*]")?;
+        let mut unexpanded = Vec::new();
+
+        for (host_name, host) in self.inventory.hosts() {
+            if range.is_match(host_name) {
+                unexpanded.push((
+                    inventory_file(host),
+                    format!(
+                        "host name '{host_name}' looks like a host range, ranges are written \
+                         as [start:end] or [start:end:stride]"
+                    ),
+                ));
+            }
+        }
+
+        for (source, message) in unexpanded {
+            self.push(Severity::Warning, source.as_deref(), message);
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, severity: Severity, source: Option<&str>, message: String) {
+        self.messages
+            .push(LintMessage::new(severity, source, message));
+    }
+}
+
+/// Expands directory sources into the files they contain, to report problems per file.
+fn expand_source(source: &str) -> Result<Vec<String>> {
+    let path = Path::new(source);
+    if is_host_list(source) || !path.is_dir() {
+        return Ok(vec![source.to_string()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in InventoryParser::directory_sources(path)? {
+        files.extend(expand_source(&entry)?);
+    }
+
+    Ok(files)
+}
+
+fn inventory_file(host: &Host) -> Option<String> {
+    match host.vars().get("inventory_file") {
+        Some(Variable::String(file)) => Some(file.to_string()),
+        _ => None,
+    }
+}
+
+fn defined_in(host: &Host) -> String {
+    match inventory_file(host) {
+        Some(file) => format!("the one from {file}"),
+        None => "an earlier source".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn lint(files: &[(&str, &str)]) -> (TempDir, InventoryManager, Vec<LintMessage>) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        let mut inventory = InventoryManager::new(&dir.path().to_path_buf());
+        let sources = vec![dir.path().to_str().unwrap().to_string()];
+        let messages = InventoryLinter::new(&mut inventory).lint(&sources).unwrap();
+
+        (dir, inventory, messages)
+    }
+
+    fn source(dir: &Path, name: &str) -> Option<String> {
+        Some(dir.join(name).to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_lint_conflicting_host_vars() {
+        let (dir, _, messages) = lint(&[
+            ("a.ini", "[web]\nfoo port=80 user=admin\n"),
+            (
+                "b.yaml",
+                "db:\n  hosts:\n    foo:\n      port: 8080\n      user: admin\n",
+            ),
+        ]);
+
+        assert_eq!(
+            messages,
+            vec![LintMessage {
+                severity: Severity::Warning,
+                source: source(dir.path(), "b.yaml"),
+                message: format!(
+                    "host 'foo' sets 'port' to a value conflicting with the one from {}",
+                    dir.path().join("a.ini").display()
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lint_empty_groups_and_unexpanded_ranges() {
+        let (dir, _, messages) = lint(&[(
+            "hosts.yaml",
+            "web:\n  hosts:\n    web[1-3]:\nempty:\n  children:\n    also_empty:\n",
+        )]);
+
+        let mut found: Vec<String> = messages.iter().map(|m| m.to_string()).collect();
+        found.sort();
+        let file = dir.path().join("hosts.yaml");
+        assert_eq!(
+            found,
+            vec![
+                format!(
+                    "[WARNING] {}: group 'also_empty' has no hosts",
+                    file.display()
+                ),
+                format!("[WARNING] {}: group 'empty' has no hosts", file.display()),
+                format!(
+                    "[WARNING] {}: host name 'web[1-3]' looks like a host range, ranges are \
+                     written as [start:end] or [start:end:stride]",
+                    file.display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_keeps_going_after_errors() {
+        let (dir, inventory, messages) = lint(&[
+            ("a.ini", "[web]\nweb[5:1]\n"),
+            (
+                "b.yaml",
+                "a:\n  children:\n    b:\nb:\n  children:\n    a:\n",
+            ),
+            ("c.ini", "[db]\ndb1\n"),
+        ]);
+
+        let errors: Vec<&LintMessage> = messages
+            .iter()
+            .filter(|m| m.severity == Severity::Error)
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].source, source(dir.path(), "a.ini"));
+        assert!(errors[0].message.contains("Invalid range"));
+        assert_eq!(errors[1].source, source(dir.path(), "b.yaml"));
+        assert!(errors[1].message.contains("recursive dependency loop"));
+
+        // the files after the broken ones are still parsed
+        assert!(inventory.get_host("db1").is_some());
+
+        let mut inventory = InventoryManager::new(&dir.path().to_path_buf());
+        let sources = vec![dir.path().join("missing.ini").to_str().unwrap().to_string()];
+        let messages = InventoryLinter::new(&mut inventory).lint(&sources).unwrap();
+        assert_eq!(messages[0].severity, Severity::Error);
+        assert_eq!(messages[0].message, "the inventory source does not exist");
+    }
+}
@@ -1,18 +1,23 @@
 use super::group::Group;
 use super::host::Host;
+use crate::config::manager::ConfigManager;
 use crate::constants::LOCALHOST;
+use crate::inventory::cache::InventoryCache;
+use crate::inventory::index::{HostId, HostList, InventoryIndex};
 use crate::inventory::patterns::PatternResolver;
 use crate::inventory::utils::{glob_to_regex, split_subscript};
 use crate::parsing::parser::InventoryParser;
-use crate::vars::variable::{
-    combine_variables, get_vars_from_inventory_sources, ConflictResolution,
-};
-use anyhow::Result;
+use crate::playbook::play::Play;
+use crate::template::Templar;
+use crate::vars::manager::VariableManager;
+use crate::vars::variable::{combine_variables, ConflictResolution, Mapping, Variable};
+use anyhow::{bail, Result};
 use indexmap::IndexMap;
 use log::{debug, warn};
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 pub struct HostManager;
 
@@ -21,6 +26,10 @@ pub struct InventoryManager {
     groups: IndexMap<String, Group>,
     hosts: IndexMap<String, Host>,
     localhost: Host,
+    sources: Vec<String>,
+    cache: Option<InventoryCache>,
+    /// Group membership, dropped whenever groups or hosts change.
+    index: OnceLock<InventoryIndex>,
 }
 
 impl InventoryManager {
@@ -32,10 +41,14 @@ impl InventoryManager {
             hosts: IndexMap::new(),
             base_dir: base_dir.to_path_buf(),
             localhost,
+            sources: Vec::new(),
+            cache: None,
+            index: OnceLock::new(),
         }
     }
 
-    fn init_implicit_groups(&mut self) -> Result<()> {
+    pub(crate) fn init_implicit_groups(&mut self) -> Result<()> {
+        self.invalidate_index();
         self.groups
             .insert("ungrouped".to_string(), Group::new("ungrouped"));
         self.groups.insert("all".to_string(), Group::new("all"));
@@ -70,6 +83,63 @@ impl InventoryManager {
         host
     }
 
+    /// Returns the host with the given ID, the ID after the last host is the implicit localhost.
+    fn get_host_by_id(&self, host_id: HostId) -> Option<&Host> {
+        match self.hosts.get_index(host_id) {
+            Some((_, host)) => Some(host),
+            None if host_id == self.implicit_localhost_id() => Some(&self.localhost),
+            None => None,
+        }
+    }
+
+    fn implicit_localhost_id(&self) -> HostId {
+        self.hosts.len()
+    }
+
+    fn index(&self) -> &InventoryIndex {
+        self.index.get_or_init(|| InventoryIndex::new(&self.groups))
+    }
+
+    /// Drops the group membership computed from the inventory, every change to groups or
+    /// hosts must call this.
+    fn invalidate_index(&mut self) {
+        self.index.take();
+    }
+
+    pub fn hosts(&self) -> &IndexMap<String, Host> {
+        &self.hosts
+    }
+
+    pub fn groups(&self) -> &IndexMap<String, Group> {
+        &self.groups
+    }
+
+    /// Returns every group the host belongs to, including ancestors and `all`,
+    /// sorted for variable precedence: by depth, then priority, then name.
+    pub fn get_host_groups(&self, host: &Host) -> Vec<&Group> {
+        let mut group_names: HashSet<String> = HashSet::from([String::from("all")]);
+
+        for group_name in host.groups() {
+            if let Some(group) = self.groups.get(group_name) {
+                group_names.extend(group.get_ancestors(&self.groups, true));
+            }
+        }
+
+        let mut groups: Vec<&Group> = group_names
+            .iter()
+            .filter_map(|name| self.groups.get(name))
+            .collect();
+
+        groups.sort_by(|a, b| {
+            a.depth()
+                .cmp(&b.depth())
+                .then(a.priority().cmp(&b.priority()))
+                .then(a.name().cmp(b.name()))
+        });
+
+        groups
+    }
+
     pub fn get_base_dir(&self) -> &PathBuf {
         &self.base_dir
     }
@@ -142,33 +212,154 @@ impl InventoryManager {
         Ok(())
     }
 
-    fn reconcile_inventory(&mut self) -> Result<()> {
+    pub(crate) fn reconcile_inventory(&mut self) -> Result<()> {
         debug!("Reconcile groups and hosts in inventory");
+        self.invalidate_index();
         self.ensure_top_level_groups_inherit_all()?;
         self.update_hosts_with_group_relationships()?;
 
         Ok(())
     }
 
+    /// Inventory sources parsed by [`InventoryManager::parse_sources`], `host_vars` and
+    /// `group_vars` are looked up next to them.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    /// Sets the cache dynamic inventory sources are restored from, `None` to always run them.
+    pub fn set_cache(&mut self, cache: Option<InventoryCache>) {
+        self.cache = cache;
+    }
+
+    /// Sets up the inventory cache from the `INVENTORY_CACHE*` settings, `flush` removes the
+    /// cached inventories first, even when caching is disabled.
+    pub async fn init_cache(&mut self, flush: bool) -> Result<()> {
+        let config_manager = ConfigManager::instance().lock().await;
+        let enabled = config_manager
+            .get_config_value::<bool>("INVENTORY_CACHE_ENABLED")?
+            .is_some_and(|(enabled, _)| enabled);
+
+        if !enabled && !flush {
+            return Ok(());
+        }
+
+        let cache = InventoryCache::from_config(&config_manager).await?;
+        if flush {
+            cache.flush()?;
+        }
+        self.cache = enabled.then_some(cache);
+
+        Ok(())
+    }
+
     pub fn parse_sources(&mut self, sources: Option<&[String]>) -> Result<()> {
         self.init_implicit_groups()?;
 
         if let Some(sources) = sources {
             for source in sources.iter() {
-                InventoryParser::parse_source(source, &mut self.groups, &mut self.hosts)?;
+                self.parse_source(source)?;
             }
 
             self.reconcile_inventory()?
         }
 
-        // TODO: combine vars for groups and hosts
-        for group in self.groups.values_mut() {
-            let vars = get_vars_from_inventory_sources(sources)?;
-            if !vars.is_empty() {
-                group.combine_vars(&vars);
-            }
+        self.sources = sources.map(|s| s.to_vec()).unwrap_or_default();
+
+        Ok(())
+    }
+
+    /// Parses a single source into the inventory, groups and hosts are reconciled afterwards.
+    pub(crate) fn parse_source(&mut self, source: &str) -> Result<()> {
+        self.invalidate_index();
+        InventoryParser::parse_source_with_cache(
+            source,
+            &mut self.groups,
+            &mut self.hosts,
+            self.cache.as_ref(),
+        )
+    }
+
+    /// Adds a host to the inventory during a run (the `add_host` action), creating the groups
+    /// it is added to. Variables are combined with those of an existing host. Hosts matched
+    /// by later plays and the `groups` magic variable include the new host.
+    pub fn add_host(
+        &mut self,
+        host_name: &str,
+        group_names: &[String],
+        vars: &IndexMap<String, Variable>,
+    ) -> Result<()> {
+        self.invalidate_index();
+        let host = self
+            .hosts
+            .entry(host_name.to_string())
+            .or_insert_with(|| Host::new(host_name));
+        for (key, value) in vars {
+            host.set_var(key, value);
+        }
+
+        for group_name in group_names {
+            self.add_host_to_group(host_name, group_name)?;
+        }
+
+        self.reconcile_inventory()
+    }
+
+    /// Adds a host to a group during a run (the `group_by` action). A new group is added as a
+    /// child of each of `parents`.
+    pub fn group_by(
+        &mut self,
+        host_name: &str,
+        group_name: &str,
+        parents: &[String],
+    ) -> Result<()> {
+        if !self.hosts.contains_key(host_name) {
+            bail!("Could not find host: '{host_name}'");
+        }
+
+        self.invalidate_index();
+        self.add_host_to_group(host_name, group_name)?;
+        for parent_name in parents {
+            self.add_child_group(parent_name, group_name)?;
+        }
+
+        self.reconcile_inventory()
+    }
+
+    fn add_host_to_group(&mut self, host_name: &str, group_name: &str) -> Result<()> {
+        self.groups
+            .entry(group_name.to_string())
+            .or_insert_with(|| Group::new(group_name))
+            .add_host(host_name);
+
+        let ancestors = self.groups[group_name].get_ancestors(&self.groups, true);
+        self.hosts
+            .get_mut(host_name)
+            .ok_or_else(|| anyhow::format_err!("Could not find host: '{host_name}'"))?
+            .populate_ancestors(ancestors);
+
+        Ok(())
+    }
+
+    fn add_child_group(&mut self, group_name: &str, child_group_name: &str) -> Result<()> {
+        for name in [group_name, child_group_name] {
+            self.groups
+                .entry(name.to_string())
+                .or_insert_with(|| Group::new(name));
         }
 
+        if self.groups[group_name].has_child_group(child_group_name) {
+            return Ok(());
+        }
+
+        let mut group = self.groups[group_name].clone();
+        let mut child_group = self.groups[child_group_name].clone();
+        group.add_child_group(&mut child_group, &mut self.groups, &mut self.hosts)?;
+
+        self.groups.insert(group.name().to_string(), group);
+        self.groups
+            .insert(child_group.name().to_string(), child_group);
+
         Ok(())
     }
 
@@ -188,7 +379,8 @@ impl InventoryManager {
         combined_patterns
     }
 
-    fn filter_with_limit(&self, selected_hosts: &[String], limit: &str) -> Result<Vec<String>> {
+    /// Keeps the hosts of `selected_hosts` matching the limit specification.
+    fn filter_with_limit(&self, selected_hosts: &mut HostList, limit: &str) -> Result<()> {
         let patterns: Vec<String> = limit
             .trim_start_matches('\'')
             .trim_end_matches('\'')
@@ -198,15 +390,12 @@ impl InventoryManager {
         let resolved_limit_patterns = PatternResolver::resolve_and_sort_patterns(&patterns);
 
         let limit_hosts = self.apply_patterns(&resolved_limit_patterns)?;
-        let limit_host_set: HashSet<String> = limit_hosts.into_iter().collect();
+        selected_hosts.intersect_with(limit_hosts.set());
 
-        Ok(selected_hosts
-            .iter()
-            .filter(|host| limit_host_set.contains(*host))
-            .cloned()
-            .collect())
+        Ok(())
     }
 
+    /// Returns the hosts matching `pattern` and `limit`, in the order the patterns matched them.
     pub fn filter_hosts(&self, pattern: &str, limit: Option<&str>) -> Result<Vec<Host>> {
         if self.hosts.is_empty() && LOCALHOST.contains(&pattern) {
             warn!("Provided hosts list is empty, only localhost is available. Note that the implicit localhost does not match 'all'");
@@ -230,59 +419,79 @@ impl InventoryManager {
 
         if let Some(limit) = limit {
             // only keep hosts that match limit specification
-            selected_hosts = self.filter_with_limit(&selected_hosts, limit)?;
+            self.filter_with_limit(&mut selected_hosts, limit)?;
         }
 
         // TODO: handle localhost and all
 
-        // Map host names to Host objects, filtering out invalid entries
         Ok(selected_hosts
+            .ids()
             .iter()
-            .filter_map(|host| self.get_host(host).cloned())
+            .filter_map(|host_id| self.get_host_by_id(*host_id).cloned())
             .collect())
     }
 
-    pub fn get_groups_dict(&self) -> IndexMap<String, Vec<String>> {
-        let mut groups_dict = IndexMap::new();
+    /// Keeps the hosts for which `expression`, a conditional such as
+    /// `ansible_kernel is version('5.10', '<')`, holds with the host's variables and cached
+    /// facts. Hosts the expression fails for are left out with a warning.
+    pub fn filter_hosts_by_expression(
+        &self,
+        hosts: Vec<Host>,
+        expression: &str,
+        variable_manager: &VariableManager,
+        play: Option<&Play>,
+    ) -> Result<Vec<Host>> {
+        let templar = Templar::new();
+        let mut selected_hosts = Vec::new();
 
-        for (group_name, group) in &self.groups {
-            // Get a set of hosts directly in the group and not in descendant groups
-            let direct_hosts = group
-                .get_hosts(&self.groups, true)
-                .unwrap_or_else(|_| Vec::new());
+        for host in hosts {
+            let vars = variable_manager.get_vars(play, Some(&host), None, Some(self), true)?;
 
-            groups_dict.insert(group_name.clone(), direct_hosts);
+            match templar.evaluate_conditional(expression, &vars) {
+                Ok(true) => selected_hosts.push(host),
+                Ok(false) => {}
+                Err(e) => warn!(
+                    "Skipping host {}, '{expression}' could not be evaluated: {e:#}",
+                    host.name()
+                ),
+            }
         }
 
-        groups_dict
+        Ok(selected_hosts)
     }
 
-    fn apply_patterns(&self, patterns: &[String]) -> Result<Vec<String>> {
-        let mut selected_hosts = Vec::new();
+    /// Returns the host names of every group, including the hosts of descendant groups.
+    pub fn get_groups_dict(&self) -> IndexMap<String, Vec<String>> {
+        self.index().groups_dict(&self.groups, &self.hosts).clone()
+    }
+
+    /// [`InventoryManager::get_groups_dict`] as the `groups` magic variable, it is only
+    /// computed again once the inventory changes.
+    pub fn get_groups_mapping(&self) -> Arc<Mapping> {
+        self.index().groups_mapping(&self.groups, &self.hosts)
+    }
+
+    fn apply_patterns(&self, patterns: &[String]) -> Result<HostList> {
+        let mut selected_hosts = HostList::default();
 
         for pattern in patterns {
             let matched_hosts = self.match_single_pattern(pattern)?;
 
             if pattern.starts_with('!') {
                 // Exclude hosts matching the pattern
-                selected_hosts.retain(|host| !matched_hosts.contains(host));
+                selected_hosts.difference_with(matched_hosts.set());
             } else if pattern.starts_with('&') {
                 // Retain only hosts that match the intersection pattern
-                selected_hosts.retain(|host| matched_hosts.contains(host));
+                selected_hosts.intersect_with(matched_hosts.set());
             } else {
-                // Add hosts that match the pattern, avoiding duplicates
-                for host in matched_hosts {
-                    if !selected_hosts.contains(&host) {
-                        selected_hosts.push(host);
-                    }
-                }
+                selected_hosts.union_with(&matched_hosts);
             }
         }
 
         Ok(selected_hosts)
     }
 
-    fn match_single_pattern(&self, pattern: &str) -> Result<Vec<String>> {
+    fn match_single_pattern(&self, pattern: &str) -> Result<HostList> {
         let stripped_pattern = if pattern.starts_with('!') || pattern.starts_with('&') {
             &pattern[1..]
         } else {
@@ -292,13 +501,16 @@ impl InventoryManager {
         let split_pattern = split_subscript(stripped_pattern)?;
         let mut hosts = self.enumerate_matches(&split_pattern.pattern)?;
         if let Some((start, end)) = split_pattern.subscript {
-            hosts = self.apply_subscript(&hosts, start, end);
+            hosts = self
+                .apply_subscript(hosts.ids(), start, end)
+                .into_iter()
+                .collect();
         }
 
         Ok(hosts)
     }
 
-    fn apply_subscript(&self, hosts: &[String], start: i32, end: Option<i32>) -> Vec<String> {
+    fn apply_subscript<T: Clone>(&self, hosts: &[T], start: i32, end: Option<i32>) -> Vec<T> {
         if hosts.is_empty() {
             return Vec::new();
         }
@@ -323,18 +535,15 @@ impl InventoryManager {
         hosts[start_idx as usize..=end_idx as usize].to_vec()
     }
 
-    fn enumerate_matches(&self, pattern: &str) -> Result<Vec<String>> {
-        let mut matches = Vec::new();
+    /// Returns the hosts of the matching groups, each in the group's own order, then the
+    /// matching hosts.
+    fn enumerate_matches(&self, pattern: &str) -> Result<HostList> {
+        let mut matches = HostList::default();
 
-        let groups: Vec<String> = self.groups.keys().cloned().collect();
-        let matched_groups = self.match_list(&groups, pattern)?;
-        for group_name in &matched_groups {
-            let group = self
-                .groups
-                .get(group_name)
-                .ok_or(anyhow::format_err!("Could not find {group_name} group"))?;
-            let group_hosts = group.get_hosts(&self.groups, true)?;
-            matches.extend(group_hosts);
+        let index = self.index();
+        let matched_groups = match_names(&self.groups, pattern)?;
+        for group_id in &matched_groups {
+            matches.union_with(index.group_hosts(*group_id, &self.groups, &self.hosts));
         }
 
         let special_chars = ['.', '?', '*', '['];
@@ -342,46 +551,51 @@ impl InventoryManager {
             || pattern.starts_with("~")
             || pattern.chars().any(|c| special_chars.contains(&c))
         {
-            let hosts: Vec<String> = self.hosts.keys().cloned().collect();
-            let matched_hosts = self.match_list(&hosts, pattern)?;
-            matches.extend(matched_hosts);
+            for host_id in match_names(&self.hosts, pattern)? {
+                matches.push(host_id);
+            }
         }
 
         if matches.is_empty() && LOCALHOST.contains(&pattern) {
-            matches.push(pattern.to_string());
+            matches.push(self.implicit_localhost_id());
         }
 
         Ok(matches)
     }
 
-    fn match_list(&self, items: &[String], pattern_str: &str) -> Result<Vec<String>> {
-        if pattern_str == "all" {
-            return Ok(items.to_vec());
-        }
-
-        // Compile patterns
-        let pattern = if !pattern_str.starts_with('~') {
-            Regex::new(&glob_to_regex(pattern_str)?)?
-        } else {
-            Regex::new(&pattern_str[1..])?
-        };
-
-        // Apply patterns
-        let results: Vec<String> = items
-            .iter()
-            .filter(|item| pattern.is_match(item))
-            .cloned()
-            .collect();
-
-        Ok(results)
-    }
-
     pub fn list_groups(&self) -> Vec<Group> {
         let groups: Vec<Group> = self.groups.values().cloned().collect();
         groups
     }
 }
 
+/// Returns the positions of the names matching a glob or `~regex` pattern. Names without
+/// wildcards are looked up instead of matched against every name.
+fn match_names<T>(items: &IndexMap<String, T>, pattern_str: &str) -> Result<Vec<usize>> {
+    if pattern_str == "all" {
+        return Ok((0..items.len()).collect());
+    }
+
+    if !pattern_str.starts_with('~') && !pattern_str.contains(['*', '?']) {
+        return Ok(items.get_index_of(pattern_str).into_iter().collect());
+    }
+
+    // Compile patterns
+    let pattern = if !pattern_str.starts_with('~') {
+        Regex::new(&glob_to_regex(pattern_str)?)?
+    } else {
+        Regex::new(&pattern_str[1..])?
+    };
+
+    // Apply patterns
+    Ok(items
+        .keys()
+        .enumerate()
+        .filter(|(_, item)| pattern.is_match(item))
+        .map(|(position, _)| position)
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -665,4 +879,221 @@ mod tests {
         let result = inventory_manager.apply_subscript(&hosts, -2, None);
         assert_eq!(result, vec!["host3".to_string()]);
     }
+
+    fn parse_basic_inventory() -> InventoryManager {
+        let base_dir = get_base_dir();
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+        inventory_manager
+    }
+
+    fn host_names(hosts: Vec<Host>) -> Vec<String> {
+        let mut names: Vec<String> = hosts.iter().map(|h| h.name().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_add_host() {
+        let mut inventory_manager = parse_basic_inventory();
+
+        let vars = IndexMap::from([("port".to_string(), Variable::String("22".to_string()))]);
+        inventory_manager
+            .add_host(
+                "new.example.com",
+                &["new".to_string(), "webservers".to_string()],
+                &vars,
+            )
+            .unwrap();
+        inventory_manager
+            .add_host("lone.example.com", &[], &IndexMap::new())
+            .unwrap();
+
+        assert_eq!(
+            host_names(inventory_manager.filter_hosts("new", None).unwrap()),
+            vec!["new.example.com"]
+        );
+        assert_eq!(
+            host_names(inventory_manager.filter_hosts("webservers", None).unwrap()),
+            vec!["bar.example.com", "foo.example.com", "new.example.com"]
+        );
+        assert_eq!(
+            host_names(inventory_manager.filter_hosts("ungrouped", None).unwrap()),
+            vec!["lone.example.com", "mail.example.com"]
+        );
+        assert!(inventory_manager.groups["all"].has_child_group("new"));
+
+        let host = inventory_manager.get_host("new.example.com").unwrap();
+        assert_eq!(host.vars()["port"], Variable::String("22".to_string()));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let mut inventory_manager = parse_basic_inventory();
+
+        inventory_manager
+            .group_by("mail.example.com", "debian", &["linux".to_string()])
+            .unwrap();
+        inventory_manager
+            .group_by("one.example.com", "debian", &["linux".to_string()])
+            .unwrap();
+
+        assert_eq!(
+            host_names(inventory_manager.filter_hosts("linux", None).unwrap()),
+            vec!["mail.example.com", "one.example.com"]
+        );
+        assert!(inventory_manager.groups["all"].has_child_group("linux"));
+        assert!(!inventory_manager.groups["all"].has_child_group("debian"));
+
+        // a host in a group is no longer ungrouped
+        assert!(inventory_manager
+            .filter_hosts("ungrouped", None)
+            .unwrap()
+            .is_empty());
+
+        let host = inventory_manager.get_host("mail.example.com").unwrap();
+        let groups: Vec<&str> = inventory_manager
+            .get_host_groups(host)
+            .iter()
+            .map(|g| g.name())
+            .collect();
+        assert!(groups.contains(&"debian") && groups.contains(&"linux"));
+
+        assert!(inventory_manager
+            .group_by("missing.example.com", "debian", &[])
+            .is_err());
+    }
+
+    #[test]
+    fn test_group_membership_follows_inventory_changes() {
+        let mut inventory_manager = parse_basic_inventory();
+
+        assert_eq!(
+            inventory_manager.get_groups_dict()["webservers"],
+            vec!["foo.example.com", "bar.example.com"]
+        );
+        assert!(inventory_manager
+            .filter_hosts("linux", None)
+            .unwrap()
+            .is_empty());
+
+        inventory_manager
+            .add_host(
+                "web3.example.com",
+                &["webservers".to_string()],
+                &IndexMap::new(),
+            )
+            .unwrap();
+        inventory_manager
+            .group_by("one.example.com", "debian", &["linux".to_string()])
+            .unwrap();
+        inventory_manager
+            .group_by("foo.example.com", "debian", &["linux".to_string()])
+            .unwrap();
+
+        let groups_dict = inventory_manager.get_groups_dict();
+        assert_eq!(
+            groups_dict["webservers"],
+            vec!["foo.example.com", "bar.example.com", "web3.example.com"]
+        );
+        assert_eq!(
+            groups_dict["linux"],
+            vec!["one.example.com", "foo.example.com"]
+        );
+        assert_eq!(groups_dict["all"].len(), 7);
+        assert_eq!(
+            host_names(inventory_manager.filter_hosts("linux", None).unwrap()),
+            vec!["foo.example.com", "one.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_filter_hosts_set_operations() {
+        let mut inventory_manager = parse_basic_inventory();
+        inventory_manager
+            .group_by("two.example.com", "prod", &["all".to_string()])
+            .unwrap();
+        inventory_manager
+            .group_by("foo.example.com", "prod", &["all".to_string()])
+            .unwrap();
+        let filter = |pattern: &str, limit: Option<&str>| -> Vec<String> {
+            let hosts = inventory_manager.filter_hosts(pattern, limit).unwrap();
+            hosts.iter().map(|h| h.name().to_string()).collect()
+        };
+
+        // hosts are returned in the order the patterns match them, once, and each group
+        // keeps its own host order
+        assert_eq!(
+            filter("prod,dbservers,foo.example.com", None),
+            vec![
+                "two.example.com",
+                "foo.example.com",
+                "one.example.com",
+                "three.example.com"
+            ]
+        );
+        assert_eq!(
+            filter("foo.example.com,dbservers", None),
+            vec![
+                "foo.example.com",
+                "one.example.com",
+                "two.example.com",
+                "three.example.com"
+            ]
+        );
+        assert_eq!(
+            filter("prod,&all", None),
+            vec!["two.example.com", "foo.example.com"]
+        );
+        assert_eq!(
+            filter("dbservers,!prod", None),
+            vec!["one.example.com", "three.example.com"]
+        );
+        assert_eq!(
+            filter("*.example.com,!~^(one|two)", Some("dbservers")),
+            vec!["three.example.com"]
+        );
+        // subscripts index a group in its own order
+        assert_eq!(
+            filter("prod[0],webservers[-1]", None),
+            vec!["two.example.com", "bar.example.com"]
+        );
+        assert_eq!(
+            filter("dbservers", Some("prod[0],one.example.com")),
+            vec!["one.example.com", "two.example.com"]
+        );
+        assert!(filter("missing", None).is_empty());
+
+        // the implicit localhost is matched by name only
+        assert_eq!(filter("localhost", None), vec!["localhost"]);
+        assert!(!filter("all", None).contains(&"localhost".to_string()));
+    }
+
+    #[test]
+    fn test_filter_hosts_by_expression() {
+        let inventory_manager = parse_basic_inventory();
+        let variable_manager = VariableManager::new(inventory_manager.get_base_dir());
+        variable_manager
+            .set_host_facts(
+                "one.example.com",
+                IndexMap::from([(
+                    "os_family".to_string(),
+                    Variable::String("Debian".to_string()),
+                )]),
+            )
+            .unwrap();
+
+        let hosts = inventory_manager.filter_hosts("all", None).unwrap();
+        let selected = inventory_manager
+            .filter_hosts_by_expression(hosts.clone(), "port > 5000", &variable_manager, None)
+            .unwrap();
+        assert_eq!(host_names(selected), vec!["foo.example.com"]);
+
+        // undefined facts compare as false
+        let selected = inventory_manager
+            .filter_hosts_by_expression(hosts, "os_family == 'Debian'", &variable_manager, None)
+            .unwrap();
+        assert_eq!(host_names(selected), vec!["one.example.com"]);
+    }
 }
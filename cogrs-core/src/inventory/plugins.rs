@@ -0,0 +1,56 @@
+mod constructed;
+pub(crate) mod ssh_config;
+mod yaml;
+
+use anyhow::Result;
+use cogrs_plugins::inventory::InventoryPlugin;
+use cogrs_plugins::plugin_loader::PluginLoader;
+use cogrs_plugins::plugin_type::PluginType;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+pub use constructed::ConstructedInventory;
+pub use ssh_config::SshConfigInventory;
+pub use yaml::YamlInventory;
+
+/// Inventory plugins by name, the built-in ones plus those found by the [`PluginLoader`].
+/// Inventories are parsed synchronously, so plugins are loaded once by
+/// [`load_inventory_plugins`] like lookup plugins are.
+static INVENTORY_PLUGINS: Lazy<RwLock<HashMap<String, Arc<dyn InventoryPlugin>>>> =
+    Lazy::new(|| RwLock::new(builtin_inventory_plugins()));
+
+fn builtin_inventory_plugins() -> HashMap<String, Arc<dyn InventoryPlugin>> {
+    HashMap::from([
+        (
+            "constructed".to_string(),
+            Arc::new(ConstructedInventory) as Arc<dyn InventoryPlugin>,
+        ),
+        ("ssh_config".to_string(), Arc::new(SshConfigInventory)),
+        ("yaml".to_string(), Arc::new(YamlInventory)),
+    ])
+}
+
+/// Registers an inventory plugin, replacing a built-in plugin with the same name.
+pub fn register_inventory_plugin(name: &str, plugin: Arc<dyn InventoryPlugin>) {
+    if let Ok(mut plugins) = INVENTORY_PLUGINS.write() {
+        plugins.insert(name.to_string(), plugin);
+    }
+}
+
+pub fn get_inventory_plugin(name: &str) -> Option<Arc<dyn InventoryPlugin>> {
+    INVENTORY_PLUGINS
+        .read()
+        .ok()
+        .and_then(|plugins| plugins.get(name).cloned())
+}
+
+/// Registers every inventory plugin found by the plugin loader.
+pub(crate) async fn load_inventory_plugins(loader: &mut PluginLoader) -> Result<()> {
+    for name in loader.get_plugin_names(&PluginType::Inventory) {
+        let plugin = loader.get_inventory_plugin(&name).await?;
+        register_inventory_plugin(&name, Arc::from(plugin));
+    }
+
+    Ok(())
+}
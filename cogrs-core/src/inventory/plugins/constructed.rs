@@ -0,0 +1,321 @@
+use crate::parsing::parser::read_inventory_file;
+use crate::template::Templar;
+use crate::vars::variable::{Number, Variable};
+use anyhow::{anyhow, bail, Result};
+use cogrs_plugins::inventory::{InventoryData, InventoryPlugin};
+use indexmap::IndexMap;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::path::Path;
+
+/// The `constructed` inventory plugin, it adds groups and variables to the hosts of the
+/// sources parsed before it, from Jinja expressions over the variables of each host.
+///
+/// ```yaml
+/// plugin: constructed
+/// compose:
+///   ansible_port: http_port | default(22)
+/// groups:
+///   webservers: "'web' in inventory_hostname"
+/// keyed_groups:
+///   - key: os_family
+///     prefix: os
+/// ```
+#[derive(Default)]
+pub struct ConstructedInventory;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConstructedConfig {
+    #[allow(dead_code)]
+    plugin: String,
+    /// Fail on expressions that can't be evaluated instead of skipping them.
+    #[serde(default)]
+    strict: bool,
+    /// Variables to set, from an expression each.
+    #[serde(default)]
+    compose: IndexMap<String, String>,
+    /// Groups to add hosts to, when a condition is true.
+    #[serde(default)]
+    groups: IndexMap<String, String>,
+    /// Groups named after the value of an expression.
+    #[serde(default)]
+    keyed_groups: Vec<KeyedGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyedGroup {
+    key: String,
+    #[serde(default)]
+    prefix: String,
+    #[serde(default = "default_separator")]
+    separator: String,
+    /// Group the keyed groups are added to as children.
+    parent_group: Option<String>,
+    /// Used when the key evaluates to an empty value.
+    default_value: Option<String>,
+    /// Keep the separator in front of the value when there is no prefix, like Ansible does.
+    #[serde(default = "default_true")]
+    leading_separator: bool,
+    /// Keep the separator after the prefix when the value is empty.
+    #[serde(default = "default_true")]
+    trailing_separator: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_separator() -> String {
+    "_".to_string()
+}
+
+impl InventoryPlugin for ConstructedInventory {
+    fn parse(&self, source: &str, inventory: &mut dyn InventoryData) -> Result<()> {
+        let content = read_inventory_file(Path::new(source))?;
+        let config: ConstructedConfig = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow!("Invalid constructed inventory {source}: {e}"))?;
+        let templar = Templar::new();
+
+        for host_name in inventory.hosts() {
+            let mut vars = IndexMap::new();
+            for (key, value) in inventory.host_vars(&host_name)? {
+                if let Value::String(key) = key {
+                    vars.insert(key, Variable::try_from(&value)?);
+                }
+            }
+            vars.insert(
+                "inventory_hostname".to_string(),
+                Variable::String(host_name.to_string()),
+            );
+
+            construct_host(&config, &templar, &host_name, &mut vars, inventory)
+                .map_err(|e| anyhow!("Unable to construct host {host_name}: {e:#}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn construct_host(
+    config: &ConstructedConfig,
+    templar: &Templar,
+    host_name: &str,
+    vars: &mut IndexMap<String, Variable>,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    // composed variables can be used by the groups and keyed groups below
+    for (key, expression) in &config.compose {
+        let Some(value) = evaluate(config, templar, expression, vars)? else {
+            continue;
+        };
+        inventory.set_host_variable(host_name, key, &Value::from(&value))?;
+        vars.insert(key.to_string(), value);
+    }
+
+    for (group_name, condition) in &config.groups {
+        let result = templar.evaluate_conditional(condition, vars);
+        if skip_errors(config, condition, result)? == Some(true) {
+            inventory.add_host(host_name, Some(group_name))?;
+        }
+    }
+
+    let invalid_chars = Regex::new(r"[^A-Za-z0-9_]")?;
+    for keyed_group in &config.keyed_groups {
+        let Some(value) = evaluate(config, templar, &keyed_group.key, vars)? else {
+            continue;
+        };
+
+        for key in key_values(&value, &keyed_group.separator)? {
+            let key = match (key.is_empty(), &keyed_group.default_value) {
+                (true, Some(default_value)) => default_value.to_string(),
+                _ => key,
+            };
+
+            let group_name = match (key.is_empty(), keyed_group.prefix.is_empty()) {
+                (true, _) if !keyed_group.trailing_separator => keyed_group.prefix.to_string(),
+                (_, true) if !keyed_group.leading_separator => key,
+                _ => format!("{}{}{}", keyed_group.prefix, keyed_group.separator, key),
+            };
+            let group_name = invalid_chars.replace_all(&group_name, "_").to_string();
+
+            inventory.add_host(host_name, Some(&group_name))?;
+            if let Some(parent_group) = &keyed_group.parent_group {
+                inventory.add_child(parent_group, &group_name)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Evaluates a Jinja expression to a native value.
+fn evaluate(
+    config: &ConstructedConfig,
+    templar: &Templar,
+    expression: &str,
+    vars: &IndexMap<String, Variable>,
+) -> Result<Option<Variable>> {
+    let template = Variable::String(format!("{{{{ {expression} }}}}"));
+    skip_errors(config, expression, templar.template(&template, vars))
+}
+
+/// Errors evaluating an expression are skipped unless the plugin is strict.
+fn skip_errors<T>(
+    config: &ConstructedConfig,
+    expression: &str,
+    result: Result<T>,
+) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if config.strict => bail!("Could not evaluate {expression}: {e:#}"),
+        Err(e) => {
+            debug!("Skipping {expression}, it could not be evaluated: {e:#}");
+            Ok(None)
+        }
+    }
+}
+
+/// The group keys for a keyed group value: a scalar, each item of a list, or
+/// `key<separator>value` for each entry of a dictionary.
+fn key_values(value: &Variable, separator: &str) -> Result<Vec<String>> {
+    match value {
+        Variable::Null => Ok(Vec::new()),
+        Variable::Sequence(items) => items.iter().map(scalar_key).collect(),
+        Variable::Mapping(mapping) => mapping
+            .iter()
+            .map(|(key, value)| Ok(format!("{key}{separator}{}", scalar_key(value)?)))
+            .collect(),
+        value => Ok(vec![scalar_key(value)?]),
+    }
+}
+
+fn scalar_key(value: &Variable) -> Result<String> {
+    match value {
+        Variable::String(value) => Ok(value.to_string()),
+        Variable::Bool(value) => Ok(value.to_string()),
+        Variable::Number(Number::Int(value)) => Ok(value.to_string()),
+        Variable::Number(Number::Float(value)) => Ok(value.to_string()),
+        Variable::Unsafe(value) => scalar_key(value),
+        value => {
+            bail!("Keyed group values should be strings, lists or dictionaries, got: {value:?}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory::group::Group;
+    use crate::inventory::host::Host;
+    use crate::parsing::parser::InventoryParser;
+    use crate::vars::variable::Variable;
+    use indexmap::IndexMap;
+    use tempfile::tempdir;
+
+    fn parse(sources: &[(&str, &str)]) -> (IndexMap<String, Group>, IndexMap<String, Host>) {
+        let dir = tempdir().unwrap();
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        for (name, content) in sources {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            InventoryParser::parse_source(path.to_str().unwrap(), &mut groups, &mut hosts).unwrap();
+        }
+        (groups, hosts)
+    }
+
+    const HOSTS: &str = "\
+[web]
+web1 os=linux tags=[\"blue\",\"canary\"] role=frontend
+web2 os=linux role=\"\"
+
+[db]
+db1 os=windows
+
+[web:vars]
+http_port=8080
+";
+
+    #[test]
+    fn test_constructed_groups_keyed_groups_and_compose() {
+        let (groups, hosts) = parse(&[
+            ("hosts.ini", HOSTS),
+            (
+                "constructed.yml",
+                r#"
+plugin: constructed
+compose:
+  cogrs_port: http_port | default(22) | int
+  fqdn: inventory_hostname ~ '.example.com'
+groups:
+  linux: os == 'linux'
+  has_fqdn: fqdn is defined
+  broken: undefined_var.attribute == 1
+keyed_groups:
+  - key: os
+    prefix: os
+    parent_group: by_os
+  - key: role
+    prefix: role
+    default_value: none
+  - key: tags
+  - key: "{'dc': 'eu-1'}"
+    prefix: ''
+    leading_separator: false
+"#,
+            ),
+        ]);
+
+        assert_eq!(
+            hosts["web1"].vars()["cogrs_port"],
+            Variable::Number(crate::vars::variable::Number::Int(8080))
+        );
+        assert_eq!(
+            hosts["db1"].vars()["cogrs_port"],
+            Variable::Number(crate::vars::variable::Number::Int(22))
+        );
+        assert_eq!(
+            hosts["db1"].vars()["fqdn"],
+            Variable::String("db1.example.com".to_string())
+        );
+        // hosts keep the source they were first defined in
+        assert!(matches!(
+            &hosts["db1"].vars()["inventory_file"],
+            Variable::String(file) if file.ends_with("hosts.ini")
+        ));
+
+        let members = |group: &str| groups[group].get_hosts(&groups, false).unwrap();
+        assert_eq!(members("linux"), vec!["web1", "web2"]);
+        assert_eq!(members("has_fqdn"), vec!["web1", "web2", "db1"]);
+        assert!(!groups.contains_key("broken"));
+        assert_eq!(members("os_linux"), vec!["web1", "web2"]);
+        assert_eq!(members("os_windows"), vec!["db1"]);
+        assert!(groups["by_os"].has_child_group("os_linux"));
+        assert!(groups["by_os"].has_child_group("os_windows"));
+        assert_eq!(members("role_frontend"), vec!["web1"]);
+        assert_eq!(members("role_none"), vec!["web2"]);
+        assert_eq!(members("_blue"), vec!["web1"]);
+        assert_eq!(members("_canary"), vec!["web1"]);
+        assert_eq!(members("dc_eu_1"), vec!["web1", "web2", "db1"]);
+    }
+
+    #[test]
+    fn test_constructed_strict() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("constructed.yml");
+        std::fs::write(
+            &path,
+            "plugin: constructed\nstrict: true\ngroups:\n  broken: undefined_var.attribute == 1\n",
+        )
+        .unwrap();
+
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::from([("web1".to_string(), Host::new("web1"))]);
+        let result = InventoryParser::parse_source(path.to_str().unwrap(), &mut groups, &mut hosts);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,491 @@
+use crate::inventory::utils::glob_to_regex;
+use crate::parsing::parser::read_inventory_file;
+use anyhow::{anyhow, bail, Result};
+use cogrs_plugins::inventory::{InventoryData, InventoryPlugin};
+use indexmap::IndexMap;
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How deep `Include` directives can be nested, the same limit as `ssh`.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The `ssh_config` inventory plugin, it adds the hosts of the `Host` blocks of an OpenSSH
+/// client configuration with their connection variables. `Include` directives are followed.
+///
+/// ```yaml
+/// plugin: ssh_config
+/// path: ~/.ssh/config
+/// ```
+///
+/// Options are looked up for each host like `ssh` does, the first value found in the blocks
+/// matching the host is used, so wildcard blocks act as defaults. Blocks with wildcard
+/// patterns other than `*` also become groups of the hosts they match, `Host *.prod.example.com`
+/// becomes `prod_example_com`. `Match` blocks are skipped.
+///
+/// A file named `ssh_config`, or `config` in a `.ssh` directory, can be used as an inventory
+/// source directly.
+#[derive(Default)]
+pub struct SshConfigInventory;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SshConfigOptions {
+    #[allow(dead_code)]
+    plugin: String,
+    /// The OpenSSH configuration file, relative to the inventory source.
+    #[serde(default = "default_path")]
+    path: String,
+}
+
+fn default_path() -> String {
+    "~/.ssh/config".to_string()
+}
+
+impl InventoryPlugin for SshConfigInventory {
+    fn parse(&self, source: &str, inventory: &mut dyn InventoryData) -> Result<()> {
+        let content = read_inventory_file(Path::new(source))?;
+        let options: SshConfigOptions = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow!("Invalid ssh_config inventory {source}: {e}"))?;
+
+        let base_dir = Path::new(source).parent().unwrap_or(Path::new("."));
+        parse_ssh_config(&resolve_path(&options.path, base_dir), inventory)
+    }
+}
+
+/// Whether a file is an OpenSSH client configuration, by its name.
+pub(crate) fn is_ssh_config(path: &Path) -> bool {
+    let file_name = path.file_name().and_then(|name| name.to_str());
+    let parent_name = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .and_then(|name| name.to_str());
+
+    matches!(
+        (file_name, parent_name),
+        (Some("ssh_config"), _) | (Some("config"), Some(".ssh"))
+    )
+}
+
+/// Adds the hosts of an OpenSSH client configuration file to the inventory.
+pub(crate) fn parse_ssh_config(path: &Path, inventory: &mut dyn InventoryData) -> Result<()> {
+    let mut options = Vec::new();
+    read_config(path, 0, &mut options)?;
+    let blocks = HostBlock::from_options(options)?;
+
+    let mut host_names: Vec<String> = Vec::new();
+    for block in &blocks {
+        for pattern in &block.patterns {
+            if !is_wildcard(pattern) && !pattern.starts_with('!') && !host_names.contains(pattern) {
+                host_names.push(pattern.to_string());
+            }
+        }
+    }
+
+    for host_name in &host_names {
+        debug!("Adding host {host_name} from {}", path.display());
+        inventory.add_host(host_name, None)?;
+
+        for (key, value) in host_vars(host_name, &blocks) {
+            inventory.set_host_variable(host_name, &key, &value)?;
+        }
+    }
+
+    for block in &blocks {
+        let Some(group_name) = block.group_name() else {
+            continue;
+        };
+
+        for host_name in &host_names {
+            if block.matches(host_name) {
+                inventory.add_host(host_name, Some(&group_name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the `keyword value` lines of a configuration file, with included files in place.
+fn read_config(path: &Path, depth: usize, options: &mut Vec<(String, String)>) -> Result<()> {
+    if depth > MAX_INCLUDE_DEPTH {
+        bail!("Include nested too deeply in {}", path.display());
+    }
+
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("Unable to read {}: {e}", path.display()))?;
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (keyword, value) = split_line(line);
+        if value.is_empty() {
+            bail!(
+                "{}: line {}: missing argument for {keyword}",
+                path.display(),
+                index + 1
+            );
+        }
+
+        if keyword == "include" {
+            for pattern in split_values(value) {
+                for include in expand_include(&resolve_path(&pattern, base_dir))? {
+                    read_config(&include, depth + 1, options)?;
+                }
+            }
+        } else {
+            options.push((keyword, value.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits a line into its lowercased keyword and its value, they are separated by
+/// whitespace or a `=`.
+fn split_line(line: &str) -> (String, &str) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let value = line[end..].trim_start();
+    let value = value.strip_prefix('=').unwrap_or(value).trim();
+
+    (line[..end].to_lowercase(), value)
+}
+
+/// Splits an argument list on whitespace, double quoted arguments can contain spaces.
+fn split_values(value: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in value.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    values.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        values.push(current);
+    }
+
+    values
+}
+
+fn expand_include(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = pattern
+        .to_str()
+        .ok_or_else(|| anyhow!("Invalid include path: {:?}", pattern))?;
+
+    let mut paths: Vec<PathBuf> = glob::glob(pattern)?
+        .filter_map(|path| path.ok())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Expands `~` and makes relative paths relative to `base_dir`.
+fn resolve_path(path: &str, base_dir: &Path) -> PathBuf {
+    let path = match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(relative), Some(home_dir)) => home_dir.join(relative),
+        _ => PathBuf::from(path),
+    };
+
+    match path.is_absolute() {
+        true => path,
+        false => base_dir.join(path),
+    }
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// The options of a `Host` block, the options before the first block apply to every host.
+struct HostBlock {
+    patterns: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl HostBlock {
+    fn from_options(options: Vec<(String, String)>) -> Result<Vec<HostBlock>> {
+        let mut blocks = vec![HostBlock {
+            patterns: vec!["*".to_string()],
+            options: Vec::new(),
+        }];
+        // options of `Match` blocks are skipped
+        let mut skipping = false;
+
+        for (keyword, value) in options {
+            match keyword.as_str() {
+                "host" => {
+                    skipping = false;
+                    blocks.push(HostBlock {
+                        patterns: split_values(&value),
+                        options: Vec::new(),
+                    });
+                }
+                "match" => {
+                    debug!("Skipping unsupported Match block: Match {value}");
+                    skipping = true;
+                }
+                _ if skipping => {}
+                _ => {
+                    if let Some(block) = blocks.last_mut() {
+                        block.options.push((keyword, value));
+                    }
+                }
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Like `ssh`, a host matches when it matches a pattern and none of the negated ones.
+    fn matches(&self, host_name: &str) -> bool {
+        let mut matched = false;
+
+        for pattern in &self.patterns {
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(pattern) => (true, pattern),
+                None => (false, pattern.as_str()),
+            };
+
+            let is_match = glob_to_regex(pattern)
+                .ok()
+                .and_then(|regex| Regex::new(&regex).ok())
+                .is_some_and(|regex| regex.is_match(host_name));
+
+            match (is_match, negated) {
+                (true, true) => return false,
+                (true, false) => matched = true,
+                _ => {}
+            }
+        }
+
+        matched
+    }
+
+    /// Blocks with wildcard patterns become groups, named after their patterns.
+    fn group_name(&self) -> Option<String> {
+        let patterns: Vec<&str> = self
+            .patterns
+            .iter()
+            .filter(|pattern| !pattern.starts_with('!'))
+            .map(|pattern| pattern.as_str())
+            .collect();
+        if !patterns.iter().any(|pattern| is_wildcard(pattern)) {
+            return None;
+        }
+
+        let name: String = patterns
+            .join("_")
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c,
+                false => '_',
+            })
+            .collect();
+        let name = name
+            .split('_')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// The connection variables of a host, from the first value of each option in the blocks
+/// matching it.
+fn host_vars(host_name: &str, blocks: &[HostBlock]) -> IndexMap<String, Value> {
+    let mut options: IndexMap<&str, &str> = IndexMap::new();
+    for block in blocks.iter().filter(|block| block.matches(host_name)) {
+        for (keyword, value) in &block.options {
+            options.entry(keyword.as_str()).or_insert(value.as_str());
+        }
+    }
+
+    let mut vars = IndexMap::new();
+    for (keyword, value) in options {
+        let value = split_values(value).join(" ");
+        let value = value.as_str();
+        let (key, value) = match keyword {
+            "hostname" => (
+                "ansible_host",
+                Value::from(value.replace("%h", host_name).replace("%%", "%")),
+            ),
+            "port" => (
+                "ansible_port",
+                match value.parse::<u16>() {
+                    Ok(port) => Value::from(port),
+                    Err(_) => Value::from(value),
+                },
+            ),
+            "user" => ("ansible_user", Value::from(value)),
+            "identityfile" if !value.eq_ignore_ascii_case("none") => (
+                "ansible_ssh_private_key_file",
+                Value::from(
+                    resolve_path(value, Path::new(""))
+                        .to_string_lossy()
+                        .as_ref(),
+                ),
+            ),
+            "proxyjump" if !value.eq_ignore_ascii_case("none") => {
+                ("ansible_ssh_proxy_jump", Value::from(value))
+            }
+            _ => continue,
+        };
+        vars.insert(key.to_string(), value);
+    }
+
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executor::connection_vars::ConnectionVars;
+    use crate::inventory::builder::InventoryBuilder;
+    use crate::inventory::group::Group;
+    use crate::inventory::host::Host;
+    use crate::parsing::parser::InventoryParser;
+    use crate::playbook::task::{Action, TaskBuilder};
+    use crate::vars::variable::{Number, Variable};
+    use indexmap::IndexMap;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const CONFIG: &str = "\
+# defaults before any block apply to every host
+User admin
+
+Host bastion
+    HostName bastion.example.com
+    Port 2222
+
+Host web1 web2
+    HostName %h.prod.example.com
+    ProxyJump bastion
+
+Host db1
+    HostName=10.0.0.5
+    User postgres
+
+Include conf.d/*.conf
+
+Match host db1
+    User ignored
+
+Host *.prod.example.com web?
+    IdentityFile /keys/prod
+    Port 22
+
+Host * !bastion
+    User ignored
+";
+
+    const INCLUDED: &str = "\
+Host cache1
+    HostName \"cache 1.example.com\"
+";
+
+    fn string(value: &str) -> Variable {
+        Variable::String(value.to_string())
+    }
+
+    #[test]
+    fn test_ssh_config_hosts_vars_and_groups() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("conf.d")).unwrap();
+        fs::write(dir.path().join("conf.d/cache.conf"), INCLUDED).unwrap();
+        let config = dir.path().join("ssh_config");
+        fs::write(&config, CONFIG).unwrap();
+
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        InventoryParser::parse_source(config.to_str().unwrap(), &mut groups, &mut hosts).unwrap();
+
+        assert_eq!(
+            hosts.keys().collect::<Vec<_>>(),
+            vec!["bastion", "web1", "web2", "db1", "cache1"]
+        );
+
+        let bastion = hosts["bastion"].vars();
+        assert_eq!(bastion["ansible_host"], string("bastion.example.com"));
+        assert_eq!(bastion["ansible_port"], Variable::Number(Number::Int(2222)));
+        assert_eq!(bastion["ansible_user"], string("admin"));
+
+        let web1 = hosts["web1"].vars();
+        assert_eq!(web1["ansible_host"], string("web1.prod.example.com"));
+        assert_eq!(web1["ansible_ssh_proxy_jump"], string("bastion"));
+        assert_eq!(web1["ansible_ssh_private_key_file"], string("/keys/prod"));
+        assert_eq!(web1["ansible_port"], Variable::Number(Number::Int(22)));
+        assert_eq!(web1["ansible_user"], string("admin"));
+
+        // the vars are the ones the ssh connection plugin is configured from
+        let task =
+            TaskBuilder::new("test", "ssh", Action::Module("ping".to_string(), None)).build();
+        let connection = ConnectionVars::resolve(&hosts["web1"], &task, web1).unwrap();
+        assert_eq!(connection.host(), "web1.prod.example.com");
+        assert_eq!(connection.proxy_jump(), Some("bastion"));
+
+        let db1 = hosts["db1"].vars();
+        assert_eq!(db1["ansible_host"], string("10.0.0.5"));
+        assert_eq!(db1["ansible_user"], string("admin"));
+        assert!(!db1.contains_key("ansible_ssh_private_key_file"));
+
+        assert_eq!(
+            hosts["cache1"].vars()["ansible_host"],
+            string("cache 1.example.com")
+        );
+
+        let group: &Group = &groups["prod_example_com_web"];
+        assert_eq!(
+            group.get_hosts(&groups, false).unwrap(),
+            vec!["web1", "web2"]
+        );
+        assert!(!groups.contains_key("bastion"));
+    }
+
+    #[test]
+    fn test_ssh_config_plugin_and_include_loop() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("hosts"),
+            "Host web1\n    HostName 10.0.0.1\n",
+        )
+        .unwrap();
+        let source = dir.path().join("ssh.yml");
+        fs::write(&source, "plugin: ssh_config\npath: hosts\n").unwrap();
+
+        let mut groups: IndexMap<String, Group> = IndexMap::new();
+        let mut hosts: IndexMap<String, Host> = IndexMap::new();
+        InventoryParser::parse_source(source.to_str().unwrap(), &mut groups, &mut hosts).unwrap();
+        assert_eq!(hosts["web1"].vars()["ansible_host"], string("10.0.0.1"));
+
+        let looping = dir.path().join("looping");
+        fs::write(&looping, format!("Include {}\n", looping.display())).unwrap();
+        let result = super::parse_ssh_config(
+            &looping,
+            &mut InventoryBuilder::new(&looping, &mut groups, &mut hosts),
+        );
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("nested too deeply"));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::inventory::utils::parse_host_pattern;
+use crate::parsing::parser::read_inventory_file;
+use anyhow::{bail, Result};
+use cogrs_plugins::inventory::{InventoryData, InventoryPlugin};
+use log::{debug, error, info, warn};
+use serde_yaml::Value;
+use std::path::Path;
+
+fn get_value_type(val: &Value) -> &str {
+    match val {
+        Value::String(_) => "String",
+        Value::Null => "Null",
+        Value::Bool(_) => "Bool",
+        Value::Number(_) => "Number",
+        Value::Sequence(_) => "Sequence",
+        Value::Mapping(_) => "Mapping",
+        Value::Tagged(_) => "Tagged",
+    }
+}
+
+/// The `yaml` inventory plugin, used for YAML inventories without a `plugin` key.
+#[derive(Default)]
+pub struct YamlInventory;
+
+impl InventoryPlugin for YamlInventory {
+    fn parse(&self, source: &str, inventory: &mut dyn InventoryData) -> Result<()> {
+        let content = read_inventory_file(Path::new(source))?;
+        let data: Value = serde_yaml::from_str(&content)?;
+
+        match data {
+            Value::Mapping(group_map) => {
+                for (key, val) in group_map {
+                    if let Value::String(group_name) = key {
+                        if let Value::Mapping(group_data) = val {
+                            parse_group(&group_name, &group_data, inventory)?;
+                        } else {
+                            error!(
+                                "YAML group has invalid structure, it should be a dictionary, got: {}",
+                                get_value_type(&val)
+                            );
+                        }
+                    }
+                }
+            }
+            _ => {
+                error!(
+                    "YAML inventory has invalid structure, it should be a dictionary, got: {}",
+                    get_value_type(&data)
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses a group and, depth first, its children. Children are linked to their parent
+/// once their own hosts are added, so those hosts inherit the parent as an ancestor.
+fn parse_group(
+    group_name: &str,
+    data: &serde_yaml::Mapping,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    debug!("Parsing {group_name} group");
+    inventory.add_group(group_name)?;
+
+    for (key, val) in data {
+        if let Value::String(key) = key {
+            match key.as_str() {
+                "vars" => parse_group_vars(group_name, val, inventory)?,
+                "hosts" => parse_group_hosts(group_name, val, inventory)?,
+                "children" => parse_group_children(group_name, val, inventory)?,
+                _ => log_unexpected_key(key, group_name),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses "vars" for the group.
+fn parse_group_vars(
+    group_name: &str,
+    val: &Value,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    info!("Parsing vars in group: {}", group_name);
+    if let Value::Mapping(val) = val {
+        for (key, val) in val.iter() {
+            if let Value::String(key) = key {
+                inventory.set_group_variable(group_name, key, val)?;
+            } else {
+                bail!(
+                    "YAML group has invalid structure, vars keys should be strings, got: {}",
+                    get_value_type(key)
+                )
+            }
+        }
+    } else {
+        bail!(
+            "YAML group has invalid structure, vars should be a dictionary, got: {}",
+            get_value_type(val)
+        )
+    }
+
+    Ok(())
+}
+
+fn populate_host_vars(
+    host_name: &str,
+    vars: &Value,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    if let Value::Mapping(vars) = vars {
+        for (key, val) in vars.iter() {
+            if let Value::String(key) = key {
+                inventory.set_host_variable(host_name, key, val)?;
+            } else {
+                bail!(
+                    "YAML host has invalid structure, vars keys should be strings, got: {}",
+                    get_value_type(key)
+                )
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses "hosts" for the group.
+fn parse_group_hosts(
+    group_name: &str,
+    val: &Value,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    if let Value::Mapping(val) = val {
+        for (host_key, host_data) in val {
+            if let Value::String(host_pattern) = host_key {
+                let new_hosts = parse_host_pattern(host_pattern)?;
+                for host_name in new_hosts {
+                    inventory.add_host(&host_name, Some(group_name))?;
+                    populate_host_vars(&host_name, host_data, inventory)?;
+                }
+            }
+        }
+    } else {
+        error!(
+            "YAML group has invalid structure, hosts should be a dictionary, got: {}",
+            get_value_type(val)
+        );
+    }
+    Ok(())
+}
+
+/// Parses "children" for the group and adds them to it.
+fn parse_group_children(
+    group_name: &str,
+    val: &Value,
+    inventory: &mut dyn InventoryData,
+) -> Result<()> {
+    if let Value::Mapping(val) = val {
+        for (child_key, child_val) in val {
+            if let Value::String(child_group_name) = child_key {
+                let child_data = match child_val {
+                    Value::Mapping(data) => data.clone(),
+                    Value::Null => {
+                        debug!("Parsing empty child group: {child_group_name}");
+                        serde_yaml::Mapping::new()
+                    }
+                    _ => {
+                        error!(
+                            "YAML group 'children' field has invalid structure, expected dictionary or null, got: {}",
+                            get_value_type(child_val)
+                        );
+                        continue;
+                    }
+                };
+                parse_group(child_group_name, &child_data, inventory)?;
+                inventory.add_child(group_name, child_group_name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Logs a warning for an unexpected key in the group.
+fn log_unexpected_key(key: &str, group_name: &str) {
+    warn!(
+        "Skipping unexpected key \"{key}\" in group \"{group_name}\", only \"vars\", \"children\" and \"hosts\" are valid"
+    );
+}
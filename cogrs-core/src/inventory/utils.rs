@@ -121,18 +121,34 @@ pub fn parse_host_pattern(pattern: &str) -> anyhow::Result<Vec<String>> {
             .get(3)
             .map_or(1, |m| m.as_str().parse::<usize>().unwrap_or(1)); // Default stride is 1
 
+        if stride == 0 {
+            bail!("Invalid stride in pattern: {}", pattern);
+        }
+
         // Determine if the range is numeric or alphabetic
         if let (Ok(start_num), Ok(end_num)) = (start.parse::<usize>(), end.parse::<usize>()) {
+            if start_num > end_num {
+                bail!("Invalid range in pattern, {start} is after {end}: {pattern}");
+            }
+            // Leading zeros on the start, as in [01:10], keep every value at that width
+            let width = match start.starts_with('0') {
+                true => start.len(),
+                false => 0,
+            };
             // Generate numeric range values with the given stride
             for i in (start_num..=end_num).step_by(stride) {
                 // Replace the full pattern in the original string with the current value
-                let generated_host = pattern.replace(full_match, &i.to_string());
+                let generated_host = pattern.replace(full_match, &format!("{i:0width$}"));
                 hosts.push(generated_host);
             }
         } else if let (Some(start_char), Some(end_char)) =
             (start.chars().next(), end.chars().next())
         {
             if start_char.is_alphabetic() && end_char.is_alphabetic() {
+                if start_char > end_char {
+                    bail!("Invalid range in pattern, {start} is after {end}: {pattern}");
+                }
+
                 // Generate alphabetic range values with the given stride
                 let mut current = start_char as u32;
 
@@ -300,6 +316,19 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_numeric_pattern_keeps_leading_zeros() {
+        let pattern = "web[08:11].example.com";
+        let expected = vec![
+            "web08.example.com",
+            "web09.example.com",
+            "web10.example.com",
+            "web11.example.com",
+        ];
+        let result = parse_host_pattern(pattern).unwrap();
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn test_pattern_with_no_matches() {
         let pattern = "host";
@@ -328,6 +357,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_reversed_and_zero_stride_patterns() {
+        assert!(parse_host_pattern("host[5:1]").is_err());
+        assert!(parse_host_pattern("host[c:a]").is_err());
+        assert!(parse_host_pattern("host[1:5:0]").is_err());
+        assert!(parse_host_pattern("host[a:c:0]").is_err());
+    }
+
     #[test]
     fn test_multiple_patterns_in_string() {
         let pattern = "host[0:3]-region[a:c]";
@@ -1,9 +1,11 @@
 pub mod adhoc;
-mod cli;
+pub mod cache;
+pub mod cli;
 pub mod config;
 pub mod constants;
 pub mod executor;
 pub mod inventory;
+pub mod lookup;
 pub mod parsing;
 pub mod playbook;
 pub mod strategy;
@@ -0,0 +1,362 @@
+mod env;
+mod file;
+mod fileglob;
+mod first_found;
+mod password;
+mod pipe;
+mod template;
+mod vars;
+
+use anyhow::{bail, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use cogrs_plugins::plugin_loader::PluginLoader;
+use cogrs_plugins::plugin_type::PluginType;
+use log::warn;
+use minijinja::value::{Kwargs, Rest};
+use minijinja::{context, Environment, Error, ErrorKind, State, Value};
+use once_cell::sync::Lazy;
+use serde_json::Map;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Lookup plugins by name, the built-in ones plus those found by the [`PluginLoader`].
+/// Lookups run while a template is rendered, outside of any async context, so plugins are
+/// loaded once by [`load_lookup_plugins`] instead of going through the loader on every call.
+static LOOKUP_PLUGINS: Lazy<RwLock<HashMap<String, Arc<dyn LookupPlugin>>>> =
+    Lazy::new(|| RwLock::new(builtin_lookup_plugins()));
+
+fn builtin_lookup_plugins() -> HashMap<String, Arc<dyn LookupPlugin>> {
+    HashMap::from([
+        (
+            "env".to_string(),
+            Arc::new(env::EnvLookup) as Arc<dyn LookupPlugin>,
+        ),
+        ("file".to_string(), Arc::new(file::FileLookup)),
+        ("fileglob".to_string(), Arc::new(fileglob::FileglobLookup)),
+        (
+            "first_found".to_string(),
+            Arc::new(first_found::FirstFoundLookup),
+        ),
+        ("password".to_string(), Arc::new(password::PasswordLookup)),
+        ("pipe".to_string(), Arc::new(pipe::PipeLookup)),
+        ("template".to_string(), Arc::new(template::TemplateLookup)),
+        ("vars".to_string(), Arc::new(vars::VarsLookup)),
+    ])
+}
+
+/// Registers a lookup plugin, replacing a built-in lookup with the same name.
+pub fn register_lookup_plugin(name: &str, plugin: Arc<dyn LookupPlugin>) {
+    if let Ok(mut plugins) = LOOKUP_PLUGINS.write() {
+        plugins.insert(name.to_string(), plugin);
+    }
+}
+
+pub fn get_lookup_plugin(name: &str) -> Option<Arc<dyn LookupPlugin>> {
+    LOOKUP_PLUGINS
+        .read()
+        .ok()
+        .and_then(|plugins| plugins.get(name).cloned())
+}
+
+/// Registers every lookup plugin found by the plugin loader.
+pub(crate) async fn load_lookup_plugins(loader: &mut PluginLoader) -> Result<()> {
+    for name in loader.get_plugin_names(&PluginType::Lookup) {
+        let plugin = loader.get_lookup_plugin(&name).await?;
+        register_lookup_plugin(&name, Arc::from(plugin));
+    }
+
+    Ok(())
+}
+
+/// Registers the `lookup()`, `query()` and `q()` template functions.
+pub(crate) fn register(env: &mut Environment<'static>) {
+    env.add_function("lookup", lookup);
+    env.add_function("query", query);
+    env.add_function("q", query);
+}
+
+/// [`LookupContext`] backed by the state of the template calling the lookup.
+struct StateContext<'a, 'env, 'source> {
+    state: &'a State<'env, 'source>,
+}
+
+impl LookupContext for StateContext<'_, '_, '_> {
+    fn get_var(&self, name: &str) -> Option<serde_json::Value> {
+        self.state
+            .lookup(name)
+            .filter(|value| !value.is_undefined())
+            .and_then(|value| serde_json::to_value(value).ok())
+    }
+
+    fn template(
+        &self,
+        source: &str,
+        extra_vars: Option<&Map<String, serde_json::Value>>,
+    ) -> Result<String> {
+        let vars = self.state.lookup("vars").unwrap_or_default();
+        let ctx = match extra_vars {
+            Some(extra_vars) => {
+                let extra_vars = Value::from_serialize(extra_vars);
+                context! { ..extra_vars, ..vars }
+            }
+            None => vars,
+        };
+
+        Ok(self.state.env().render_str(source, ctx)?)
+    }
+}
+
+fn lookup_error(name: &str, e: anyhow::Error) -> Error {
+    Error::new(
+        ErrorKind::InvalidOperation,
+        format!(
+            "An unhandled exception occurred while running the lookup plugin '{}'. Error was: {:#}",
+            name, e
+        ),
+    )
+}
+
+fn run_lookup(
+    state: &State,
+    name: &str,
+    terms: &[Value],
+    kwargs: Kwargs,
+    wantlist: bool,
+) -> Result<Value, Error> {
+    let mut wantlist = wantlist;
+    let mut errors = "strict".to_string();
+    let mut options = Map::new();
+
+    for key in kwargs.args() {
+        match key {
+            "wantlist" => wantlist = kwargs.get::<bool>(key)?,
+            "errors" => errors = kwargs.get::<String>(key)?,
+            _ => {
+                let value: Value = kwargs.get(key)?;
+                options.insert(
+                    key.to_string(),
+                    serde_json::to_value(&value).map_err(|e| lookup_error(name, e.into()))?,
+                );
+            }
+        }
+    }
+
+    if !["strict", "warn", "ignore"].contains(&errors.as_str()) {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            "Valid values for the 'errors' option are 'strict', 'warn' or 'ignore'",
+        ));
+    }
+
+    let Some(plugin) = get_lookup_plugin(name) else {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("lookup plugin ({}) not found", name),
+        ));
+    };
+
+    let terms = terms
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| lookup_error(name, e.into()))?;
+
+    let results = match plugin.run(&terms, &options, &StateContext { state }) {
+        Ok(results) => results,
+        Err(e) if errors == "strict" => return Err(lookup_error(name, e)),
+        Err(e) => {
+            if errors == "warn" {
+                warn!("Lookup plugin '{}' failed: {:#}", name, e);
+            }
+            return Ok(if wantlist {
+                Value::from(Vec::<Value>::new())
+            } else {
+                Value::from(())
+            });
+        }
+    };
+
+    if wantlist {
+        return Ok(Value::from_serialize(&results));
+    }
+
+    // like ansible, lists of strings are joined with commas and single values are unwrapped
+    let strings: Option<Vec<&str>> = results.iter().map(|result| result.as_str()).collect();
+    Ok(match strings {
+        Some(strings) => Value::from(strings.join(",")),
+        None if results.len() == 1 => Value::from_serialize(&results[0]),
+        None => Value::from_serialize(&results),
+    })
+}
+
+/// `lookup(name, *terms, wantlist=false, errors='strict', **options)`
+fn lookup(state: &State, name: &str, terms: Rest<Value>, kwargs: Kwargs) -> Result<Value, Error> {
+    run_lookup(state, name, &terms, kwargs, false)
+}
+
+/// `query(name, *terms, errors='strict', **options)`, always returns a list.
+fn query(state: &State, name: &str, terms: Rest<Value>, kwargs: Kwargs) -> Result<Value, Error> {
+    run_lookup(state, name, &terms, kwargs, true)
+}
+
+fn term_str(term: &serde_json::Value) -> Result<&str> {
+    match term.as_str() {
+        Some(term) => Ok(term),
+        None => bail!("Lookup terms must be strings, got {}", term),
+    }
+}
+
+fn bool_option(
+    options: &Map<String, serde_json::Value>,
+    name: &str,
+    default: bool,
+) -> Result<bool> {
+    match options.get(name) {
+        None | Some(serde_json::Value::Null) => Ok(default),
+        Some(serde_json::Value::Bool(value)) => Ok(*value),
+        Some(serde_json::Value::String(value)) => match value.to_lowercase().as_str() {
+            "yes" | "on" | "true" | "1" => Ok(true),
+            "no" | "off" | "false" | "0" => Ok(false),
+            _ => bail!("Option '{}' must be a boolean, got '{}'", name, value),
+        },
+        Some(value) => bail!("Option '{}' must be a boolean, got {}", name, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template::Templar;
+    use crate::vars::variable::Variable;
+    use indexmap::IndexMap;
+
+    fn render(template: &str, vars: &[(&str, Variable)]) -> Result<Variable> {
+        let vars: IndexMap<String, Variable> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        Templar::new().template(&Variable::String(template.to_string()), &vars)
+    }
+
+    #[test]
+    fn test_lookup_joins_and_query_returns_lists() {
+        let vars = [
+            ("a", Variable::String("x".to_string())),
+            ("b", Variable::String("y".to_string())),
+        ];
+
+        assert_eq!(
+            render("{{ lookup('vars', 'a', 'b') }}", &vars).unwrap(),
+            Variable::String("x,y".to_string())
+        );
+        assert_eq!(
+            render("{{ query('vars', 'a', 'b') }}", &vars).unwrap(),
+            Variable::Sequence(vec![
+                Variable::String("x".to_string()),
+                Variable::String("y".to_string())
+            ])
+        );
+        assert_eq!(
+            render("{{ lookup('vars', 'a', wantlist=true) | length }}", &vars).unwrap(),
+            render("{{ q('vars', 'b') | length }}", &vars).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lookup_errors() {
+        let err = render("{{ lookup('missing_plugin', 'x') }}", &[]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("lookup plugin (missing_plugin) not found"));
+
+        let err = render("{{ lookup('vars', 'undefined_var') }}", &[]).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("No variable found with this name: undefined_var"),
+            "{}",
+            err
+        );
+
+        assert_eq!(
+            render("{{ query('vars', 'undefined_var', errors='ignore') }}", &[]).unwrap(),
+            Variable::Sequence(vec![])
+        );
+    }
+
+    #[test]
+    fn test_file_lookups() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("files")).unwrap();
+        std::fs::write(dir.path().join("files/a.conf"), "alpha\n").unwrap();
+        std::fs::write(dir.path().join("files/b.conf"), "beta\n").unwrap();
+        let vars = [("playbook_dir", Variable::Path(dir.path().to_path_buf()))];
+
+        assert_eq!(
+            render("{{ lookup('file', 'a.conf', 'b.conf') }}", &vars).unwrap(),
+            Variable::String("alpha,beta".to_string())
+        );
+        assert_eq!(
+            render("{{ query('fileglob', '*.conf') | length }}", &vars).unwrap(),
+            render("{{ 2 }}", &[]).unwrap()
+        );
+        assert_eq!(
+            render(
+                "{{ lookup('first_found', ['missing.conf', 'b.conf']) }}",
+                &vars
+            )
+            .unwrap(),
+            Variable::String(
+                dir.path()
+                    .join("files/b.conf")
+                    .to_string_lossy()
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            render(
+                "{{ lookup('first_found', 'missing.conf', skip=true) }}",
+                &vars
+            )
+            .unwrap(),
+            Variable::String(String::new())
+        );
+        assert_eq!(
+            render("{{ lookup('pipe', 'echo piped') }}", &[]).unwrap(),
+            Variable::String("piped".to_string())
+        );
+        assert_eq!(
+            render(
+                "{{ lookup('env', 'COGRS_TEST_MISSING_ENV', default='unset') }}",
+                &[]
+            )
+            .unwrap(),
+            Variable::String("unset".to_string())
+        );
+    }
+
+    #[test]
+    fn test_template_lookup_uses_calling_context() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("templates")).unwrap();
+        std::fs::write(
+            dir.path().join("templates/motd.j2"),
+            "Welcome to {{ name }}, port {{ port }}\n",
+        )
+        .unwrap();
+
+        let vars = [
+            ("playbook_dir", Variable::Path(dir.path().to_path_buf())),
+            ("name", Variable::String("{{ prefix }}-web".to_string())),
+            ("prefix", Variable::String("prod".to_string())),
+        ];
+
+        assert_eq!(
+            render(
+                "{{ lookup('template', 'motd.j2', template_vars={'port': 8080}) }}",
+                &vars
+            )
+            .unwrap(),
+            Variable::String("Welcome to prod-web, port 8080\n".to_string())
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use super::term_str;
+use anyhow::Result;
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+
+/// `lookup('env', 'HOME', default='')`, reads environment variables of the controller.
+pub(super) struct EnvLookup;
+
+impl LookupPlugin for EnvLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        _context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        let default = options
+            .get("default")
+            .cloned()
+            .unwrap_or_else(|| Value::String(String::new()));
+
+        terms
+            .iter()
+            .map(|term| {
+                Ok(std::env::var(term_str(term)?)
+                    .map(Value::String)
+                    .unwrap_or_else(|_| default.clone()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,42 @@
+use super::{bool_option, term_str};
+use anyhow::{format_err, Context, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+use std::fs;
+
+/// `lookup('file', 'path', rstrip=true, lstrip=false)`, returns the content of files
+/// found in the `files` directories of the role and playbook.
+pub(super) struct FileLookup;
+
+impl LookupPlugin for FileLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        let rstrip = bool_option(options, "rstrip", true)?;
+        let lstrip = bool_option(options, "lstrip", false)?;
+
+        terms
+            .iter()
+            .map(|term| {
+                let name = term_str(term)?;
+                let path = context
+                    .find_file("files", name)
+                    .ok_or_else(|| format_err!("could not locate file in lookup: {}", name))?;
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+
+                let content = match (lstrip, rstrip) {
+                    (true, true) => content.trim(),
+                    (true, false) => content.trim_start(),
+                    (false, true) => content.trim_end(),
+                    (false, false) => content.as_str(),
+                };
+
+                Ok(Value::String(content.to_string()))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,45 @@
+use super::term_str;
+use anyhow::{Context, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// `lookup('fileglob', '*.conf')`, lists files matching a pattern. Relative patterns are
+/// matched in the `files` directories of the role and playbook.
+pub(super) struct FileglobLookup;
+
+impl LookupPlugin for FileglobLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        _options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        let mut results = Vec::new();
+
+        for term in terms {
+            let pattern = Path::new(term_str(term)?);
+            let Some(file_pattern) = pattern.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let dir = match pattern.parent().and_then(|dir| dir.to_str()) {
+                Some("") | None => ".",
+                Some(dir) => dir,
+            };
+
+            let Some(dir) = context.find_file("files", dir) else {
+                continue;
+            };
+
+            let full_pattern = dir.join(file_pattern);
+            let paths = glob::glob(&full_pattern.to_string_lossy())
+                .with_context(|| format!("Invalid glob pattern {}", full_pattern.display()))?;
+
+            for path in paths.filter_map(Result::ok).filter(|path| path.is_file()) {
+                results.push(Value::String(path.to_string_lossy().to_string()));
+            }
+        }
+
+        Ok(results)
+    }
+}
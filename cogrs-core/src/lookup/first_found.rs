@@ -0,0 +1,92 @@
+use super::bool_option;
+use anyhow::{bail, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// `lookup('first_found', ['a.yml', 'b.yml'], paths=[...], skip=false)`, returns the path
+/// of the first file which exists. Terms may also be dictionaries with `files` and `paths`.
+pub(super) struct FirstFoundLookup;
+
+/// Flattens a string or list of strings, splitting strings on any of `separators`.
+fn split_values(value: &Value, separators: &[char], into: &mut Vec<String>) -> Result<()> {
+    match value {
+        Value::Null => {}
+        Value::String(value) => into.extend(
+            value
+                .split(separators)
+                .map(str::trim)
+                .filter(|part| !part.is_empty())
+                .map(str::to_string),
+        ),
+        Value::Array(values) => {
+            for value in values {
+                split_values(value, separators, into)?;
+            }
+        }
+        _ => bail!("Invalid term supplied to first_found: {}", value),
+    }
+
+    Ok(())
+}
+
+impl LookupPlugin for FirstFoundLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        let skip = bool_option(options, "skip", false)?;
+        let mut files = Vec::new();
+        let mut paths = Vec::new();
+
+        if let Some(value) = options.get("files") {
+            split_values(value, &[',', ';'], &mut files)?;
+        }
+        if let Some(value) = options.get("paths") {
+            split_values(value, &[',', ':', ';'], &mut paths)?;
+        }
+
+        let mut pending: Vec<&Value> = terms.iter().rev().collect();
+        while let Some(term) = pending.pop() {
+            match term {
+                Value::Array(values) => pending.extend(values.iter().rev()),
+                Value::Object(term) => {
+                    if let Some(value) = term.get("files") {
+                        split_values(value, &[',', ';'], &mut files)?;
+                    }
+                    if let Some(value) = term.get("paths") {
+                        split_values(value, &[',', ':', ';'], &mut paths)?;
+                    }
+                }
+                term => split_values(term, &[',', ';'], &mut files)?,
+            }
+        }
+
+        let candidates: Vec<String> = if paths.is_empty() {
+            files
+        } else {
+            paths
+                .iter()
+                .flat_map(|path| {
+                    files
+                        .iter()
+                        .map(move |file| Path::new(path).join(file).to_string_lossy().to_string())
+                })
+                .collect()
+        };
+
+        for candidate in candidates {
+            if let Some(path) = context.find_file("files", &candidate) {
+                return Ok(vec![Value::String(path.to_string_lossy().to_string())]);
+            }
+        }
+
+        if skip {
+            return Ok(Vec::new());
+        }
+
+        bail!("No file was found when using first_found.")
+    }
+}
@@ -0,0 +1,371 @@
+use super::term_str;
+use crate::parsing::splitter::parse_kv;
+use crate::template::filters::{random_salt, sha_crypt};
+use anyhow::{bail, Context, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ring::digest;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_LENGTH: usize = 20;
+const DEFAULT_CHARS: &[&str] = &["ascii_letters", "digits", ".,:-_"];
+const VALID_PARAMS: &[&str] = &["length", "encrypt", "chars", "seed"];
+const ENCRYPT_METHODS: &[&str] = &["sha512_crypt", "sha256_crypt"];
+
+const ASCII_LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const ASCII_UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const PUNCTUATION: &str = r##"!"#$%&'()*+,-./:;<=>?@[\]^_`{|}~"##;
+
+/// `lookup('password', '/path/to/file length=20 chars=ascii_letters,digits encrypt=sha512_crypt')`
+///
+/// Returns the password stored in the file, generating a random one and writing it to the
+/// file first if it does not exist yet. Relative paths are relative to the playbook
+/// directory, `/dev/null` generates a new password on every call without storing it.
+/// With `encrypt`, the salt is stored next to the password so the hash stays stable.
+pub(super) struct PasswordLookup;
+
+#[derive(Debug)]
+struct PasswordParams {
+    length: usize,
+    encrypt: Option<String>,
+    chars: Vec<char>,
+    seed: Option<String>,
+}
+
+/// Resolves `chars` entries, names of python `string` constants are expanded.
+fn expand_chars(specs: &[String]) -> Vec<char> {
+    let mut chars: Vec<char> = Vec::new();
+
+    for spec in specs {
+        let expanded = match spec.as_str() {
+            "ascii_letters" => format!("{}{}", ASCII_LOWERCASE, ASCII_UPPERCASE),
+            "ascii_lowercase" => ASCII_LOWERCASE.to_string(),
+            "ascii_uppercase" => ASCII_UPPERCASE.to_string(),
+            "digits" => DIGITS.to_string(),
+            "hexdigits" => "0123456789abcdefABCDEF".to_string(),
+            "octdigits" => "01234567".to_string(),
+            "punctuation" => PUNCTUATION.to_string(),
+            other => other.to_string(),
+        };
+
+        for c in expanded.chars() {
+            if !chars.contains(&c) {
+                chars.push(c);
+            }
+        }
+    }
+
+    chars
+}
+
+/// Splits a `chars` string on commas, `,,` stands for a literal comma.
+fn split_chars(chars: &str) -> Vec<String> {
+    let mut specs = Vec::new();
+
+    if chars.contains(",,") {
+        specs.push(",".to_string());
+    }
+    specs.extend(
+        chars
+            .replace(",,", ",")
+            .split(',')
+            .filter(|spec| !spec.is_empty())
+            .map(str::to_string),
+    );
+
+    specs
+}
+
+fn option_string(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+impl PasswordParams {
+    fn new(options: &Map<String, Value>, term_params: &Map<String, Value>) -> Result<Self> {
+        let mut params = PasswordParams {
+            length: DEFAULT_LENGTH,
+            encrypt: None,
+            chars: Vec::new(),
+            seed: None,
+        };
+        let mut chars: Vec<String> = DEFAULT_CHARS.iter().map(|s| s.to_string()).collect();
+
+        let invalid: Vec<&str> = term_params
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !VALID_PARAMS.contains(key))
+            .collect();
+        if !invalid.is_empty() {
+            bail!(
+                "Unrecognized parameter(s) given to password lookup: {}",
+                invalid.join(", ")
+            );
+        }
+
+        // parameters given in the term take precedence over keyword arguments
+        for (key, value) in options.iter().chain(term_params.iter()) {
+            match (key.as_str(), value) {
+                (_, Value::Null) => {}
+                ("length", value) => {
+                    params.length = option_string(value)
+                        .parse()
+                        .with_context(|| format!("Invalid password length: {}", value))?
+                }
+                ("encrypt", value) => params.encrypt = Some(option_string(value)),
+                ("seed", value) => params.seed = Some(option_string(value)),
+                ("chars", Value::Array(values)) => {
+                    chars = values.iter().map(option_string).collect()
+                }
+                ("chars", value) => chars = split_chars(&option_string(value)),
+                _ => {}
+            }
+        }
+
+        params.chars = expand_chars(&chars);
+        if params.chars.is_empty() {
+            bail!("The password lookup needs at least one character to choose from");
+        }
+
+        // checked before anything is written to the password file
+        if let Some(encrypt) = &params.encrypt {
+            if !ENCRYPT_METHODS.contains(&encrypt.as_str()) {
+                bail!(
+                    "Unsupported encryption method for password lookup: {}",
+                    encrypt
+                );
+            }
+        }
+
+        Ok(params)
+    }
+}
+
+/// Splits a term into the path and its `key=value` parameters.
+fn parse_term(term: &str) -> Result<(String, Map<String, Value>)> {
+    let term = term.trim();
+    let (path, params) = match term.split_once(char::is_whitespace) {
+        Some((path, params)) => (path, parse_kv(params)?),
+        None => (term, Default::default()),
+    };
+
+    let params = params
+        .into_iter()
+        .map(|(key, value)| (key, Value::String(value)))
+        .collect();
+
+    Ok((path.to_string(), params))
+}
+
+fn generate_password(params: &PasswordParams) -> String {
+    let mut rng = match &params.seed {
+        Some(seed) => {
+            let seed = digest::digest(&digest::SHA256, seed.as_bytes());
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(seed.as_ref());
+            StdRng::from_seed(bytes)
+        }
+        None => StdRng::from_os_rng(),
+    };
+
+    (0..params.length)
+        .map(|_| params.chars[rng.random_range(0..params.chars.len())])
+        .collect()
+}
+
+/// Splits stored content into the password and salt, written as `password salt=...`.
+fn parse_content(content: &str) -> (String, Option<String>) {
+    match content.rsplit_once(" salt=") {
+        Some((password, salt)) => {
+            // ansible stores bcrypt idents after the salt, the sha-crypt hashes don't use them
+            let salt = salt.split(" ident=").next().unwrap_or(salt);
+            (password.to_string(), Some(salt.to_string()))
+        }
+        None => (content.to_string(), None),
+    }
+}
+
+fn write_password_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write password file {}", path.display()))?;
+    file.write_all(format!("{}\n", content).as_bytes())?;
+
+    Ok(())
+}
+
+fn resolve_path(path: &str, context: &dyn LookupContext) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    match context.get_var("playbook_dir") {
+        Some(Value::String(dir)) => Path::new(&dir).join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl LookupPlugin for PasswordLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        terms
+            .iter()
+            .map(|term| {
+                let (path, term_params) = parse_term(term_str(term)?)?;
+                let params = PasswordParams::new(options, &term_params)?;
+                let path = resolve_path(&path, context);
+                let persist = path != Path::new("/dev/null");
+
+                let (password, mut salt, exists) = match persist && path.exists() {
+                    true => {
+                        let content = fs::read_to_string(&path).with_context(|| {
+                            format!("Failed to read password file {}", path.display())
+                        })?;
+                        let (password, salt) = parse_content(content.trim_end());
+                        (password, salt, true)
+                    }
+                    false => (generate_password(&params), None, false),
+                };
+
+                let mut changed = !exists;
+                if params.encrypt.is_some() && salt.is_none() {
+                    salt = Some(random_salt(16));
+                    changed = true;
+                }
+
+                if persist && changed {
+                    let content = match &salt {
+                        Some(salt) => format!("{} salt={}", password, salt),
+                        None => password.clone(),
+                    };
+                    write_password_file(&path, &content)?;
+                }
+
+                let result = match (params.encrypt.as_deref(), salt) {
+                    (Some(encrypt), Some(salt)) => {
+                        sha_crypt(&password, &salt, None, encrypt == "sha512_crypt")?
+                    }
+                    _ => password,
+                };
+
+                Ok(Value::String(result))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestContext(PathBuf);
+
+    impl LookupContext for TestContext {
+        fn get_var(&self, name: &str) -> Option<Value> {
+            (name == "playbook_dir").then(|| Value::String(self.0.to_string_lossy().to_string()))
+        }
+
+        fn template(&self, source: &str, _: Option<&Map<String, Value>>) -> Result<String> {
+            Ok(source.to_string())
+        }
+    }
+
+    fn lookup(term: &str, context: &TestContext) -> Result<String> {
+        let results = PasswordLookup.run(&[Value::from(term)], &Map::new(), context)?;
+        Ok(results[0].as_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_password_is_generated_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = TestContext(dir.path().to_path_buf());
+
+        let password = lookup("credentials/db length=15 chars=digits", &context).unwrap();
+        assert_eq!(password.len(), 15);
+        assert!(password.chars().all(|c| c.is_ascii_digit()));
+
+        let stored = fs::read_to_string(dir.path().join("credentials/db")).unwrap();
+        assert_eq!(stored, format!("{}\n", password));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.path().join("credentials/db"))
+                .unwrap()
+                .permissions();
+            assert_eq!(mode.mode() & 0o777, 0o600);
+        }
+
+        // the stored password is returned from now on
+        assert_eq!(
+            lookup("credentials/db length=40", &context).unwrap(),
+            password
+        );
+    }
+
+    #[test]
+    fn test_password_encrypt_stores_salt() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = TestContext(dir.path().to_path_buf());
+        fs::write(dir.path().join("admin"), "secret\n").unwrap();
+
+        let hash = lookup("admin encrypt=sha512_crypt", &context).unwrap();
+        assert!(hash.starts_with("$6$"));
+
+        let stored = fs::read_to_string(dir.path().join("admin")).unwrap();
+        let (password, salt) = parse_content(stored.trim_end());
+        assert_eq!(password, "secret");
+        assert_eq!(
+            hash,
+            sha_crypt("secret", &salt.unwrap(), None, true).unwrap()
+        );
+
+        // same salt, same hash
+        assert_eq!(
+            lookup("admin encrypt=sha512_crypt", &context).unwrap(),
+            hash
+        );
+        assert_eq!(lookup("admin", &context).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_password_params() {
+        let dir = tempfile::tempdir().unwrap();
+        let context = TestContext(dir.path().to_path_buf());
+
+        let seeded = lookup("/dev/null seed=bootstrap chars=ab,,", &context).unwrap();
+        assert_eq!(
+            seeded,
+            lookup("/dev/null seed=bootstrap chars=ab,,", &context).unwrap()
+        );
+        assert!(seeded.chars().all(|c| "ab,".contains(c)));
+
+        assert!(lookup("/dev/null size=10", &context).is_err());
+        assert!(lookup("/dev/null encrypt=md5_crypt", &context).is_err());
+
+        // invalid parameters fail before the password file is created
+        assert!(lookup("admin encrypt=md5_crypt", &context).is_err());
+        assert!(!dir.path().join("admin").exists());
+    }
+}
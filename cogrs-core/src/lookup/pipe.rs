@@ -0,0 +1,40 @@
+use super::term_str;
+use anyhow::{bail, Context, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+use std::process::Command;
+
+/// `lookup('pipe', 'date')`, runs commands on the controller and returns their output.
+pub(super) struct PipeLookup;
+
+impl LookupPlugin for PipeLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        _options: &Map<String, Value>,
+        _context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        terms
+            .iter()
+            .map(|term| {
+                let command = term_str(term)?;
+                let output = Command::new("/bin/sh")
+                    .arg("-c")
+                    .arg(command)
+                    .output()
+                    .with_context(|| format!("Failed to run {}", command))?;
+
+                if !output.status.success() {
+                    bail!(
+                        "lookup_plugin.pipe({}) returned {}",
+                        command,
+                        output.status.code().unwrap_or(-1)
+                    );
+                }
+
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                Ok(Value::String(stdout.trim_end().to_string()))
+            })
+            .collect()
+    }
+}
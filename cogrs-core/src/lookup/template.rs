@@ -0,0 +1,41 @@
+use super::term_str;
+use anyhow::{bail, format_err, Context, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+use std::fs;
+
+/// `lookup('template', 'motd.j2', template_vars={})`, renders templates found in the
+/// `templates` directories with the variables of the calling context.
+pub(super) struct TemplateLookup;
+
+impl LookupPlugin for TemplateLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        let template_vars = match options.get("template_vars") {
+            None | Some(Value::Null) => None,
+            Some(Value::Object(vars)) => Some(vars),
+            Some(_) => bail!("'template_vars' must be a dictionary"),
+        };
+
+        terms
+            .iter()
+            .map(|term| {
+                let name = term_str(term)?;
+                let path = context.find_file("templates", name).ok_or_else(|| {
+                    format_err!(
+                        "the template file {} could not be found for the lookup",
+                        name
+                    )
+                })?;
+                let source = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+
+                Ok(Value::String(context.template(&source, template_vars)?))
+            })
+            .collect()
+    }
+}
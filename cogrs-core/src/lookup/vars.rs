@@ -0,0 +1,27 @@
+use super::term_str;
+use anyhow::{format_err, Result};
+use cogrs_plugins::lookup::{LookupContext, LookupPlugin};
+use serde_json::{Map, Value};
+
+/// `lookup('vars', 'name', default=None)`, returns variables by (templated) name.
+pub(super) struct VarsLookup;
+
+impl LookupPlugin for VarsLookup {
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>> {
+        terms
+            .iter()
+            .map(|term| {
+                let name = term_str(term)?;
+                context
+                    .get_var(name)
+                    .or_else(|| options.get("default").cloned())
+                    .ok_or_else(|| format_err!("No variable found with this name: {}", name))
+            })
+            .collect()
+    }
+}
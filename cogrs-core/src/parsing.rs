@@ -1,2 +1,6 @@
+pub mod host_list;
+pub mod ini;
+pub mod loader;
 pub mod parser;
-pub mod yml;
+pub mod script;
+pub mod splitter;
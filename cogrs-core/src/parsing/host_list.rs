@@ -0,0 +1,129 @@
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::vars::variable::{Number, Variable};
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use log::debug;
+use std::path::Path;
+
+/// Returns true if the source is an inline host list such as `web1,web2:2222,`
+/// rather than a path, an existing path always wins even if it contains a comma.
+pub fn is_host_list(source: &str) -> bool {
+    source.contains(',') && !Path::new(source).exists()
+}
+
+/// Adds every host of a comma-separated list to the `ungrouped` group.
+pub fn parse_host_list(
+    source: &str,
+    groups: &mut IndexMap<String, Group>,
+    hosts: &mut IndexMap<String, Host>,
+) -> Result<()> {
+    debug!("Parsing host list: {source}");
+
+    let group = groups
+        .entry("ungrouped".to_string())
+        .or_insert_with(|| Group::new("ungrouped"));
+
+    for entry in source.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (host_name, port) = parse_host_port(entry)?;
+
+        let host = hosts
+            .entry(host_name.to_string())
+            .or_insert_with(|| Host::new(&host_name));
+        group.add_host(host.name());
+        host.add_group(group.name());
+
+        if let Some(port) = port {
+            host.set_var("ansible_port", &Variable::Number(Number::Int(port as i64)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits `host`, `host:port`, `[ipv6]`, `[ipv6]:port` or a bare IPv6 address.
+fn parse_host_port(entry: &str) -> Result<(String, Option<u16>)> {
+    if let Some(rest) = entry.strip_prefix('[') {
+        let Some((address, rest)) = rest.split_once(']') else {
+            bail!("Invalid host \"{entry}\" in host list, missing closing \"]\"");
+        };
+        return match rest {
+            "" => Ok((address.to_string(), None)),
+            _ => match rest.strip_prefix(':') {
+                Some(port) => Ok((address.to_string(), Some(parse_port(entry, port)?))),
+                None => bail!("Invalid host \"{entry}\" in host list, expected [address]:port"),
+            },
+        };
+    }
+
+    match entry.matches(':').count() {
+        0 => Ok((entry.to_string(), None)),
+        1 => {
+            let (host_name, port) = entry.split_once(':').unwrap_or((entry, ""));
+            if host_name.is_empty() {
+                bail!("Invalid host \"{entry}\" in host list, missing host name");
+            }
+            Ok((host_name.to_string(), Some(parse_port(entry, port)?)))
+        }
+        // bare IPv6 address, a port needs the [address]:port form
+        _ => Ok((entry.to_string(), None)),
+    }
+}
+
+fn parse_port(entry: &str, port: &str) -> Result<u16> {
+    match port.parse::<u16>() {
+        Ok(port) => Ok(port),
+        Err(_) => bail!("Invalid port \"{port}\" for host \"{entry}\" in host list"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_host_list() {
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        parse_host_list(
+            "web1, web2:2222,10.0.0.5,[fe80::1]:2200,::1,",
+            &mut groups,
+            &mut hosts,
+        )
+        .unwrap();
+
+        assert_eq!(
+            groups["ungrouped"].get_hosts(&groups, false).unwrap(),
+            vec!["web1", "web2", "10.0.0.5", "fe80::1", "::1"]
+        );
+        assert!(!hosts["web1"].vars().contains_key("ansible_port"));
+        assert_eq!(
+            hosts["web2"].vars()["ansible_port"],
+            Variable::Number(Number::Int(2222))
+        );
+        assert_eq!(
+            hosts["fe80::1"].vars()["ansible_port"],
+            Variable::Number(Number::Int(2200))
+        );
+        assert!(hosts["::1"].groups().contains(&"ungrouped".to_string()));
+    }
+
+    #[test]
+    fn test_parse_host_list_invalid_entries() {
+        for source in ["web1:ssh,", "[fe80::1,", "[fe80::1]2200,", ":22,"] {
+            let mut groups = IndexMap::new();
+            let mut hosts = IndexMap::new();
+            assert!(
+                parse_host_list(source, &mut groups, &mut hosts).is_err(),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_host_list() {
+        assert!(is_host_list("web1,"));
+        assert!(!is_host_list("web1"));
+        assert!(!is_host_list(env!("CARGO_MANIFEST_DIR")));
+    }
+}
@@ -0,0 +1,422 @@
+use crate::inventory::builder::set_inventory_source_vars;
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::inventory::utils::parse_host_pattern;
+use crate::parsing::parser::read_inventory_file;
+use crate::vars::variable::{Number, Variable};
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+use log::{debug, warn};
+use regex::Regex;
+use std::path::Path;
+
+/// Kind of an INI section, `[name]`, `[name:vars]` or `[name:children]`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum SectionKind {
+    Hosts,
+    Vars,
+    Children,
+}
+
+/// Everything defined for a group in an INI file, collected before anything is linked
+/// so child groups can be referenced before their own section appears.
+#[derive(Default)]
+struct IniGroup {
+    hosts: Vec<(String, IndexMap<String, Variable>)>,
+    vars: IndexMap<String, Variable>,
+    children: Vec<String>,
+}
+
+pub fn parse_ini_file(
+    file_path: &Path,
+    groups: &mut IndexMap<String, Group>,
+    hosts: &mut IndexMap<String, Host>,
+) -> Result<()> {
+    let content = read_inventory_file(file_path)?;
+
+    let sections = parse_sections(&content)
+        .map_err(|e| anyhow!("Unable to parse {}: {:#}", file_path.display(), e))?;
+
+    for (group_name, section) in &sections {
+        debug!("Parsing {group_name} group");
+        let group = groups
+            .entry(group_name.to_string())
+            .or_insert_with(|| Group::new(group_name));
+
+        for (key, value) in &section.vars {
+            group.set_variable(key, value.clone());
+        }
+
+        for (host_name, vars) in &section.hosts {
+            let host = hosts
+                .entry(host_name.to_string())
+                .or_insert_with(|| Host::new(host_name));
+            group.add_host(host.name());
+            host.add_group(group.name());
+            set_inventory_source_vars(host, file_path)?;
+            for (key, value) in vars {
+                host.set_var(key, value);
+            }
+        }
+    }
+
+    for (group_name, section) in &sections {
+        for child_group_name in &section.children {
+            let mut parent_group = groups
+                .get(group_name)
+                .ok_or(anyhow::format_err!(
+                    "Parent group {group_name} does not exist in the provided groups collection"
+                ))?
+                .clone();
+
+            let mut child_group = groups
+                .get(child_group_name)
+                .ok_or(anyhow::format_err!("Child group {child_group_name} does not exist in the provided groups collection"))?
+                .clone();
+
+            parent_group.add_child_group(&mut child_group, groups, hosts)?;
+
+            groups.insert(parent_group.name().to_string(), parent_group);
+            groups.insert(child_group.name().to_string(), child_group);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the content looks like an INI inventory rather than a YAML one,
+/// used for inventory files without an extension.
+pub fn is_ini_content(content: &str) -> bool {
+    let yaml_key = Regex::new(r"^[^\s=\[#;]+:(\s|$)").expect("valid regex");
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line == "---" || line.starts_with("$ANSIBLE_VAULT") {
+            return false;
+        }
+        return !yaml_key.is_match(line);
+    }
+
+    false
+}
+
+/// Splits the file into groups, hosts before the first section are `ungrouped`.
+fn parse_sections(content: &str) -> Result<IndexMap<String, IniGroup>> {
+    let section_re = Regex::new(r"^\[([^\]]+?)(?::(\w+))?]$")?;
+
+    let mut sections: IndexMap<String, IniGroup> = IndexMap::new();
+    let mut group_name = "ungrouped".to_string();
+    let mut kind = SectionKind::Hosts;
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        let in_line = |e: anyhow::Error| anyhow!("line {}: {:#}", number + 1, e);
+
+        if let Some(captures) = section_re.captures(line) {
+            group_name = captures[1].trim().to_string();
+            kind = match captures.get(2).map(|m| m.as_str()) {
+                None => SectionKind::Hosts,
+                Some("vars") => SectionKind::Vars,
+                Some("children") => SectionKind::Children,
+                Some(other) => {
+                    return Err(in_line(anyhow!(
+                        "Section suffix \"{other}\" is not one of \"vars\" or \"children\""
+                    )))
+                }
+            };
+            sections.entry(group_name.clone()).or_default();
+            continue;
+        }
+
+        let section = sections.entry(group_name.clone()).or_default();
+        match kind {
+            SectionKind::Hosts => {
+                let (pattern, vars) = parse_host_line(line).map_err(in_line)?;
+                for host_name in parse_host_pattern(&pattern).map_err(in_line)? {
+                    section.hosts.push((host_name, vars.clone()));
+                }
+            }
+            SectionKind::Vars => {
+                let (key, value) = line.split_once('=').ok_or_else(|| {
+                    in_line(anyhow!(
+                        "Expected key=value in [{group_name}:vars], got: {line}"
+                    ))
+                })?;
+                // like Ansible, values of `:vars` sections are strings, only host line
+                // values are parsed as literals
+                section.vars.insert(
+                    key.trim().to_string(),
+                    Variable::String(value.trim().to_string()),
+                );
+            }
+            SectionKind::Children => {
+                let tokens = split_tokens(line).map_err(in_line)?;
+                if tokens.len() != 1 {
+                    return Err(in_line(anyhow!(
+                        "Expected a single group name in [{group_name}:children], got: {line}"
+                    )));
+                }
+                section.children.push(tokens[0].clone());
+            }
+        }
+    }
+
+    // children may be listed without a section of their own
+    let children: Vec<String> = sections
+        .values()
+        .flat_map(|section| section.children.clone())
+        .collect();
+    for child in children {
+        sections.entry(child).or_default();
+    }
+
+    Ok(sections)
+}
+
+/// Parses `host[01:10]:2222 key=value ...` into the host pattern and its variables.
+fn parse_host_line(line: &str) -> Result<(String, IndexMap<String, Variable>)> {
+    let mut tokens = split_tokens(line)?.into_iter();
+    let Some(pattern) = tokens.next() else {
+        bail!("Missing host name");
+    };
+
+    let mut vars = IndexMap::new();
+    let (pattern, port) = split_port(&pattern)?;
+    if let Some(port) = port {
+        vars.insert(
+            "ansible_port".to_string(),
+            Variable::Number(Number::Int(port)),
+        );
+    }
+
+    for token in tokens {
+        let Some((key, value)) = token.split_once('=') else {
+            bail!("Expected key=value host variable, got: {token}");
+        };
+        if key.is_empty() {
+            bail!("Empty variable name in: {token}");
+        }
+        vars.insert(key.to_string(), parse_value(value));
+    }
+
+    Ok((pattern, vars))
+}
+
+/// Splits a trailing `:port` off a host pattern, leaving bare IPv6 addresses alone.
+fn split_port(pattern: &str) -> Result<(String, Option<i64>)> {
+    if let Some((name, port)) = pattern.rsplit_once(':') {
+        let ranges = Regex::new(r"\[[^\]]*]")?;
+        if !port.is_empty()
+            && port.chars().all(|c| c.is_ascii_digit())
+            && !ranges.replace_all(name, "").contains(':')
+        {
+            return Ok((name.to_string(), Some(port.parse()?)));
+        }
+    }
+
+    Ok((pattern.to_string(), None))
+}
+
+/// Splits a line on whitespace, keeping quoted parts together and stopping at a comment.
+/// Quotes are kept so `parse_value` can tell quoted strings from literals.
+fn split_tokens(line: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                if c == '\\' {
+                    if let Some(&next) = chars.peek() {
+                        if next == q || next == '\\' {
+                            current.push(c);
+                            current.push(next);
+                            chars.next();
+                            continue;
+                        }
+                    }
+                }
+                if c == q {
+                    quote = None;
+                }
+                current.push(c);
+            }
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    current.push(c);
+                }
+                '#' if current.is_empty() => break,
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                }
+                _ => current.push(c),
+            },
+        }
+    }
+
+    if quote.is_some() {
+        bail!("Unterminated quote in: {line}");
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Coerces an INI value: quoted values stay strings, everything else is read as a
+/// boolean, number, list or dictionary literal when it is one, and as a string otherwise.
+fn parse_value(value: &str) -> Variable {
+    for q in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(q) && value.ends_with(q) {
+            let inner = &value[1..value.len() - 1];
+            return Variable::String(
+                inner
+                    .replace(&format!("\\{q}"), &q.to_string())
+                    .replace("\\\\", "\\"),
+            );
+        }
+    }
+
+    match value {
+        "true" | "True" => return Variable::Bool(true),
+        "false" | "False" => return Variable::Bool(false),
+        _ => {}
+    }
+
+    if let Ok(int) = value.parse::<i64>() {
+        return Variable::Number(Number::Int(int));
+    }
+    if let Ok(float) = value.parse::<f64>() {
+        if float.is_finite() {
+            return Variable::Number(Number::Float(float));
+        }
+    }
+
+    if value.starts_with('[') || value.starts_with('{') {
+        match serde_yaml::from_str::<serde_yaml::Value>(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|v| Variable::try_from(&v))
+        {
+            Ok(variable) => return variable,
+            Err(e) => warn!("Keeping \"{value}\" as a string, it is not a valid literal: {e}"),
+        }
+    }
+
+    Variable::String(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn get_inventory(name: &str) -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("tests/inventory");
+        path.push(name);
+        path
+    }
+
+    #[test]
+    fn test_parse_ini_file() {
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        parse_ini_file(&get_inventory("basic.ini"), &mut groups, &mut hosts).unwrap();
+
+        assert!(groups["ungrouped"]
+            .get_hosts(&groups, false)
+            .unwrap()
+            .contains(&"bastion.example.com".to_string()));
+        assert_eq!(
+            hosts["bastion.example.com"].vars()["ansible_port"],
+            Variable::Number(Number::Int(2222))
+        );
+
+        for name in ["web01", "web02", "web03"] {
+            assert!(hosts[name].groups().contains(&"webservers".to_string()));
+        }
+        assert!(!hosts.contains_key("web04"));
+
+        let db = hosts["db-a"].vars();
+        assert_eq!(db["ansible_user"], Variable::String("postgres".to_string()));
+        assert_eq!(db["primary"], Variable::Bool(true));
+        assert_eq!(db["max_connections"], Variable::Number(Number::Int(100)));
+        assert_eq!(db["ratio"], Variable::Number(Number::Float(0.5)));
+        assert_eq!(db["version"], Variable::String("15".to_string()));
+        assert_eq!(db["motd"], Variable::String("hello world".to_string()));
+        assert_eq!(
+            db["replicas"],
+            Variable::Sequence(vec![
+                Variable::String("db-b".to_string()),
+                Variable::String("db-c".to_string())
+            ])
+        );
+        assert!(db.contains_key("inventory_file"));
+
+        let vars = groups["webservers"].get_vars();
+        assert_eq!(vars["http_port"], Variable::String("80".to_string()));
+        assert_eq!(
+            vars["proxy"],
+            Variable::String("proxy.example.com".to_string())
+        );
+
+        let production = &groups["production"];
+        assert!(production.has_child_group("webservers"));
+        assert!(production.has_child_group("databases"));
+        assert!(groups["webservers"]
+            .get_ancestors(&groups, false)
+            .contains(&"production".to_string()));
+    }
+
+    #[test]
+    fn test_first_source_sets_inventory_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first.ini");
+        let second = dir.path().join("second.ini");
+        std::fs::write(&first, "[web]\nweb1\n").unwrap();
+        std::fs::write(&second, "[db]\nweb1\n").unwrap();
+
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        parse_ini_file(&first, &mut groups, &mut hosts).unwrap();
+        parse_ini_file(&second, &mut groups, &mut hosts).unwrap();
+
+        assert_eq!(
+            hosts["web1"].vars()["inventory_file"],
+            Variable::String(first.to_str().unwrap().to_string())
+        );
+        assert_eq!(
+            hosts["web1"].groups(),
+            &vec!["web".to_string(), "db".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_sections_errors() {
+        assert!(parse_sections("[web:bogus]\nweb1\n").is_err());
+        assert!(parse_sections("[web:vars]\nno_value\n").is_err());
+        assert!(parse_sections("[web]\nweb1 novalue\n").is_err());
+        assert!(parse_sections("[web]\nweb1 motd=\"open\n").is_err());
+    }
+
+    #[test]
+    fn test_is_ini_content() {
+        assert!(is_ini_content("# comment\n[web]\nweb1\n"));
+        assert!(is_ini_content("web1:2222 ansible_user=root\n"));
+        assert!(!is_ini_content("all:\n  hosts:\n    web1:\n"));
+        assert!(!is_ini_content("---\nall: {}\n"));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::vault::secrets::{vault_secrets, VaultSecrets};
+use crate::vault::Vault;
+use anyhow::Result;
+use log::debug;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Reads YAML/JSON data files, transparently decrypting vault encrypted files.
+/// Parsed files are cached, so vars files referenced by many hosts are only read once.
+#[derive(Default)]
+pub struct DataLoader {
+    vault: Vault,
+    vault_secrets: VaultSecrets,
+    file_cache: Mutex<HashMap<PathBuf, Value>>,
+}
+
+impl DataLoader {
+    pub fn new() -> Self {
+        DataLoader {
+            vault: Vault::new(),
+            vault_secrets: VaultSecrets::new(),
+            file_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the secrets of this loader, without any it uses those given on the command line.
+    pub fn set_vault_secrets(&mut self, secrets: VaultSecrets) {
+        self.vault_secrets = secrets;
+    }
+
+    /// Loads and parses a YAML or JSON file, decrypting it first if it is vault encrypted.
+    pub fn load_from_file(&self, path: &Path) -> Result<Value> {
+        if let Some(value) = self
+            .file_cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(path).cloned())
+        {
+            return Ok(value);
+        }
+
+        debug!("Loading data file: {}", path.display());
+
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::format_err!("Unable to read {}: {}", path.display(), e))?;
+        let content = self.decrypt_if_needed(&content, path)?;
+
+        let value: Value = serde_yaml::from_str(&content)
+            .map_err(|e| anyhow::format_err!("Unable to parse {}: {}", path.display(), e))?;
+
+        if let Ok(mut cache) = self.file_cache.lock() {
+            cache.insert(path.to_path_buf(), value.clone());
+        }
+
+        Ok(value)
+    }
+
+    fn decrypt_if_needed(&self, content: &str, path: &Path) -> Result<String> {
+        if !self.vault.is_encrypted(content) {
+            return Ok(content.to_string());
+        }
+
+        let secrets = match self.vault_secrets.is_empty() {
+            true => vault_secrets(),
+            false => Arc::new(self.vault_secrets.clone()),
+        };
+
+        secrets
+            .decrypt(content)
+            .map_err(|e| anyhow::format_err!("Unable to decrypt {}: {:#}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::aes256::AES256;
+    use crate::vault::secrets::VaultSecret;
+    use tempfile::tempdir;
+
+    fn write_file(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_plain_yaml() {
+        let dir = tempdir().unwrap();
+        let path = write_file(dir.path(), "vars.yml", "port: 8080\n");
+
+        let loader = DataLoader::new();
+        let value = loader.load_from_file(&path).unwrap();
+
+        assert_eq!(value["port"], Value::Number(8080.into()));
+    }
+
+    #[test]
+    fn test_load_vault_encrypted_yaml() {
+        let dir = tempdir().unwrap();
+        let encrypted = AES256::encrypt_aes256("password: secret\n", "vault_pass").unwrap();
+        let path = write_file(
+            dir.path(),
+            "vars.yml",
+            &format!("$ANSIBLE_VAULT;1.1;AES256\n{}", encrypted),
+        );
+
+        let mut loader = DataLoader::new();
+        assert!(loader.load_from_file(&path).is_err());
+
+        let mut secrets = VaultSecrets::new();
+        secrets.add(VaultSecret::new("default", "vault_pass"));
+        loader.set_vault_secrets(secrets);
+        let value = loader.load_from_file(&path).unwrap();
+
+        assert_eq!(value["password"], Value::String("secret".to_string()));
+    }
+}
@@ -1,12 +1,24 @@
+use crate::inventory::builder::InventoryBuilder;
+use crate::inventory::cache::InventoryCache;
 use crate::inventory::group::Group;
 use crate::inventory::host::Host;
-use crate::parsing::yml::parse_yaml_file;
+use crate::inventory::plugins::get_inventory_plugin;
+use crate::inventory::plugins::ssh_config::{is_ssh_config, parse_ssh_config};
+use crate::parsing::host_list::{is_host_list, parse_host_list};
+use crate::parsing::ini::{is_ini_content, parse_ini_file};
+use crate::parsing::script::{is_inventory_script, parse_inventory_script};
+use crate::vault::secrets::vault_secrets;
+use crate::vault::Vault;
 use indexmap::IndexMap;
-use log::debug;
+use log::{debug, warn};
 use regex::Regex;
 use std::fs;
 use std::path::Path;
 
+/// Inventory plugins whose sources are never cached: `yaml` and `ssh_config` inventories are
+/// static and `constructed` works on the hosts of the other sources.
+const UNCACHED_INVENTORY_PLUGINS: [&str; 3] = ["yaml", "ssh_config", "constructed"];
+
 pub struct InventoryParser;
 
 impl InventoryParser {
@@ -14,19 +26,42 @@ impl InventoryParser {
         source: &str,
         groups: &mut IndexMap<String, Group>,
         hosts: &mut IndexMap<String, Host>,
+    ) -> anyhow::Result<()> {
+        InventoryParser::parse_source_with_cache(source, groups, hosts, None)
+    }
+
+    /// Parses a source, restoring dynamic sources (scripts and inventory plugins) from the
+    /// cache when one is given.
+    pub fn parse_source_with_cache(
+        source: &str,
+        groups: &mut IndexMap<String, Group>,
+        hosts: &mut IndexMap<String, Host>,
+        cache: Option<&InventoryCache>,
     ) -> anyhow::Result<()> {
         debug!("Examining source {}", source);
         let path = Path::new(source);
 
+        if is_host_list(source) {
+            return parse_host_list(source, groups, hosts);
+        }
+
         if !path.exists() {
-            // TODO: this is not a path, could be a host list separated by commas
+            match source.contains('/') || path.extension().is_some() {
+                true => warn!(
+                    "Unable to parse {source} as an inventory source, the path does not exist"
+                ),
+                false => warn!(
+                    "Unable to parse {source} as an inventory source, the path does not exist. \
+                     To use it as a single host add a trailing comma: '{source},'"
+                ),
+            }
             return Ok(());
         }
 
         if path.is_dir() {
-            InventoryParser::parse_directory(path, groups, hosts)?;
+            InventoryParser::parse_directory(path, groups, hosts, cache)?;
         } else {
-            InventoryParser::parse_file(path, groups, hosts)?;
+            InventoryParser::parse_file(path, groups, hosts, cache)?;
         }
 
         Ok(())
@@ -36,14 +71,28 @@ impl InventoryParser {
         dir_path: &Path,
         groups: &mut IndexMap<String, Group>,
         hosts: &mut IndexMap<String, Host>,
+        cache: Option<&InventoryCache>,
     ) -> anyhow::Result<()> {
         debug!(
             "Loading inventory files in directory: {}",
             dir_path.display()
         );
+
+        for entry in InventoryParser::directory_sources(dir_path)? {
+            InventoryParser::parse_source_with_cache(&entry, groups, hosts, cache)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the entries of an inventory directory that are parsed as sources, sorted by
+    /// name. Hidden files and the `host_vars`, `group_vars` and `vars_plugins` directories
+    /// are skipped.
+    pub(crate) fn directory_sources(dir_path: &Path) -> anyhow::Result<Vec<String>> {
         let paths = fs::read_dir(dir_path)?;
 
         let exclude_pattern = Regex::new(r"^(?:\.|host_vars|group_vars|vars_plugins)(/|$)")?;
+        let mut sources = Vec::new();
 
         for entry in paths.flatten() {
             let entry_path = entry.path();
@@ -57,32 +106,112 @@ impl InventoryParser {
                     continue;
                 }
 
-                InventoryParser::parse_source(entry_str, groups, hosts)?;
+                sources.push(entry_str.to_string());
             }
         }
+        // parsed in a stable order, the first source a host is found in is its inventory_file
+        sources.sort();
 
-        Ok(())
+        Ok(sources)
     }
 
     fn parse_file(
         file_path: &Path,
         groups: &mut IndexMap<String, Group>,
         hosts: &mut IndexMap<String, Host>,
+        cache: Option<&InventoryCache>,
     ) -> anyhow::Result<()> {
         debug!("Parsing inventory file: {}", file_path.display());
 
-        if let Some(extension) = file_path.extension() {
-            match extension.to_str() {
-                Some("yml" | "yaml") => parse_yaml_file(file_path, groups, hosts)?,
-                _ => {
-                    debug!(
-                        "Skipping file due to incompatible extension: {}",
-                        file_path.display()
-                    );
+        if is_inventory_script(file_path) {
+            return match cache {
+                Some(cache) => cache.parse(file_path, "--list", groups, hosts, |groups, hosts| {
+                    parse_inventory_script(file_path, groups, hosts)
+                }),
+                None => parse_inventory_script(file_path, groups, hosts),
+            };
+        }
+
+        if is_ssh_config(file_path) {
+            return parse_ssh_config(
+                file_path,
+                &mut InventoryBuilder::new(file_path, groups, hosts),
+            );
+        }
+
+        match file_path.extension().map(|extension| extension.to_str()) {
+            Some(Some("yml" | "yaml")) => {
+                InventoryParser::parse_with_plugin(file_path, groups, hosts, cache)?
+            }
+            Some(Some("ini")) => parse_ini_file(file_path, groups, hosts)?,
+            Some(_) => {
+                debug!(
+                    "Skipping file due to incompatible extension: {}",
+                    file_path.display()
+                );
+            }
+            None => {
+                // extension-less inventories are detected by content
+                if is_ini_content(&read_inventory_file(file_path)?) {
+                    parse_ini_file(file_path, groups, hosts)?
+                } else {
+                    InventoryParser::parse_with_plugin(file_path, groups, hosts, cache)?
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Parses a YAML inventory with the inventory plugin named by its `plugin` key,
+    /// or with the `yaml` plugin when it has none.
+    fn parse_with_plugin(
+        file_path: &Path,
+        groups: &mut IndexMap<String, Group>,
+        hosts: &mut IndexMap<String, Host>,
+        cache: Option<&InventoryCache>,
+    ) -> anyhow::Result<()> {
+        let content = read_inventory_file(file_path)?;
+        let plugin_name = serde_yaml::from_str::<serde_yaml::Value>(&content)
+            .ok()
+            .and_then(|data| data.get("plugin")?.as_str().map(str::to_string))
+            .unwrap_or_else(|| "yaml".to_string());
+
+        let plugin = get_inventory_plugin(&plugin_name).ok_or_else(|| {
+            anyhow::format_err!(
+                "Unknown inventory plugin '{plugin_name}' in {}",
+                file_path.display()
+            )
+        })?;
+
+        debug!(
+            "Parsing {} with the {plugin_name} inventory plugin",
+            file_path.display()
+        );
+        let source = file_path
+            .to_str()
+            .ok_or_else(|| anyhow::format_err!("Invalid inventory source path: {:?}", file_path))?;
+        let parse = |groups: &mut IndexMap<String, Group>, hosts: &mut IndexMap<String, Host>| {
+            plugin.parse(source, &mut InventoryBuilder::new(file_path, groups, hosts))
+        };
+
+        match cache {
+            // the plugin options are part of the key, changing them parses the source again
+            Some(cache) if !UNCACHED_INVENTORY_PLUGINS.contains(&plugin_name.as_str()) => {
+                cache.parse(file_path, &content, groups, hosts, parse)
+            }
+            _ => parse(groups, hosts),
+        }
+    }
+}
+
+/// Reads an inventory file, decrypting it if it is vault encrypted.
+pub(crate) fn read_inventory_file(file_path: &Path) -> anyhow::Result<String> {
+    let content = fs::read_to_string(file_path)?;
+    match Vault::new().is_encrypted(&content) {
+        true => vault_secrets()
+            .decrypt(&content)
+            .map_err(|e| anyhow::format_err!("Unable to decrypt {}: {:#}", file_path.display(), e)),
+        false => Ok(content),
+    }
 }
@@ -0,0 +1,398 @@
+use crate::config::manager::ConfigManager;
+use crate::inventory::builder::set_inventory_source_vars;
+use crate::inventory::group::Group;
+use crate::inventory::host::Host;
+use crate::vars::variable::Variable;
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+use log::{debug, warn};
+use nix::sys::signal::{killpg, Signal};
+use nix::unistd::Pid;
+use serde_json::Value;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Used when `INVENTORY_SCRIPT_TIMEOUT` can't be read from the configuration.
+const DEFAULT_SCRIPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Returns true for executable files that can be run as dynamic inventory scripts,
+/// interpreted scripts need a shebang so executable YAML or INI files are not run.
+pub fn is_inventory_script(path: &Path) -> bool {
+    let executable = path
+        .metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false);
+    if !executable {
+        return false;
+    }
+
+    let mut magic = [0u8; 4];
+    match std::fs::File::open(path).and_then(|mut file| file.read_exact(&mut magic)) {
+        Ok(_) => magic.starts_with(b"#!") || magic == *b"\x7fELF",
+        Err(_) => false,
+    }
+}
+
+/// Runs an executable inventory with `--list` and adds the groups and hosts it prints.
+/// Host variables come from `_meta.hostvars`, or a `--host <name>` call per host when
+/// the script doesn't return `_meta`.
+pub fn parse_inventory_script(
+    file_path: &Path,
+    groups: &mut IndexMap<String, Group>,
+    hosts: &mut IndexMap<String, Host>,
+) -> Result<()> {
+    parse_script_with_timeout(file_path, script_timeout(), groups, hosts)
+}
+
+fn script_timeout() -> Duration {
+    ConfigManager::instance()
+        .try_lock()
+        .ok()
+        .and_then(|config| {
+            config
+                .get_config_value::<u64>("INVENTORY_SCRIPT_TIMEOUT")
+                .ok()
+                .flatten()
+        })
+        .map(|(timeout, _)| Duration::from_secs(timeout))
+        .unwrap_or(DEFAULT_SCRIPT_TIMEOUT)
+}
+
+fn parse_script_with_timeout(
+    file_path: &Path,
+    timeout: Duration,
+    groups: &mut IndexMap<String, Group>,
+    hosts: &mut IndexMap<String, Host>,
+) -> Result<()> {
+    let data = run_script(file_path, &["--list"], timeout)?;
+    let Value::Object(data) = data else {
+        bail!(
+            "Inventory script {} should print a JSON object for --list",
+            file_path.display()
+        );
+    };
+
+    let hostvars = data.get("_meta").map(|meta| {
+        meta.get("hostvars")
+            .and_then(Value::as_object)
+            .cloned()
+            .unwrap_or_default()
+    });
+
+    for (group_name, group_data) in data.iter().filter(|(key, _)| *key != "_meta") {
+        debug!("Parsing {group_name} group");
+        parse_group(group_name, group_data, groups, hosts, file_path)?;
+    }
+
+    let host_names: Vec<String> = hosts.keys().cloned().collect();
+    for host_name in host_names {
+        let vars = match &hostvars {
+            Some(hostvars) => match hostvars.get(&host_name) {
+                Some(vars) => vars.clone(),
+                None => continue,
+            },
+            None => {
+                if !script_defines_host(&data, &host_name) {
+                    continue;
+                }
+                run_script(file_path, &["--host", &host_name], timeout)?
+            }
+        };
+
+        if let Some(host) = hosts.get_mut(&host_name) {
+            set_host_vars(host, &vars, file_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A group is either a list of hosts or a dictionary of `hosts`, `vars` and `children`.
+fn parse_group(
+    group_name: &str,
+    data: &Value,
+    groups: &mut IndexMap<String, Group>,
+    hosts: &mut IndexMap<String, Host>,
+    source: &Path,
+) -> Result<()> {
+    let (host_names, vars, children) = match data {
+        Value::Array(host_names) => (Some(host_names), None, None),
+        Value::Object(data) => (
+            data.get("hosts").and_then(Value::as_array),
+            data.get("vars"),
+            data.get("children").and_then(Value::as_array),
+        ),
+        _ => {
+            warn!("Skipping group \"{group_name}\", it should be a list or a dictionary");
+            return Ok(());
+        }
+    };
+
+    let group = groups
+        .entry(group_name.to_string())
+        .or_insert_with(|| Group::new(group_name));
+
+    if let Some(vars) = vars {
+        match Variable::try_from(&serde_yaml::to_value(vars)?)? {
+            Variable::Mapping(vars) => {
+                for (key, value) in vars.iter() {
+                    group.set_variable(key, value.clone());
+                }
+            }
+            _ => bail!("Inventory script group \"{group_name}\" vars should be a dictionary"),
+        }
+    }
+
+    for host_name in host_names.into_iter().flatten().filter_map(Value::as_str) {
+        let host = hosts
+            .entry(host_name.to_string())
+            .or_insert_with(|| Host::new(host_name));
+        group.add_host(host.name());
+        host.add_group(group.name());
+        set_inventory_source_vars(host, source)?;
+    }
+
+    for child_group_name in children.into_iter().flatten().filter_map(Value::as_str) {
+        let mut parent_group = groups
+            .get(group_name)
+            .ok_or(anyhow::format_err!(
+                "Parent group {group_name} does not exist in the provided groups collection"
+            ))?
+            .clone();
+
+        let mut child_group = groups
+            .get(child_group_name)
+            .cloned()
+            .unwrap_or_else(|| Group::new(child_group_name));
+
+        parent_group.add_child_group(&mut child_group, groups, hosts)?;
+
+        groups.insert(parent_group.name().to_string(), parent_group);
+        groups.insert(child_group.name().to_string(), child_group);
+    }
+
+    Ok(())
+}
+
+/// True if the host is listed by this script, other sources may have added hosts before it.
+fn script_defines_host(data: &serde_json::Map<String, Value>, host_name: &str) -> bool {
+    data.values().any(|group| {
+        let host_names = match group {
+            Value::Array(host_names) => Some(host_names),
+            Value::Object(group) => group.get("hosts").and_then(Value::as_array),
+            _ => None,
+        };
+        host_names
+            .into_iter()
+            .flatten()
+            .any(|name| name.as_str() == Some(host_name))
+    })
+}
+
+fn set_host_vars(host: &mut Host, vars: &Value, source: &Path) -> Result<()> {
+    set_inventory_source_vars(host, source)?;
+
+    match Variable::try_from(&serde_yaml::to_value(vars)?)? {
+        Variable::Mapping(vars) => {
+            for (key, value) in vars.iter() {
+                host.set_var(key, value);
+            }
+        }
+        Variable::Null => {}
+        _ => bail!(
+            "Inventory script host \"{}\" vars should be a dictionary",
+            host.name()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Runs the script and parses its stdout as JSON, killing it once the timeout expires.
+fn run_script(file_path: &Path, args: &[&str], timeout: Duration) -> Result<Value> {
+    let command = format!("{} {}", file_path.display(), args.join(" "));
+    debug!("Running inventory script: {command}");
+
+    let mut child = Command::new(file_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // own process group, so children the script started are killed on timeout too
+        .process_group(0)
+        .spawn()
+        .map_err(|e| anyhow!("Unable to run inventory script {command}: {e}"))?;
+
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL);
+            child.wait()?;
+            let stderr = stderr.join().unwrap_or_default();
+            bail!(
+                "Inventory script {command} timed out after {}s{}",
+                timeout.as_secs_f32(),
+                format_stderr(&stderr)
+            );
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        bail!(
+            "Inventory script {command} failed with {status}{}",
+            format_stderr(&stderr)
+        );
+    }
+
+    serde_json::from_slice(&stdout).map_err(|e| {
+        anyhow!(
+            "Inventory script {command} did not print valid JSON: {e}{}",
+            format_stderr(&stderr)
+        )
+    })
+}
+
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut output);
+        }
+        output
+    })
+}
+
+fn format_stderr(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    match stderr.trim() {
+        "" => String::new(),
+        stderr => format!(", stderr: {stderr}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::variable::Number;
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn write_script(dir: &Path, content: &str) -> PathBuf {
+        let path = dir.join("inventory.sh");
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_script_with_meta() {
+        let dir = tempdir().unwrap();
+        let path = write_script(
+            dir.path(),
+            r#"#!/bin/sh
+cat <<'EOF'
+{
+  "web": {"hosts": ["web1", "web2"], "vars": {"http_port": 80}},
+  "databases": ["db1"],
+  "production": {"children": ["web", "databases"]},
+  "_meta": {"hostvars": {"web1": {"rack": "a1"}}}
+}
+EOF
+"#,
+        );
+        assert!(is_inventory_script(&path));
+
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        parse_inventory_script(&path, &mut groups, &mut hosts).unwrap();
+
+        assert_eq!(
+            groups["web"].get_vars()["http_port"],
+            Variable::Number(Number::Int(80))
+        );
+        assert!(hosts["db1"].groups().contains(&"databases".to_string()));
+        assert!(groups["production"].has_child_group("web"));
+        assert!(groups["production"].has_child_group("databases"));
+        assert_eq!(
+            hosts["web1"].vars()["rack"],
+            Variable::String("a1".to_string())
+        );
+        assert!(!hosts["web2"].vars().contains_key("rack"));
+    }
+
+    #[test]
+    fn test_parse_script_without_meta_calls_host() {
+        let dir = tempdir().unwrap();
+        let path = write_script(
+            dir.path(),
+            r#"#!/bin/sh
+if [ "$1" = "--list" ]; then
+    echo '{"web": ["web1", "web2"]}'
+else
+    echo "{\"served_by\": \"$2\"}"
+fi
+"#,
+        );
+
+        let mut groups = IndexMap::new();
+        let mut hosts = IndexMap::new();
+        parse_inventory_script(&path, &mut groups, &mut hosts).unwrap();
+
+        for name in ["web1", "web2"] {
+            assert_eq!(
+                hosts[name].vars()["served_by"],
+                Variable::String(name.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_script_errors_include_stderr() {
+        let dir = tempdir().unwrap();
+        let path = write_script(
+            dir.path(),
+            "#!/bin/sh\necho 'cmdb unreachable' >&2\nexit 3\n",
+        );
+
+        let err = run_script(&path, &["--list"], DEFAULT_SCRIPT_TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("cmdb unreachable"), "{err}");
+    }
+
+    #[test]
+    fn test_script_timeout() {
+        let dir = tempdir().unwrap();
+        let path = write_script(
+            dir.path(),
+            "#!/bin/sh\necho 'querying' >&2\nexec sleep 10\n",
+        );
+
+        let started = Instant::now();
+        let err = run_script(&path, &["--list"], Duration::from_millis(200)).unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err.to_string().contains("timed out"), "{err}");
+        assert!(err.to_string().contains("querying"), "{err}");
+    }
+
+    #[test]
+    fn test_executable_without_shebang_is_not_a_script() {
+        let dir = tempdir().unwrap();
+        let path = write_script(dir.path(), "all:\n  hosts:\n    web1:\n");
+        assert!(!is_inventory_script(&path));
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+
+/// Splits a string of arguments on whitespace, keeping quoted sections together.
+/// Quotes are preserved in the returned tokens, see [`unquote`].
+pub fn split_args(args: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for c in args.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+
+        match c {
+            '\\' => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                current.push(c);
+            }
+            c if Some(c) == quote => {
+                quote = None;
+                current.push(c);
+            }
+            c if c.is_whitespace() && quote.is_none() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if let Some(quote) = quote {
+        bail!("Unbalanced quote ({}) in arguments: {}", quote, args);
+    }
+
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+/// Removes matching surrounding quotes, unescaping quotes inside the value.
+pub fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return value[1..value.len() - 1].replace(&format!("\\{}", quote), &quote.to_string());
+        }
+    }
+
+    value.to_string()
+}
+
+/// Parses `key=value` pairs separated by whitespace into a map, values are unquoted.
+pub fn parse_kv(args: &str) -> Result<IndexMap<String, String>> {
+    let mut options = IndexMap::new();
+
+    for token in split_args(args)? {
+        let Some((key, value)) = token.split_once('=') else {
+            bail!("'{}' is not in key=value format", token);
+        };
+
+        if key.is_empty() {
+            bail!("Missing key in '{}'", token);
+        }
+
+        options.insert(key.to_string(), unquote(value));
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args_keeps_quoted_sections() {
+        let tokens = split_args(r#"a=1 msg="hello world" b='x y'"#).unwrap();
+        assert_eq!(tokens, vec!["a=1", r#"msg="hello world""#, "b='x y'"]);
+    }
+
+    #[test]
+    fn test_split_args_unbalanced_quote() {
+        assert!(split_args(r#"msg="hello"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_kv() {
+        let options = parse_kv(r#"port=8080 msg="hello \"world\"" url=http://x?a=b"#).unwrap();
+
+        assert_eq!(options["port"], "8080");
+        assert_eq!(options["msg"], r#"hello "world""#);
+        assert_eq!(options["url"], "http://x?a=b");
+    }
+
+    #[test]
+    fn test_parse_kv_rejects_bare_words() {
+        assert!(parse_kv("port=8080 verbose").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use crate::playbook::task::Task;
 use crate::utils::get_unique_id;
+use crate::vars::variable::{combine_variables, ConflictResolution, Variable};
+use indexmap::IndexMap;
 
 #[derive(Clone, Debug)]
 pub enum BlockEntry {
@@ -15,6 +17,7 @@ pub struct Block {
     run_once: bool,
     implicit: bool,
     uuid: String,
+    vars: IndexMap<String, Variable>,
 }
 
 impl Block {
@@ -26,6 +29,7 @@ impl Block {
             always: Vec::new(),
             run_once: false,
             implicit: false,
+            vars: IndexMap::new(),
         }
     }
 
@@ -69,18 +73,60 @@ impl Block {
         self.always.get(index)
     }
 
-    pub fn add_to_block(&mut self, entry: BlockEntry) {
+    pub fn add_to_block(&mut self, mut entry: BlockEntry) {
+        Self::propagate_vars(&mut entry, &self.vars);
         self.block.push(entry);
     }
 
-    pub fn add_to_rescue(&mut self, entry: BlockEntry) {
+    pub fn add_to_rescue(&mut self, mut entry: BlockEntry) {
+        Self::propagate_vars(&mut entry, &self.vars);
         self.rescue.push(entry);
     }
 
-    pub fn add_to_always(&mut self, entry: BlockEntry) {
+    pub fn add_to_always(&mut self, mut entry: BlockEntry) {
+        Self::propagate_vars(&mut entry, &self.vars);
         self.always.push(entry);
     }
 
+    pub fn vars(&self) -> &IndexMap<String, Variable> {
+        &self.vars
+    }
+
+    /// Sets block vars and pushes them down to every task already in the block,
+    /// entries added later inherit them on insertion.
+    pub fn set_vars(&mut self, vars: IndexMap<String, Variable>) {
+        for entry in self
+            .block
+            .iter_mut()
+            .chain(self.rescue.iter_mut())
+            .chain(self.always.iter_mut())
+        {
+            Self::propagate_vars(entry, &vars);
+        }
+        self.vars = vars;
+    }
+
+    fn propagate_vars(entry: &mut BlockEntry, vars: &IndexMap<String, Variable>) {
+        if vars.is_empty() {
+            return;
+        }
+
+        match entry {
+            BlockEntry::Task(task) => task.inherit_vars(vars),
+            BlockEntry::Block(block) => {
+                block.vars = combine_variables(vars, &block.vars, &ConflictResolution::Replace);
+                for child in block
+                    .block
+                    .iter_mut()
+                    .chain(block.rescue.iter_mut())
+                    .chain(block.always.iter_mut())
+                {
+                    Self::propagate_vars(child, vars);
+                }
+            }
+        }
+    }
+
     pub fn set_is_implicit(&mut self, value: bool) {
         self.implicit = value;
     }
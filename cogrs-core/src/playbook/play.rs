@@ -24,6 +24,7 @@ pub struct Play {
     gather_timeout: u32,
     handlers: Vec<Handler>,
     limit: Option<String>,
+    limit_expr: Option<String>,
     name: String,
     no_log: bool,
     pattern: String,
@@ -58,6 +59,7 @@ impl Play {
         gather_timeout: u32,
         handlers: Vec<Handler>,
         limit: Option<String>,
+        limit_expr: Option<String>,
         name: String,
         no_log: bool,
         pattern: String,
@@ -89,6 +91,7 @@ impl Play {
             gather_timeout,
             handlers,
             limit,
+            limit_expr,
             name,
             no_log,
             pattern,
@@ -138,6 +141,10 @@ impl Play {
         self.limit.as_ref().map(|l| l.as_str())
     }
 
+    pub fn limit_expr(&self) -> Option<&str> {
+        self.limit_expr.as_deref()
+    }
+
     pub fn tags(&self) -> &Vec<String> {
         &self.tags
     }
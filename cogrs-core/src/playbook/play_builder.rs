@@ -25,6 +25,7 @@ pub struct PlayBuilder {
     gather_timeout: u32,
     handlers: Vec<Handler>,
     limit: Option<String>,
+    limit_expr: Option<String>,
     name: String,
     no_log: bool,
     pattern: String,
@@ -59,6 +60,7 @@ impl PlayBuilder {
             gather_timeout: GATHER_TIMEOUT_DEFAULT,
             handlers: Vec::new(),
             limit: None,
+            limit_expr: None,
             name: String::from(name),
             no_log: false,
             pattern: String::from("all"),
@@ -158,11 +160,28 @@ impl PlayBuilder {
         self
     }
 
+    /// Only keeps the hosts for which the conditional expression holds, evaluated with each
+    /// host's variables and cached facts.
+    pub fn limit_expr(mut self, limit_expr: Option<&str>) -> Self {
+        self.limit_expr = limit_expr.map(String::from);
+        self
+    }
+
     pub fn tags(mut self, tags: Vec<String>) -> Self {
         self.tags = tags;
         self
     }
 
+    pub fn vars(mut self, vars: IndexMap<String, Variable>) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    pub fn vars_files(mut self, vars_files: Vec<String>) -> Self {
+        self.vars_files = vars_files;
+        self
+    }
+
     pub fn finalized(mut self, value: bool) -> Self {
         self.finalized = value;
         self
@@ -185,6 +204,7 @@ impl PlayBuilder {
             self.gather_timeout,
             self.handlers,
             self.limit,
+            self.limit_expr,
             self.name,
             self.no_log,
             self.pattern,
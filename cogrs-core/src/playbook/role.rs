@@ -1,4 +1,8 @@
+use crate::parsing::loader::DataLoader;
 use crate::utils::get_unique_id;
+use crate::vars::variable::{load_vars_from_file, Variable};
+use anyhow::Result;
+use indexmap::IndexMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
@@ -7,6 +11,9 @@ pub struct Role {
     name: String,
     allow_duplicates: bool,
     path: PathBuf,
+    default_vars: IndexMap<String, Variable>,
+    vars: IndexMap<String, Variable>,
+    params: IndexMap<String, Variable>,
 }
 
 impl Role {
@@ -16,6 +23,9 @@ impl Role {
             name: name.to_string(),
             allow_duplicates: false,
             path: path.to_path_buf(),
+            default_vars: IndexMap::new(),
+            vars: IndexMap::new(),
+            params: IndexMap::new(),
         }
     }
 
@@ -34,4 +44,52 @@ impl Role {
     pub fn uuid(&self) -> &str {
         &self.uuid
     }
+
+    /// Variables from `defaults/main.yml`, lowest precedence of all play variables.
+    pub fn default_vars(&self) -> &IndexMap<String, Variable> {
+        &self.default_vars
+    }
+
+    /// Variables from `vars/main.yml`, these override play vars and vars_files.
+    pub fn vars(&self) -> &IndexMap<String, Variable> {
+        &self.vars
+    }
+
+    /// Parameters passed to the role where it is referenced in the play.
+    pub fn params(&self) -> &IndexMap<String, Variable> {
+        &self.params
+    }
+
+    pub fn set_default_vars(&mut self, vars: IndexMap<String, Variable>) {
+        self.default_vars = vars;
+    }
+
+    pub fn set_vars(&mut self, vars: IndexMap<String, Variable>) {
+        self.vars = vars;
+    }
+
+    pub fn set_params(&mut self, params: IndexMap<String, Variable>) {
+        self.params = params;
+    }
+
+    /// Loads `defaults/main.{yml,yaml}` and `vars/main.{yml,yaml}` from the role path,
+    /// missing files are silently skipped.
+    pub fn load_vars(&mut self, loader: &DataLoader) -> Result<()> {
+        if let Some(path) = self.find_main_file("defaults") {
+            self.default_vars = load_vars_from_file(&path, loader)?;
+        }
+
+        if let Some(path) = self.find_main_file("vars") {
+            self.vars = load_vars_from_file(&path, loader)?;
+        }
+
+        Ok(())
+    }
+
+    fn find_main_file(&self, sub_dir: &str) -> Option<PathBuf> {
+        ["main.yml", "main.yaml"]
+            .iter()
+            .map(|file_name| self.path.join(sub_dir).join(file_name))
+            .find(|path| path.is_file())
+    }
 }
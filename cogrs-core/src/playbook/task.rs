@@ -1,5 +1,7 @@
 use crate::playbook::role::Role;
 use crate::utils::get_unique_id;
+use crate::vars::variable::{combine_variables, ConflictResolution, Variable};
+use indexmap::IndexMap;
 use std::fmt;
 use std::fmt::Formatter;
 
@@ -23,6 +25,8 @@ pub struct Task {
     throttle: usize,
     run_once: bool,
     connection: String,
+    vars: IndexMap<String, Variable>,
+    include_params: IndexMap<String, Variable>,
 }
 
 // TODO: add task builder
@@ -50,6 +54,8 @@ impl Task {
             throttle: 0, // TODO: where do we  get throttle
             run_once: false,
             connection,
+            vars: IndexMap::new(),
+            include_params: IndexMap::new(),
         }
     }
 
@@ -65,6 +71,10 @@ impl Task {
         &self.action
     }
 
+    pub(crate) fn set_action(&mut self, action: Action) {
+        self.action = action;
+    }
+
     pub fn role(&self) -> Option<&Role> {
         self.role.as_ref()
     }
@@ -88,6 +98,23 @@ impl Task {
     pub fn run_once(&self) -> bool {
         self.run_once
     }
+
+    /// Returns the task vars combined with the vars of all enclosing blocks,
+    /// the task's own vars take precedence.
+    pub fn get_vars(&self) -> &IndexMap<String, Variable> {
+        &self.vars
+    }
+
+    /// Params of the include that brought this task in (`include_tasks: file.yml` with `vars:`).
+    pub fn include_params(&self) -> &IndexMap<String, Variable> {
+        &self.include_params
+    }
+
+    /// Adds vars from an enclosing block. Blocks are wrapped from the inside out,
+    /// so the new vars go underneath the ones already set.
+    pub(crate) fn inherit_vars(&mut self, vars: &IndexMap<String, Variable>) {
+        self.vars = combine_variables(vars, &self.vars, &ConflictResolution::Replace);
+    }
 }
 
 impl fmt::Display for Task {
@@ -115,6 +142,8 @@ pub struct TaskBuilder {
     implicit: bool,
     tags: Vec<String>,
     connection: String,
+    vars: IndexMap<String, Variable>,
+    include_params: IndexMap<String, Variable>,
 }
 
 impl TaskBuilder {
@@ -128,6 +157,8 @@ impl TaskBuilder {
             implicit: false,
             tags: Vec::new(),
             connection: connection.to_string(),
+            vars: IndexMap::new(),
+            include_params: IndexMap::new(),
         }
     }
 
@@ -156,8 +187,18 @@ impl TaskBuilder {
         self
     }
 
+    pub fn vars(mut self, vars: IndexMap<String, Variable>) -> Self {
+        self.vars = vars;
+        self
+    }
+
+    pub fn include_params(mut self, params: IndexMap<String, Variable>) -> Self {
+        self.include_params = params;
+        self
+    }
+
     pub fn build(self) -> Task {
-        Task::new(
+        let mut task = Task::new(
             self.name,
             &self.action,
             self.role,
@@ -166,6 +207,9 @@ impl TaskBuilder {
             self.implicit,
             self.tags,
             self.connection,
-        )
+        );
+        task.vars = self.vars;
+        task.include_params = self.include_params;
+        task
     }
 }
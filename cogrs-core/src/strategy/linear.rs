@@ -1,4 +1,5 @@
 use crate::executor::host_state::HostState;
+use crate::executor::inventory_actions::InventoryAction;
 use crate::executor::play_iterator::PlayIterator;
 use crate::executor::task_executor::TaskExecutor;
 use crate::executor::task_queue_manager::TaskQueueManager;
@@ -8,13 +9,17 @@ use crate::inventory::manager::InventoryManager;
 use crate::playbook::block::BlockEntry;
 use crate::playbook::play::Play;
 use crate::playbook::task::{Action, Task};
+use crate::template::Templar;
+use crate::vars::hostvars::HostVars;
 use crate::vars::manager::VariableManager;
 use crate::vars::variable::Variable;
 use anyhow::{anyhow, bail, Result};
+use cogrs_modules::task_result::TaskResult;
 use cogrs_plugins::callback::EventType;
 use indexmap::IndexMap;
 use log::{debug, error, warn};
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::time::{self, Duration};
 
@@ -23,19 +28,31 @@ use tokio::time::{self, Duration};
 ///         moving on to the next task
 pub struct LinearStrategy<'a> {
     tqm: &'a mut TaskQueueManager,
-    inventory_manager: &'a InventoryManager,
-    variable_manager: &'a VariableManager,
+    inventory_manager: Arc<InventoryManager>,
+    variable_manager: Arc<VariableManager>,
     host_cache: Vec<String>,
     blocked_hosts: HashMap<String, bool>,
     cur_worker: usize,
     pending_results: u32,
 }
 
-async fn results_thread(mut receiver: mpsc::Receiver<WorkerMessage>) {
+async fn results_thread(
+    mut receiver: mpsc::Receiver<WorkerMessage>,
+    variable_manager: Arc<VariableManager>,
+) {
     while let Some(msg) = receiver.recv().await {
         match msg {
             WorkerMessage::Callback((event, task_result)) => {
                 debug!("received callback from worker: {:?}", event);
+                if let Some(task_result) = task_result {
+                    if let Err(e) = store_facts(&variable_manager, &task_result) {
+                        error!(
+                            "Failed to store the facts of {}: {}",
+                            task_result.host_name(),
+                            e
+                        );
+                    }
+                }
             }
             WorkerMessage::Display(_) => {}
             WorkerMessage::Prompt(_) => {}
@@ -43,15 +60,33 @@ async fn results_thread(mut receiver: mpsc::Receiver<WorkerMessage>) {
     }
 }
 
+/// Keeps the facts a task gathered in the fact cache, so they are used by later tasks
+/// and by smart gathering.
+fn store_facts(variable_manager: &VariableManager, task_result: &TaskResult) -> Result<()> {
+    let Some(facts) = task_result.facts() else {
+        return Ok(());
+    };
+
+    match Variable::try_from(&serde_yaml::to_value(facts)?)? {
+        Variable::Mapping(facts) => {
+            variable_manager.set_host_facts(task_result.host_name(), facts.into_map())
+        }
+        _ => bail!(
+            "Facts returned for {} are not a dictionary",
+            task_result.host_name()
+        ),
+    }
+}
+
 impl<'a> LinearStrategy<'a> {
     pub fn new(
         tqm: &'a mut TaskQueueManager,
-        inventory_manager: &'a InventoryManager,
-        variable_manager: &'a VariableManager,
+        inventory_manager: InventoryManager,
+        variable_manager: Arc<VariableManager>,
     ) -> Self {
         LinearStrategy {
             tqm,
-            inventory_manager,
+            inventory_manager: Arc::new(inventory_manager),
             variable_manager,
             host_cache: Vec::new(),
             blocked_hosts: HashMap::new(),
@@ -69,22 +104,28 @@ impl<'a> LinearStrategy<'a> {
         let pattern = play.pattern();
         let limit = play.limit();
 
-        self.host_cache = self
-            .inventory_manager
-            .filter_hosts(pattern, limit)?
-            .iter()
-            .map(|h| h.name().to_string())
-            .collect();
+        let mut hosts = self.inventory_manager.filter_hosts(pattern, limit)?;
+        if let Some(limit_expr) = play.limit_expr() {
+            hosts = self.inventory_manager.filter_hosts_by_expression(
+                hosts,
+                limit_expr,
+                &self.variable_manager,
+                Some(play),
+            )?;
+        }
+
+        self.host_cache = hosts.iter().map(|h| h.name().to_string()).collect();
 
         Ok(())
     }
 
-    fn get_hosts_left(&self) -> Vec<&Host> {
+    fn get_hosts_left(&self) -> Vec<Host> {
         self.host_cache
             .iter()
             .filter(|h| !self.tqm.get_unreachable_hosts().contains_key(*h))
             // we're assuming inventory should be able to return all hosts here
             .filter_map(|h| self.inventory_manager.get_host(h))
+            .cloned()
             .collect()
     }
 
@@ -159,21 +200,29 @@ impl<'a> LinearStrategy<'a> {
 
     pub async fn run(&mut self, iterator: &mut PlayIterator) -> Result<()> {
         self.set_host_cache(iterator.play(), false)?;
+        let play = iterator.play().clone();
         let mut work_to_do = true;
         let mut callback_sent = false;
 
         // TODO: how big of a channel do we want?
         let (sender, receiver) = mpsc::channel(100);
-        let reader = tokio::spawn(results_thread(receiver));
+        let reader = tokio::spawn(results_thread(receiver, self.variable_manager.clone()));
 
         while work_to_do && !self.tqm.is_terminated() {
             debug!("getting the remaining hosts for this loop");
             let hosts_left = self.get_hosts_left();
+            let hosts_left_names: Vec<String> =
+                hosts_left.iter().map(|h| h.name().to_string()).collect();
+            self.variable_manager
+                .set_play_hosts(&self.host_cache, &hosts_left_names);
 
             let mut callback_sent = false;
             work_to_do = false;
 
-            let host_tasks = self.get_next_task_lockstep(hosts_left.clone(), iterator)?;
+            let host_tasks = self.get_next_task_lockstep(hosts_left.iter().collect(), iterator)?;
+            // actions bypassing the host loop only run for the first host
+            let mut bypassed_tasks: HashSet<String> = HashSet::new();
+            let mut templar = self.new_templar(&play);
 
             let skip_rest = false;
             let choose_step = true;
@@ -187,23 +236,38 @@ impl<'a> LinearStrategy<'a> {
                 let host = self
                     .inventory_manager
                     .get_host(&host)
+                    .cloned()
                     .ok_or(anyhow!("Host not found: {}", host))?;
 
                 let mut task_vars = self.variable_manager.get_vars(
                     Some(iterator.play()),
-                    Some(host),
+                    Some(&host),
                     Some(&task),
-                    Some(self.inventory_manager),
-                    true,
+                    Some(&self.inventory_manager),
                     true,
-                );
+                )?;
 
                 task_vars.insert(
                     String::from("remote_addr"),
                     Variable::String(host.address().to_string()),
                 );
 
-                if let Action::Meta(action) = task.action() {
+                let task = self.template_task(&templar, &task, &task_vars)?;
+
+                if let Some(action) = InventoryAction::from_task(&task)? {
+                    if action.bypass_host_loop() && !bypassed_tasks.insert(task.uuid().to_string())
+                    {
+                        continue;
+                    }
+                    debug!("updating the inventory for {}/{}", host.name(), task);
+                    if action.changes(host.name(), &self.inventory_manager) {
+                        // the hostvars of the templar share the inventory, they are built
+                        // again once it changed
+                        drop(templar);
+                        action.apply(host.name(), self.inventory_manager_mut()?)?;
+                        templar = self.new_templar(&play);
+                    }
+                } else if let Action::Meta(action) = task.action() {
                     // TODO: handle meta actions
                 } else {
                     if !callback_sent {
@@ -222,7 +286,7 @@ impl<'a> LinearStrategy<'a> {
                     }
 
                     self.blocked_hosts.insert(host.name().to_string(), true);
-                    self.queue_task(host, &task, task_vars, sender.clone())
+                    self.queue_task(&host, &task, task_vars, sender.clone())
                         .await?;
                 }
             }
@@ -233,6 +297,51 @@ impl<'a> LinearStrategy<'a> {
         Ok(())
     }
 
+    /// Returns the templar for the tasks of one iteration, its `hostvars` only compute the
+    /// variables of the hosts templates access, once per iteration.
+    fn new_templar(&self, play: &Play) -> Templar {
+        let mut templar = Templar::new();
+        let hostvars = Arc::new(HostVars::new(
+            self.variable_manager.clone(),
+            self.inventory_manager.clone(),
+            Some(play.clone()),
+        ));
+        templar.set_global("hostvars", hostvars.to_template_value(&templar));
+        templar
+    }
+
+    /// Renders the module arguments of a task with the variables of the host it runs on.
+    fn template_task(
+        &self,
+        templar: &Templar,
+        task: &Task,
+        task_vars: &IndexMap<String, Variable>,
+    ) -> Result<Task> {
+        let Action::Module(module_name, Some(args)) = task.action() else {
+            return Ok(task.clone());
+        };
+
+        let mut task = task.clone();
+        task.set_action(Action::Module(
+            module_name.to_string(),
+            Some(templar.template_args(args, task_vars)?),
+        ));
+
+        Ok(task)
+    }
+
+    /// The inventory can only change while no `hostvars` share it.
+    fn inventory_manager_mut(&mut self) -> Result<&mut InventoryManager> {
+        Arc::get_mut(&mut self.inventory_manager)
+            .ok_or(anyhow!("The inventory can't change while hostvars use it"))
+    }
+
+    /// Returns the inventory, with the changes of the play, once the strategy is done.
+    pub fn into_inventory_manager(self) -> Result<InventoryManager> {
+        Arc::try_unwrap(self.inventory_manager)
+            .map_err(|_| anyhow!("The inventory is still used by hostvars"))
+    }
+
     fn spawn_new_worker(
         &mut self,
         worker_index: usize,
@@ -250,7 +359,13 @@ impl<'a> LinearStrategy<'a> {
             let result = executor.run(&host, &task, task_vars, &sender).await;
 
             match result {
-                Ok(_) => {}
+                Ok(task_result) => {
+                    let message =
+                        WorkerMessage::Callback((EventType::RunnerOnOk, Some(task_result)));
+                    if let Err(e) = sender.send(message).await {
+                        error!("Failed to send the task result: {}", e);
+                    }
+                }
                 Err(e) => {
                     error!("Error running task: {}", e);
                 }
@@ -329,3 +444,38 @@ impl<'a> LinearStrategy<'a> {
 
     pub fn cleanup(&self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::variable::Number;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_results_store_gathered_facts() {
+        let variable_manager = Arc::new(VariableManager::new(&PathBuf::from(".")));
+        let (sender, receiver) = mpsc::channel(10);
+        let reader = tokio::spawn(results_thread(receiver, variable_manager.clone()));
+
+        let mut gathered = TaskResult::new("web1", "setup");
+        gathered.set_facts(json!({"os_family": "Debian", "processor_count": 4}));
+        for task_result in [gathered, TaskResult::new("web2", "ping")] {
+            sender
+                .send(WorkerMessage::Callback((
+                    EventType::RunnerOnOk,
+                    Some(task_result),
+                )))
+                .await
+                .unwrap();
+        }
+        drop(sender);
+        reader.await.unwrap();
+
+        let fact_cache = variable_manager.fact_cache();
+        let facts = fact_cache.get("web1").unwrap().unwrap();
+        assert_eq!(facts["os_family"], Variable::String("Debian".to_string()));
+        assert_eq!(facts["processor_count"], Variable::Number(Number::Int(4)));
+        assert!(!fact_cache.contains("web2").unwrap());
+    }
+}
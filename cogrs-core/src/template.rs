@@ -1,32 +1,688 @@
+pub(crate) mod filters;
+mod jinja_tests;
+
+use crate::lookup;
 use crate::vars::variable::Variable;
-use anyhow::Result;
-use minijinja::Template;
+use crate::vault::secrets::VaultSecrets;
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+use minijinja::value::{Enumerator, Object};
+use minijinja::{Environment, ErrorKind, UndefinedBehavior, Value};
 use once_cell::sync::Lazy;
 use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
 static JINJA_REGEX: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"(\{\{\s*[a-zA-Z_][a-zA-Z0-9_]*\s*}}|\{%.+?%})").unwrap());
 
-pub struct Templar;
+/// Renders jinja templates found in variables and task arguments.
+#[derive(Debug, Clone)]
+pub struct Templar {
+    env: Arc<Environment<'static>>,
+    globals: IndexMap<String, Value>,
+    vault_secrets: Option<Arc<VaultSecrets>>,
+}
+
+impl Default for Templar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Templar {
     pub fn new() -> Self {
-        Self
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_keep_trailing_newline(true);
+        filters::register(&mut env);
+        jinja_tests::register(&mut env);
+        lookup::register(&mut env);
+
+        Self {
+            env: Arc::new(env),
+            globals: IndexMap::new(),
+            vault_secrets: None,
+        }
+    }
+
+    /// Makes a value available to every template rendered by this templar,
+    /// such as the lazy `hostvars` object. Globals take precedence over variables.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    /// Decrypts `!vault` values with `secrets` instead of the secrets given on the command line.
+    pub fn set_vault_secrets(&mut self, secrets: VaultSecrets) {
+        self.vault_secrets = Some(Arc::new(secrets));
     }
 
+    /// Checks if any string in the variable tree may contain a template.
     pub fn is_template(&self, data: &Variable) -> Result<bool> {
-        todo!()
+        Ok(contains_template(data))
     }
 
     pub fn is_jinja_template(&self, data: &str) -> bool {
         // TODO: see if we could use minijina to make sure it also has valid syntax
         JINJA_REGEX.is_match(data)
     }
+
+    /// Recursively renders every template in `data`. Variables referenced by a template are
+    /// rendered lazily on first use, so they can reference other variables in turn.
+    /// A string made of a single `{{ expression }}` keeps the native type of the result.
+    pub fn template(&self, data: &Variable, vars: &IndexMap<String, Variable>) -> Result<Variable> {
+        if !contains_template(data) {
+            return Ok(data.clone());
+        }
+
+        let context = Arc::new(TemplateContext::new(self, Arc::new(vars.clone())));
+
+        context.render(data)
+    }
+
+    /// Evaluates a conditional expression, such as `ansible_kernel is version('5.10', '<')`,
+    /// to whether it holds.
+    pub fn evaluate_conditional(
+        &self,
+        condition: &str,
+        vars: &IndexMap<String, Variable>,
+    ) -> Result<bool> {
+        let template = format!("{{% if {condition} %}}True{{% else %}}False{{% endif %}}");
+        match self.template(&Variable::String(template), vars)? {
+            Variable::String(result) => Ok(result == "True"),
+            Variable::Bool(result) => Ok(result),
+            result => bail!("Unexpected result for conditional '{condition}': {result:?}"),
+        }
+    }
+
+    /// Wraps variables into a template value whose entries are rendered on access.
+    pub(crate) fn lazy_vars(&self, vars: Arc<IndexMap<String, Variable>>) -> Value {
+        Value::from_object(TemplateContext::new(self, vars))
+    }
+
+    /// Renders the arguments of an `Action::Module`, given as a JSON string.
+    pub fn template_args(&self, args: &str, vars: &IndexMap<String, Variable>) -> Result<String> {
+        let data = serde_yaml::from_str::<serde_yaml::Value>(args)
+            .ok()
+            .and_then(|value| Variable::try_from(&value).ok())
+            .unwrap_or_else(|| Variable::String(args.to_string()));
+
+        if !contains_template(&data) {
+            return Ok(args.to_string());
+        }
+
+        match self.template(&data, vars)? {
+            Variable::String(rendered) => Ok(rendered),
+            rendered => Ok(serde_json::to_string(&rendered)?),
+        }
+    }
+}
+
+/// Cheap check for template markers, the same one ansible uses before rendering.
+fn is_possibly_template(data: &str) -> bool {
+    data.contains("{{") || data.contains("{%") || data.contains("{#")
+}
+
+fn contains_template(data: &Variable) -> bool {
+    match data {
+        Variable::String(s) => is_possibly_template(s),
+        Variable::Sequence(seq) => seq.iter().any(contains_template),
+        Variable::Mapping(mapping) => mapping
+            .iter()
+            .any(|(key, value)| is_possibly_template(key) || contains_template(value)),
+        // rendering decrypts the value
+        Variable::Vault(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns the expression if the whole string is a single `{{ expression }}` block.
+fn single_expression(data: &str) -> Option<&str> {
+    let inner = data.strip_prefix("{{")?.strip_suffix("}}")?;
+
+    if inner.contains("{{") || inner.contains("}}") || inner.contains("{%") || inner.contains("{#")
+    {
+        return None;
+    }
+
+    Some(inner.trim())
+}
+
+fn value_to_variable(value: &Value) -> Result<Variable> {
+    let value = serde_yaml::to_value(value)?;
+    Variable::try_from(&value)
+}
+
+/// A template used an undefined variable. A variable whose template fails this way is
+/// undefined itself, so `default` and `is defined` still handle it.
+#[derive(Debug)]
+struct UndefinedError(String);
+
+impl fmt::Display for UndefinedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for UndefinedError {}
+
+/// Template context which renders variables the first time a template looks them up,
+/// detecting variables that reference themselves.
+struct TemplateContext {
+    env: Arc<Environment<'static>>,
+    vars: Arc<IndexMap<String, Variable>>,
+    globals: IndexMap<String, Value>,
+    vault_secrets: Option<Arc<VaultSecrets>>,
+    resolved: Mutex<HashMap<String, Value>>,
+    resolving: Mutex<Vec<String>>,
+    error: Mutex<Option<anyhow::Error>>,
+    undefined: Mutex<Option<anyhow::Error>>,
+}
+
+impl fmt::Debug for TemplateContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TemplateContext")
+            .field("vars", &self.vars.keys().collect::<Vec<_>>())
+            .field("globals", &self.globals.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Object for TemplateContext {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        let name = key.as_str()?;
+
+        if let Some(value) = self.globals.get(name) {
+            return Some(value.clone());
+        }
+
+        match self.resolve(name) {
+            // `vars` gives templates and lookups access to the whole context
+            Ok(None) if name == "vars" => Some(Value::from_dyn_object(self.clone())),
+            Ok(value) => value,
+            Err(e) => {
+                // minijinja can't carry our error, keep it until the render call returns.
+                // An undefined variable only fails the render if the template doesn't
+                // handle it, it then explains why the variable is undefined
+                let slot = match e.is::<UndefinedError>() {
+                    true => &self.undefined,
+                    false => &self.error,
+                };
+                if let Ok(mut error) = slot.lock() {
+                    error.get_or_insert(e);
+                }
+                None
+            }
+        }
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        Enumerator::Values(
+            self.vars
+                .keys()
+                .chain(self.globals.keys())
+                .map(|key| Value::from(key.as_str()))
+                .collect(),
+        )
+    }
+}
+
+impl TemplateContext {
+    fn new(templar: &Templar, vars: Arc<IndexMap<String, Variable>>) -> Self {
+        TemplateContext {
+            env: templar.env.clone(),
+            vars,
+            globals: templar.globals.clone(),
+            vault_secrets: templar.vault_secrets.clone(),
+            resolved: Mutex::new(HashMap::new()),
+            resolving: Mutex::new(Vec::new()),
+            error: Mutex::new(None),
+            undefined: Mutex::new(None),
+        }
+    }
+
+    fn resolve(self: &Arc<Self>, name: &str) -> Result<Option<Value>> {
+        if let Some(value) = self
+            .resolved
+            .lock()
+            .ok()
+            .and_then(|resolved| resolved.get(name).cloned())
+        {
+            return Ok(Some(value));
+        }
+
+        let Some(variable) = self.vars.get(name) else {
+            return Ok(None);
+        };
+
+        {
+            let mut resolving = self
+                .resolving
+                .lock()
+                .map_err(|_| anyhow!("Template context lock is poisoned"))?;
+
+            if let Some(pos) = resolving.iter().position(|n| n == name) {
+                bail!(
+                    "Recursive loop detected in template: {} -> {}",
+                    resolving[pos..].join(" -> "),
+                    name
+                );
+            }
+
+            resolving.push(name.to_string());
+        }
+
+        let rendered = self.render(variable);
+
+        if let Ok(mut resolving) = self.resolving.lock() {
+            resolving.pop();
+        }
+
+        let value = Value::from_serialize(rendered?);
+
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.insert(name.to_string(), value.clone());
+        }
+
+        Ok(Some(value))
+    }
+
+    fn render(self: &Arc<Self>, data: &Variable) -> Result<Variable> {
+        match data {
+            Variable::String(s) => self.render_string(s),
+            Variable::Sequence(seq) => Ok(Variable::Sequence(
+                seq.iter()
+                    .map(|item| self.render(item))
+                    .collect::<Result<_>>()?,
+            )),
+            Variable::Mapping(mapping) => {
+                let mut map = IndexMap::new();
+
+                for (key, value) in mapping {
+                    let key = match self.render_string(key)? {
+                        Variable::String(key) => key,
+                        key => serde_json::to_string(&key)?,
+                    };
+                    map.insert(key, self.render(value)?);
+                }
+
+                Ok(Variable::Mapping(map.into()))
+            }
+            // the plaintext is a value like any other string, it is not a template
+            Variable::Vault(value) => {
+                let plaintext = match &self.vault_secrets {
+                    Some(secrets) => value.decrypt_with(secrets)?,
+                    None => value.decrypt()?,
+                };
+                Ok(Variable::String(plaintext.to_string()))
+            }
+            // scalars and `!unsafe` values, including their keys, are passed through untouched
+            _ => Ok(data.clone()),
+        }
+    }
+
+    fn render_string(self: &Arc<Self>, data: &str) -> Result<Variable> {
+        if !is_possibly_template(data) {
+            return Ok(Variable::String(data.to_string()));
+        }
+
+        let ctx = Value::from_dyn_object(self.clone());
+
+        let env: &Environment<'_> = &self.env;
+        let result = match single_expression(data) {
+            Some(expr) => env
+                .compile_expression(expr)
+                .and_then(|expr| expr.eval(&ctx))
+                .map(|value| match value.is_undefined() {
+                    true => Err(UndefinedError(format!("'{}' is undefined", expr)).into()),
+                    false => value_to_variable(&value),
+                }),
+            None => env
+                .render_str(data, &ctx)
+                .map(|rendered| Ok(Variable::String(rendered))),
+        };
+
+        let undefined = self
+            .undefined
+            .lock()
+            .ok()
+            .and_then(|mut undefined| undefined.take());
+        if let Some(error) = self.error.lock().ok().and_then(|mut error| error.take()) {
+            return Err(error);
+        }
+
+        match result {
+            Ok(Err(e)) if e.is::<UndefinedError>() => Err(undefined.unwrap_or(e)),
+            Ok(variable) => variable,
+            Err(e) if e.kind() == ErrorKind::UndefinedError => {
+                Err(undefined.unwrap_or_else(|| {
+                    UndefinedError(format!("Error rendering template '{}': {:#}", data, e)).into()
+                }))
+            }
+            Err(e) => bail!("Error rendering template '{}': {:#}", data, e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Templar;
+    use super::*;
+    use crate::vars::variable::{Mapping, Number};
+    use crate::vault::editor::format_ciphertext_yaml;
+    use crate::vault::secrets::{VaultSecret, VaultSecrets};
+
+    fn vars(pairs: &[(&str, Variable)]) -> IndexMap<String, Variable> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn string(value: &str) -> Variable {
+        Variable::String(value.to_string())
+    }
+
+    #[test]
+    fn test_template_keeps_native_types() {
+        let templar = Templar::new();
+        let vars = vars(&[("port", Variable::Number(Number::Int(8080)))]);
+
+        assert_eq!(
+            templar.template(&string("{{ 3 }}"), &vars).unwrap(),
+            Variable::Number(Number::Int(3))
+        );
+        assert_eq!(
+            templar.template(&string("{{ port }}"), &vars).unwrap(),
+            Variable::Number(Number::Int(8080))
+        );
+        assert_eq!(
+            templar.template(&string("{{ [1, true] }}"), &vars).unwrap(),
+            Variable::Sequence(vec![Variable::Number(Number::Int(1)), Variable::Bool(true)])
+        );
+        assert_eq!(
+            templar.template(&string("port {{ port }}"), &vars).unwrap(),
+            string("port 8080")
+        );
+    }
+
+    #[test]
+    fn test_template_resolves_variables_lazily() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("url", string("http://{{ host }}:{{ port }}/")),
+            ("host", string("{{ name }}.example.com")),
+            ("name", string("web")),
+            ("port", Variable::Number(Number::Int(80))),
+            ("broken", string("{{ undefined_var }}")),
+        ]);
+
+        let mut args = Mapping::new();
+        args.insert("{{ name }}_url".to_string(), string("{{ url }}"));
+        args.insert(
+            "ports".to_string(),
+            Variable::Sequence(vec![string("{{ port }}"), string("{{ port + 1 }}")]),
+        );
+
+        let rendered = templar.template(&Variable::Mapping(args), &vars).unwrap();
+
+        let mut expected = Mapping::new();
+        expected.insert("web_url".to_string(), string("http://web.example.com:80/"));
+        expected.insert(
+            "ports".to_string(),
+            Variable::Sequence(vec![
+                Variable::Number(Number::Int(80)),
+                Variable::Number(Number::Int(81)),
+            ]),
+        );
+        assert_eq!(rendered, Variable::Mapping(expected));
+    }
+
+    #[test]
+    fn test_template_passes_unsafe_values_through() {
+        let templar = Templar::new();
+        let data: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+alert: !unsafe "{{ $labels.instance }} is down"
+rules: !unsafe
+  - summary: "{{ $value }}"
+    "{{ key }}": x
+name: "{{ job }}"
+nested:
+  - !unsafe "{{ raw }}"
+  - "{{ job }}"
+"#,
+        )
+        .unwrap();
+        let data = Variable::try_from(&data).unwrap();
+        let vars = vars(&[("job", string("node")), ("alias", string("{{ alert }}"))]);
+
+        let rendered = templar.template(&data, &vars).unwrap();
+        let Variable::Mapping(rendered) = rendered else {
+            panic!("expected a mapping");
+        };
+        let Variable::Mapping(expected) = &data else {
+            panic!("expected a mapping");
+        };
+
+        assert_eq!(rendered.map["alert"], expected.map["alert"]);
+        assert_eq!(rendered.map["rules"], expected.map["rules"]);
+        assert_eq!(rendered.map["name"], string("node"));
+        assert_eq!(
+            rendered.map["nested"],
+            Variable::Sequence(vec![
+                Variable::Unsafe(Box::new(string("{{ raw }}"))),
+                string("node")
+            ])
+        );
+
+        // unsafe values referenced by other variables are not rendered either
+        let mut vars = vars;
+        vars.insert("alert".to_string(), expected.map["alert"].clone());
+        assert_eq!(
+            templar.template(&string("{{ alias }}"), &vars).unwrap(),
+            string("{{ $labels.instance }} is down")
+        );
+    }
+
+    #[test]
+    fn test_template_decrypts_vault_values_on_use() {
+        let secret = VaultSecret::new("template-test", "template-secret");
+        let data: serde_yaml::Value = serde_yaml::from_str(&format!(
+            "db_password: {}\nport: 5432\n",
+            format_ciphertext_yaml(&secret.encrypt("{{ prefix }}-hunter2").unwrap(), None)
+        ))
+        .unwrap();
+        let Variable::Mapping(mapping) = Variable::try_from(&data).unwrap() else {
+            panic!("expected a mapping");
+        };
+        let mut vars = mapping.into_map();
+        vars.insert("prefix".to_string(), string("prod"));
+
+        // values that are never used don't need the vault password
+        let mut templar = Templar::new();
+        assert_eq!(
+            templar.template(&string("{{ port }}"), &vars).unwrap(),
+            Variable::Number(Number::Int(5432))
+        );
+        assert!(templar
+            .template(&string("{{ db_password }}"), &vars)
+            .is_err());
+
+        let mut secrets = VaultSecrets::new();
+        secrets.add(secret);
+        templar.set_vault_secrets(secrets);
+
+        // the plaintext is not a template
+        assert_eq!(
+            templar
+                .template(&string("{{ db_password }}"), &vars)
+                .unwrap(),
+            string("{{ prefix }}-hunter2")
+        );
+        // serializing keeps the value encrypted
+        assert!(!serde_json::to_string(&vars["db_password"])
+            .unwrap()
+            .contains("hunter2"));
+    }
+
+    #[test]
+    fn test_template_detects_recursive_loop() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("a", string("{{ b }}")),
+            ("b", string("x{{ c }}")),
+            ("c", string("{{ a }}")),
+        ]);
+
+        let err = templar.template(&string("{{ a }}"), &vars).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Recursive loop detected in template: a -> b -> c -> a"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_template_undefined_variable() {
+        let templar = Templar::new();
+        let result = templar.template(&string("{{ missing }}"), &IndexMap::new());
+        assert!(result.is_err());
+
+        let rendered = templar
+            .template(&string("{{ missing | default('x') }}"), &IndexMap::new())
+            .unwrap();
+        assert_eq!(rendered, string("x"));
+    }
+
+    #[test]
+    fn test_template_nested_undefined_variable() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("x", string("{{ y }}")),
+            ("greeting", string("hello {{ name }}")),
+        ]);
+
+        for (template, expected) in [
+            ("{{ x | default('a') }}", string("a")),
+            ("{{ greeting | default('hi') }}", string("hi")),
+            ("{{ x is defined }}", Variable::Bool(false)),
+        ] {
+            let rendered = templar.template(&string(template), &vars).unwrap();
+            assert_eq!(rendered, expected, "{template}");
+        }
+
+        // the render fails on the variable that is actually undefined
+        let err = templar.template(&string("{{ x }}"), &vars).unwrap_err();
+        assert!(err.to_string().contains("'y' is undefined"), "{}", err);
+        let err = templar
+            .template(&string("say {{ greeting }}"), &vars)
+            .unwrap_err();
+        assert!(err.to_string().contains("hello {{ name }}"), "{}", err);
+    }
+
+    #[test]
+    fn test_evaluate_conditional_nested_undefined_variable() {
+        let templar = Templar::new();
+        let vars = vars(&[("x", string("{{ y }}"))]);
+
+        assert!(!templar.evaluate_conditional("x is defined", &vars).unwrap());
+        assert!(templar
+            .evaluate_conditional("x is undefined", &vars)
+            .unwrap());
+        assert!(templar
+            .evaluate_conditional("x.field == 'b'", &vars)
+            .is_err());
+    }
+
+    #[test]
+    fn test_evaluate_conditional() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("os_family", string("Debian")),
+            ("port", string("{{ 80 }}")),
+        ]);
+
+        assert!(templar
+            .evaluate_conditional("os_family == 'Debian'", &vars)
+            .unwrap());
+        assert!(!templar.evaluate_conditional("port > 1024", &vars).unwrap());
+        assert!(templar
+            .evaluate_conditional("missing.attribute == 'x'", &vars)
+            .is_err());
+    }
+
+    #[test]
+    fn test_template_args() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("cmd", string("uptime")),
+            ("timeout", string("{{ 5 * 2 }}")),
+        ]);
+
+        let args = templar
+            .template_args(r#"{"cmd": "{{ cmd }}", "timeout": "{{ timeout }}"}"#, &vars)
+            .unwrap();
+        assert_eq!(args, r#"{"cmd":"uptime","timeout":10}"#);
+
+        let args = templar.template_args(r#"{"cmd": "ls"}"#, &vars).unwrap();
+        assert_eq!(args, r#"{"cmd": "ls"}"#);
+    }
+
+    #[test]
+    fn test_template_globals() {
+        let mut templar = Templar::new();
+        templar.set_global(
+            "hostvars",
+            Value::from_serialize(IndexMap::from([(
+                "db1",
+                IndexMap::from([("ip", "10.0.0.1")]),
+            )])),
+        );
+
+        let rendered = templar
+            .template(&string("{{ hostvars['db1'].ip }}"), &IndexMap::new())
+            .unwrap();
+        assert_eq!(rendered, string("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_template_ansible_filters_and_tests() {
+        let templar = Templar::new();
+        let vars = vars(&[
+            ("version", string("2.10")),
+            ("empty", string("")),
+            ("result", string("{{ {'changed': true} }}")),
+        ]);
+
+        assert_eq!(
+            templar
+                .template(
+                    &string(
+                        "{{ empty | default('x', true) }}-{{ version is version('2.9', '>') }}"
+                    ),
+                    &vars
+                )
+                .unwrap(),
+            string("x-true")
+        );
+        assert_eq!(
+            templar
+                .template(&string("{{ result is changed }}"), &vars)
+                .unwrap(),
+            Variable::Bool(true)
+        );
+    }
+
+    #[test]
+    fn test_is_template() {
+        let templar = Templar::new();
+        let nested = Variable::Sequence(vec![Variable::Bool(true), string("{{ a.b }}")]);
+
+        assert!(templar.is_template(&nested).unwrap());
+        assert!(!templar.is_template(&string("plain")).unwrap());
+    }
 
     #[test]
     fn test_contains_jinja_expression() {
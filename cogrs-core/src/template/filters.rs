@@ -0,0 +1,1123 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ipnet::IpNet;
+use minijinja::value::{Kwargs, Rest, ValueKind};
+use minijinja::{Environment, Error, ErrorKind, Value};
+use rand::{rng, Rng};
+use regex::{Regex, RegexBuilder};
+use ring::digest;
+use std::net::IpAddr;
+
+const CRYPT_ALPHABET: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Registers the ansible compatible filters.
+pub(crate) fn register(env: &mut Environment<'static>) {
+    env.add_filter("default", default);
+    env.add_filter("d", default);
+    env.add_filter("mandatory", mandatory);
+    env.add_filter("ternary", ternary);
+    env.add_filter("bool", to_bool);
+    env.add_filter("combine", combine);
+    env.add_filter("dict2items", dict2items);
+    env.add_filter("items2dict", items2dict);
+    env.add_filter("to_json", to_json);
+    env.add_filter("to_nice_json", to_nice_json);
+    env.add_filter("from_json", from_json);
+    env.add_filter("to_yaml", to_yaml);
+    env.add_filter("to_nice_yaml", to_nice_yaml);
+    env.add_filter("from_yaml", from_yaml);
+    env.add_filter("regex_replace", regex_replace);
+    env.add_filter("regex_search", regex_search);
+    env.add_filter("regex_findall", regex_findall);
+    env.add_filter("b64encode", b64encode);
+    env.add_filter("b64decode", b64decode);
+    env.add_filter("hash", hash);
+    env.add_filter("checksum", checksum);
+    env.add_filter("password_hash", password_hash);
+    env.add_filter("ipaddr", ipaddr);
+    env.add_filter("ipv4", ipv4);
+    env.add_filter("ipv6", ipv6);
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidOperation, msg.into())
+}
+
+/// `default(default_value='', boolean=false)`, with `boolean` falsy values are replaced too.
+fn default(value: Value, default_value: Option<Value>, boolean: Option<bool>) -> Value {
+    if value.is_undefined() || (boolean.unwrap_or(false) && !value.is_true()) {
+        default_value.unwrap_or_else(|| Value::from(""))
+    } else {
+        value
+    }
+}
+
+fn mandatory(value: Value, msg: Option<String>) -> Result<Value, Error> {
+    if value.is_undefined() {
+        return Err(Error::new(
+            ErrorKind::UndefinedError,
+            msg.unwrap_or_else(|| "Mandatory variable has not been defined".to_string()),
+        ));
+    }
+
+    Ok(value)
+}
+
+fn ternary(value: Value, true_val: Value, false_val: Value, none_val: Option<Value>) -> Value {
+    match none_val {
+        Some(none_val) if value.is_none() => none_val,
+        _ if value.is_true() => true_val,
+        _ => false_val,
+    }
+}
+
+fn to_bool(value: Value) -> bool {
+    match value.as_str() {
+        Some(s) => matches!(
+            s.to_lowercase().as_str(),
+            "yes" | "on" | "1" | "true" | "y" | "t"
+        ),
+        None => value.is_true(),
+    }
+}
+
+/// Returns the entries of a map value, in insertion order.
+fn map_entries(value: &Value) -> Result<Vec<(Value, Value)>, Error> {
+    value
+        .try_iter()?
+        .map(|key| Ok((key.clone(), value.get_item(&key)?)))
+        .collect()
+}
+
+fn merge_dicts(a: Value, b: Value, recursive: bool, list_merge: &str) -> Result<Value, Error> {
+    if a.kind() != ValueKind::Map || b.kind() != ValueKind::Map {
+        return Ok(b);
+    }
+
+    // keys keep the position they were first defined at
+    let mut merged = map_entries(&a)?;
+    for (key, b_value) in map_entries(&b)? {
+        let Some(pos) = merged.iter().position(|(k, _)| *k == key) else {
+            merged.push((key, b_value));
+            continue;
+        };
+
+        let a_value = merged[pos].1.clone();
+        merged[pos].1 = match (a_value.kind(), b_value.kind()) {
+            (ValueKind::Map, ValueKind::Map) if recursive => {
+                merge_dicts(a_value, b_value, recursive, list_merge)?
+            }
+            (ValueKind::Seq, ValueKind::Seq) => merge_lists(
+                a_value.try_iter()?.collect(),
+                b_value.try_iter()?.collect(),
+                list_merge,
+            )?,
+            _ => b_value,
+        };
+    }
+
+    Ok(Value::from_iter(merged))
+}
+
+fn merge_lists(a: Vec<Value>, b: Vec<Value>, list_merge: &str) -> Result<Value, Error> {
+    let merged: Vec<Value> = match list_merge {
+        "replace" => b,
+        "keep" => a,
+        "append" => a.into_iter().chain(b).collect(),
+        "prepend" => b.into_iter().chain(a).collect(),
+        "append_rp" => a
+            .into_iter()
+            .filter(|item| !b.contains(item))
+            .chain(b.clone())
+            .collect(),
+        "prepend_rp" => {
+            let rest: Vec<_> = a.into_iter().filter(|item| !b.contains(item)).collect();
+            b.into_iter().chain(rest).collect()
+        }
+        _ => {
+            return Err(invalid(format!(
+                "combine: list_merge must be one of replace, keep, append, prepend, append_rp, prepend_rp, got '{}'",
+                list_merge
+            )))
+        }
+    };
+
+    Ok(Value::from(merged))
+}
+
+/// `combine(*dicts, recursive=false, list_merge='replace')`, later dictionaries win.
+fn combine(value: Value, others: Rest<Value>, kwargs: Kwargs) -> Result<Value, Error> {
+    let recursive = kwargs.get::<Option<bool>>("recursive")?.unwrap_or(false);
+    let list_merge = kwargs
+        .get::<Option<String>>("list_merge")?
+        .unwrap_or_else(|| "replace".to_string());
+    kwargs.assert_all_used()?;
+
+    let mut dicts = Vec::new();
+    for item in std::iter::once(value).chain(others.0) {
+        match item.kind() {
+            ValueKind::Seq => dicts.extend(item.try_iter()?),
+            _ => dicts.push(item),
+        }
+    }
+
+    let mut result = Value::from_iter(Vec::<(Value, Value)>::new());
+    for dict in dicts {
+        if dict.kind() != ValueKind::Map {
+            return Err(invalid(format!(
+                "combine expects dictionaries, got {}",
+                dict.kind()
+            )));
+        }
+        result = merge_dicts(result, dict, recursive, &list_merge)?;
+    }
+
+    Ok(result)
+}
+
+fn dict2items(value: Value, kwargs: Kwargs) -> Result<Value, Error> {
+    let key_name = kwargs
+        .get::<Option<String>>("key_name")?
+        .unwrap_or_else(|| "key".to_string());
+    let value_name = kwargs
+        .get::<Option<String>>("value_name")?
+        .unwrap_or_else(|| "value".to_string());
+    kwargs.assert_all_used()?;
+
+    if value.kind() != ValueKind::Map {
+        return Err(invalid(format!(
+            "dict2items requires a dictionary, got {}",
+            value.kind()
+        )));
+    }
+
+    let mut items = Vec::new();
+    for key in value.try_iter()? {
+        let item = value.get_item(&key)?;
+        items.push(Value::from_iter([
+            (key_name.clone(), key),
+            (value_name.clone(), item),
+        ]));
+    }
+
+    Ok(Value::from(items))
+}
+
+fn items2dict(value: Value, kwargs: Kwargs) -> Result<Value, Error> {
+    let key_name = kwargs
+        .get::<Option<String>>("key_name")?
+        .unwrap_or_else(|| "key".to_string());
+    let value_name = kwargs
+        .get::<Option<String>>("value_name")?
+        .unwrap_or_else(|| "value".to_string());
+    kwargs.assert_all_used()?;
+
+    let mut dict = Vec::new();
+    for item in value.try_iter()? {
+        let key = item.get_attr(&key_name)?;
+        let value = item.get_attr(&value_name)?;
+
+        if key.is_undefined() || value.is_undefined() {
+            return Err(invalid(format!(
+                "items2dict requires each item to have '{}' and '{}' keys",
+                key_name, value_name
+            )));
+        }
+        dict.push((key, value));
+    }
+
+    Ok(Value::from_iter(dict))
+}
+
+/// Writes JSON with the separators python's `json.dumps` uses by default.
+struct PythonFormatter;
+
+impl serde_json::ser::Formatter for PythonFormatter {
+    fn begin_array_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b", ")
+        }
+    }
+
+    fn begin_object_key<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> std::io::Result<()> {
+        if first {
+            Ok(())
+        } else {
+            writer.write_all(b", ")
+        }
+    }
+
+    fn begin_object_value<W: ?Sized + std::io::Write>(
+        &mut self,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_all(b": ")
+    }
+}
+
+fn write_json<F: serde_json::ser::Formatter>(value: &Value, formatter: F) -> Result<String, Error> {
+    let mut output = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    serde::Serialize::serialize(value, &mut serializer)
+        .map_err(|e| invalid(format!("unable to convert to JSON: {}", e)))?;
+
+    String::from_utf8(output).map_err(|e| invalid(e.to_string()))
+}
+
+fn to_json(value: Value) -> Result<String, Error> {
+    write_json(&value, PythonFormatter)
+}
+
+/// `to_nice_json(indent=4, sort_keys=true)`
+fn to_nice_json(value: Value, indent: Option<usize>, kwargs: Kwargs) -> Result<String, Error> {
+    let indent = match indent {
+        Some(indent) => indent,
+        None => kwargs.get::<Option<usize>>("indent")?.unwrap_or(4),
+    };
+    let sort_keys = kwargs.get::<Option<bool>>("sort_keys")?.unwrap_or(true);
+    kwargs.assert_all_used()?;
+
+    let value = if sort_keys {
+        sort_keys_of(&value)?
+    } else {
+        value
+    };
+    let indent = " ".repeat(indent);
+    write_json(
+        &value,
+        serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes()),
+    )
+}
+
+/// Returns the value with the keys of every map sorted.
+fn sort_keys_of(value: &Value) -> Result<Value, Error> {
+    match value.kind() {
+        ValueKind::Map => {
+            let mut entries = map_entries(value)?
+                .into_iter()
+                .map(|(key, item)| Ok((key, sort_keys_of(&item)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            entries.sort_by_key(|(key, _)| key.to_string());
+            Ok(Value::from_iter(entries))
+        }
+        ValueKind::Seq => Ok(Value::from(
+            value
+                .try_iter()?
+                .map(|item| sort_keys_of(&item))
+                .collect::<Result<Vec<_>, Error>>()?,
+        )),
+        _ => Ok(value.clone()),
+    }
+}
+
+fn from_json(value: &str) -> Result<Value, Error> {
+    let json: serde_json::Value = serde_json::from_str(value)
+        .map_err(|e| invalid(format!("from_json: unable to parse JSON: {}", e)))?;
+    Ok(Value::from_serialize(json))
+}
+
+fn to_yaml(value: Value) -> Result<String, Error> {
+    serde_yaml::to_string(&value).map_err(|e| invalid(format!("unable to convert to YAML: {}", e)))
+}
+
+/// `to_nice_yaml(indent=4, sort_keys=true)`, block style YAML laid out like PyYAML does.
+fn to_nice_yaml(value: Value, kwargs: Kwargs) -> Result<String, Error> {
+    let indent = kwargs.get::<Option<usize>>("indent")?.unwrap_or(4);
+    let sort_keys = kwargs.get::<Option<bool>>("sort_keys")?.unwrap_or(true);
+    kwargs.assert_all_used()?;
+
+    if !(2..10).contains(&indent) {
+        return Err(invalid(format!(
+            "to_nice_yaml: indent must be between 2 and 9, got {}",
+            indent
+        )));
+    }
+
+    let lines = NiceYaml { indent, sort_keys }.lines(&value)?;
+    Ok(lines.join("\n") + "\n")
+}
+
+struct NiceYaml {
+    indent: usize,
+    sort_keys: bool,
+}
+
+impl NiceYaml {
+    /// Returns the lines of a block node, relative to the column it starts at.
+    fn lines(&self, value: &Value) -> Result<Vec<String>, Error> {
+        if is_empty_collection(value) {
+            return Ok(vec![yaml_scalar(value)?]);
+        }
+
+        let mut lines = Vec::new();
+        match value.kind() {
+            ValueKind::Map => {
+                let mut entries = map_entries(value)?;
+                if self.sort_keys {
+                    entries.sort_by_key(|(key, _)| key.to_string());
+                }
+
+                for (key, item) in entries {
+                    let key = yaml_scalar(&key)?;
+                    match item.kind() {
+                        ValueKind::Map | ValueKind::Seq if !is_empty_collection(&item) => {
+                            lines.push(format!("{}:", key));
+                            // sequences in a mapping are not indented
+                            let indent = match item.kind() {
+                                ValueKind::Map => " ".repeat(self.indent),
+                                _ => String::new(),
+                            };
+                            for line in self.lines(&item)? {
+                                lines.push(format!("{}{}", indent, line));
+                            }
+                        }
+                        _ => lines.push(format!("{}: {}", key, yaml_scalar(&item)?)),
+                    }
+                }
+            }
+            ValueKind::Seq => {
+                let marker = format!("{:<width$}", "-", width = self.indent);
+                let indent = " ".repeat(self.indent);
+
+                for item in value.try_iter()? {
+                    for (i, line) in self.lines(&item)?.into_iter().enumerate() {
+                        let prefix = if i == 0 { &marker } else { &indent };
+                        lines.push(format!("{}{}", prefix, line));
+                    }
+                }
+            }
+            _ => lines.push(yaml_scalar(value)?),
+        }
+
+        Ok(lines)
+    }
+}
+
+fn is_empty_collection(value: &Value) -> bool {
+    matches!(value.kind(), ValueKind::Map | ValueKind::Seq) && value.len() == Some(0)
+}
+
+fn yaml_scalar(value: &Value) -> Result<String, Error> {
+    match value.kind() {
+        ValueKind::Map => return Ok("{}".to_string()),
+        ValueKind::Seq => return Ok("[]".to_string()),
+        _ => {}
+    }
+
+    let scalar = match value.as_str() {
+        // a double quoted string keeps the line breaks on a single line
+        Some(s) if s.contains('\n') => serde_json::to_string(s).map_err(|e| invalid(e.to_string())),
+        _ => serde_yaml::to_string(value).map_err(|e| invalid(e.to_string())),
+    };
+
+    scalar
+        .map(|scalar| scalar.trim_end_matches('\n').to_string())
+        .map_err(|e| invalid(format!("unable to convert to YAML: {}", e)))
+}
+
+fn from_yaml(value: &str) -> Result<Value, Error> {
+    let yaml: serde_yaml::Value = serde_yaml::from_str(value)
+        .map_err(|e| invalid(format!("from_yaml: unable to parse YAML: {}", e)))?;
+    Ok(Value::from_serialize(yaml))
+}
+
+fn build_regex(pattern: &str, kwargs: &Kwargs) -> Result<Regex, Error> {
+    let ignorecase = kwargs.get::<Option<bool>>("ignorecase")?.unwrap_or(false);
+    let multiline = kwargs.get::<Option<bool>>("multiline")?.unwrap_or(false);
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignorecase)
+        .multi_line(multiline)
+        .build()
+        .map_err(|e| invalid(format!("invalid regular expression '{}': {}", pattern, e)))
+}
+
+/// Converts python style back references (`\1`, `\g<name>`) to the regex crate syntax.
+fn convert_replacement(replacement: &str) -> String {
+    static BACKREF: once_cell::sync::Lazy<Regex> =
+        once_cell::sync::Lazy::new(|| Regex::new(r"\\(?:g<(\w+)>|(\d+))").unwrap());
+
+    let escaped = replacement.replace('$', "$$");
+    BACKREF
+        .replace_all(&escaped, |caps: &regex::Captures| {
+            let group = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            format!("${{{}}}", group)
+        })
+        .into_owned()
+}
+
+fn regex_replace(
+    value: &str,
+    pattern: &str,
+    replacement: Option<&str>,
+    kwargs: Kwargs,
+) -> Result<String, Error> {
+    let count = kwargs.get::<Option<usize>>("count")?.unwrap_or(0);
+    let re = build_regex(pattern, &kwargs)?;
+    kwargs.assert_all_used()?;
+
+    let replacement = convert_replacement(replacement.unwrap_or(""));
+    Ok(re.replacen(value, count, replacement.as_str()).into_owned())
+}
+
+/// `regex_search(regex, *backrefs)`, returns the match, the requested groups or none.
+fn regex_search(
+    value: &str,
+    pattern: &str,
+    backrefs: Rest<String>,
+    kwargs: Kwargs,
+) -> Result<Value, Error> {
+    let re = build_regex(pattern, &kwargs)?;
+    kwargs.assert_all_used()?;
+
+    let Some(caps) = re.captures(value) else {
+        return Ok(Value::from(()));
+    };
+
+    if backrefs.is_empty() {
+        return Ok(Value::from(caps.get(0).map_or("", |m| m.as_str())));
+    }
+
+    let mut groups = Vec::new();
+    for backref in backrefs.iter() {
+        let group = if let Some(name) = backref
+            .strip_prefix("\\g<")
+            .and_then(|b| b.strip_suffix('>'))
+        {
+            caps.name(name)
+        } else if let Some(index) = backref.strip_prefix('\\') {
+            let index = index
+                .parse::<usize>()
+                .map_err(|_| invalid(format!("invalid back reference '{}'", backref)))?;
+            caps.get(index)
+        } else {
+            return Err(invalid(format!("unknown argument '{}'", backref)));
+        };
+        groups.push(Value::from(group.map(|m| m.as_str())));
+    }
+
+    Ok(Value::from(groups))
+}
+
+fn regex_findall(value: &str, pattern: &str, kwargs: Kwargs) -> Result<Value, Error> {
+    let re = build_regex(pattern, &kwargs)?;
+    kwargs.assert_all_used()?;
+
+    let matches: Vec<Value> = re
+        .captures_iter(value)
+        .map(|caps| match caps.len() {
+            1 => Value::from(caps.get(0).map_or("", |m| m.as_str())),
+            2 => Value::from(caps.get(1).map_or("", |m| m.as_str())),
+            _ => Value::from(
+                caps.iter()
+                    .skip(1)
+                    .map(|m| Value::from(m.map_or("", |m| m.as_str())))
+                    .collect::<Vec<_>>(),
+            ),
+        })
+        .collect();
+
+    Ok(Value::from(matches))
+}
+
+fn b64encode(value: &str) -> String {
+    STANDARD.encode(value)
+}
+
+fn b64decode(value: &str) -> Result<String, Error> {
+    let decoded = STANDARD
+        .decode(value.trim())
+        .map_err(|e| invalid(format!("b64decode: {}", e)))?;
+    String::from_utf8(decoded).map_err(|e| invalid(format!("b64decode: {}", e)))
+}
+
+fn get_digest_algorithm(hashtype: &str) -> Result<&'static digest::Algorithm, Error> {
+    match hashtype {
+        "sha1" => Ok(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+        "sha256" => Ok(&digest::SHA256),
+        "sha384" => Ok(&digest::SHA384),
+        "sha512" => Ok(&digest::SHA512),
+        _ => Err(invalid(format!("unsupported hash type '{}'", hashtype))),
+    }
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+/// MD5 as specified by RFC 1321, ring has no legacy MD5 support.
+fn md5(data: &[u8]) -> [u8; 16] {
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(MD5_SHIFTS[i]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn hash(value: &str, hashtype: Option<&str>) -> Result<String, Error> {
+    let hashtype = hashtype.unwrap_or("sha1");
+    let digest = match hashtype {
+        "md5" => md5(value.as_bytes()).to_vec(),
+        _ => digest::digest(get_digest_algorithm(hashtype)?, value.as_bytes())
+            .as_ref()
+            .to_vec(),
+    };
+
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn checksum(value: &str) -> Result<String, Error> {
+    hash(value, Some("sha1"))
+}
+
+/// Appends `n` crypt base64 characters for the given 24 bits.
+fn crypt_b64_from_24bit(b2: u8, b1: u8, b0: u8, n: usize, output: &mut String) {
+    let mut w = ((b2 as u32) << 16) | ((b1 as u32) << 8) | (b0 as u32);
+
+    for _ in 0..n {
+        output.push(CRYPT_ALPHABET[(w & 0x3f) as usize] as char);
+        w >>= 6;
+    }
+}
+
+fn repeat_digest(bytes: &[u8], len: usize) -> Vec<u8> {
+    bytes.iter().cycle().take(len).copied().collect()
+}
+
+/// SHA-crypt (`$5$`/`$6$`) as used by glibc `crypt(3)` and ansible's `password_hash`.
+pub(crate) fn sha_crypt(
+    password: &str,
+    salt: &str,
+    rounds: Option<u32>,
+    sha512: bool,
+) -> Result<String, Error> {
+    let algorithm = if sha512 {
+        &digest::SHA512
+    } else {
+        &digest::SHA256
+    };
+    let password = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(16)];
+    let rounds_count = rounds.unwrap_or(5000).clamp(1000, 999_999_999);
+
+    let mut ctx = digest::Context::new(algorithm);
+    ctx.update(password);
+    ctx.update(salt);
+    ctx.update(password);
+    let alternate = ctx.finish();
+
+    let mut ctx = digest::Context::new(algorithm);
+    ctx.update(password);
+    ctx.update(salt);
+    ctx.update(&repeat_digest(alternate.as_ref(), password.len()));
+
+    let mut length = password.len();
+    while length > 0 {
+        if length & 1 != 0 {
+            ctx.update(alternate.as_ref());
+        } else {
+            ctx.update(password);
+        }
+        length >>= 1;
+    }
+    let mut result = ctx.finish().as_ref().to_vec();
+
+    let mut ctx = digest::Context::new(algorithm);
+    for _ in 0..password.len() {
+        ctx.update(password);
+    }
+    let p_bytes = repeat_digest(ctx.finish().as_ref(), password.len());
+
+    let mut ctx = digest::Context::new(algorithm);
+    for _ in 0..(16 + result[0] as usize) {
+        ctx.update(salt);
+    }
+    let s_bytes = repeat_digest(ctx.finish().as_ref(), salt.len());
+
+    for i in 0..rounds_count {
+        let mut ctx = digest::Context::new(algorithm);
+        if i % 2 != 0 {
+            ctx.update(&p_bytes);
+        } else {
+            ctx.update(&result);
+        }
+        if i % 3 != 0 {
+            ctx.update(&s_bytes);
+        }
+        if i % 7 != 0 {
+            ctx.update(&p_bytes);
+        }
+        if i % 2 != 0 {
+            ctx.update(&result);
+        } else {
+            ctx.update(&p_bytes);
+        }
+        result = ctx.finish().as_ref().to_vec();
+    }
+
+    let mut output = String::from(if sha512 { "$6$" } else { "$5$" });
+    if rounds.is_some() {
+        output.push_str(&format!("rounds={}$", rounds_count));
+    }
+    output.push_str(&String::from_utf8_lossy(salt));
+    output.push('$');
+
+    let r = &result;
+    if sha512 {
+        for i in 0..21 {
+            let (a, b, c) = (i, i + 21, i + 42);
+            let order = match i % 3 {
+                0 => (r[a], r[b], r[c]),
+                1 => (r[b], r[c], r[a]),
+                _ => (r[c], r[a], r[b]),
+            };
+            crypt_b64_from_24bit(order.0, order.1, order.2, 4, &mut output);
+        }
+        crypt_b64_from_24bit(0, 0, r[63], 2, &mut output);
+    } else {
+        for i in 0..10 {
+            let (a, b, c) = (i, i + 10, i + 20);
+            let order = match i % 3 {
+                0 => (r[a], r[b], r[c]),
+                1 => (r[c], r[a], r[b]),
+                _ => (r[b], r[c], r[a]),
+            };
+            crypt_b64_from_24bit(order.0, order.1, order.2, 4, &mut output);
+        }
+        crypt_b64_from_24bit(0, r[31], r[30], 3, &mut output);
+    }
+
+    Ok(output)
+}
+
+pub(crate) fn random_salt(len: usize) -> String {
+    let mut rng = rng();
+    (0..len)
+        .map(|_| CRYPT_ALPHABET[rng.random_range(0..CRYPT_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// `password_hash(hashtype='sha512', salt=None, rounds=None)`
+fn password_hash(
+    value: &str,
+    hashtype: Option<&str>,
+    salt: Option<String>,
+    rounds: Option<u32>,
+) -> Result<String, Error> {
+    let salt = salt.unwrap_or_else(|| random_salt(16));
+
+    match hashtype.unwrap_or("sha512") {
+        "sha512" | "sha512_crypt" => sha_crypt(value, &salt, rounds, true),
+        "sha256" | "sha256_crypt" => sha_crypt(value, &salt, rounds, false),
+        other => Err(invalid(format!(
+            "password_hash: unsupported hash type '{}'",
+            other
+        ))),
+    }
+}
+
+fn parse_ip(value: &str) -> Option<IpNet> {
+    value
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| value.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+fn query_ip(value: &str, query: &str, version: Option<u8>) -> Result<Option<Value>, Error> {
+    let Some(net) = parse_ip(value) else {
+        return Ok(None);
+    };
+
+    let matches_version = match version {
+        Some(4) => matches!(net, IpNet::V4(_)),
+        Some(6) => matches!(net, IpNet::V6(_)),
+        _ => true,
+    };
+    if !matches_version {
+        return Ok(None);
+    }
+
+    let result = match query {
+        "" => Value::from(value),
+        "address" => Value::from(net.addr().to_string()),
+        "network" => Value::from(net.network().to_string()),
+        "netmask" => Value::from(net.netmask().to_string()),
+        "broadcast" => Value::from(net.broadcast().to_string()),
+        "prefix" => Value::from(net.prefix_len()),
+        "host" => Value::from(format!("{}/{}", net.addr(), net.max_prefix_len())),
+        "net" => match net.addr() == net.network() && net.prefix_len() < net.max_prefix_len() {
+            true => Value::from(net.to_string()),
+            false => return Ok(None),
+        },
+        "private" => match net.addr() {
+            IpAddr::V4(addr) if addr.is_private() => Value::from(value),
+            IpAddr::V6(addr) if (addr.segments()[0] & 0xfe00) == 0xfc00 => Value::from(value),
+            _ => return Ok(None),
+        },
+        "public" => match net.addr() {
+            IpAddr::V4(addr)
+                if !(addr.is_private()
+                    || addr.is_loopback()
+                    || addr.is_link_local()
+                    || addr.is_unspecified()) =>
+            {
+                Value::from(value)
+            }
+            IpAddr::V6(addr)
+                if !(addr.is_loopback()
+                    || addr.is_unspecified()
+                    || (addr.segments()[0] & 0xfe00) == 0xfc00) =>
+            {
+                Value::from(value)
+            }
+            _ => return Ok(None),
+        },
+        _ => return Err(invalid(format!("ipaddr: unknown query '{}'", query))),
+    };
+
+    Ok(Some(result))
+}
+
+/// ipaddr style filtering: strings yield the query result or `false`,
+/// lists keep only the valid entries.
+fn ip_filter(value: Value, query: Option<&str>, version: Option<u8>) -> Result<Value, Error> {
+    let query = query.unwrap_or("");
+
+    if value.kind() == ValueKind::Seq {
+        let mut results = Vec::new();
+        for item in value.try_iter()? {
+            if let Some(result) = item
+                .as_str()
+                .map(|s| query_ip(s, query, version))
+                .transpose()?
+                .flatten()
+            {
+                results.push(result);
+            }
+        }
+        return Ok(Value::from(results));
+    }
+
+    let result = match value.as_str() {
+        Some(s) => query_ip(s, query, version)?,
+        None => None,
+    };
+
+    Ok(result.unwrap_or(Value::from(false)))
+}
+
+fn ipaddr(value: Value, query: Option<&str>) -> Result<Value, Error> {
+    ip_filter(value, query, None)
+}
+
+fn ipv4(value: Value, query: Option<&str>) -> Result<Value, Error> {
+    ip_filter(value, query, Some(4))
+}
+
+fn ipv6(value: Value, query: Option<&str>) -> Result<Value, Error> {
+    ip_filter(value, query, Some(6))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, ctx: Value) -> String {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+        register(&mut env);
+        env.render_str(template, ctx).unwrap()
+    }
+
+    #[test]
+    fn test_default_filter() {
+        let ctx = Value::from_serialize(serde_json::json!({"empty": "", "name": "web"}));
+
+        assert_eq!(render("{{ missing | default('x') }}", ctx.clone()), "x");
+        assert_eq!(render("{{ name | d('x') }}", ctx.clone()), "web");
+        assert_eq!(render("{{ empty | default('x') }}", ctx.clone()), "");
+        assert_eq!(render("{{ empty | default('x', true) }}", ctx), "x");
+    }
+
+    #[test]
+    fn test_mandatory_and_ternary() {
+        let mut env = Environment::new();
+        register(&mut env);
+
+        let result = env.render_str("{{ missing | mandatory('missing is required') }}", ());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("missing is required"));
+
+        assert_eq!(
+            render("{{ (1 > 0) | ternary('yes', 'no') }}", Value::from(())),
+            "yes"
+        );
+        assert_eq!(
+            render("{{ none | ternary('yes', 'no', 'null') }}", Value::from(())),
+            "null"
+        );
+    }
+
+    #[test]
+    fn test_combine() {
+        let ctx = Value::from_serialize(serde_json::json!({
+            "a": {"x": 1, "nested": {"k": 1}, "list": [1, 2]},
+            "b": {"y": 2, "nested": {"j": 2}, "list": [2, 3]},
+        }));
+
+        assert_eq!(
+            render("{{ a | combine(b) | to_json }}", ctx.clone()),
+            r#"{"list": [2, 3], "nested": {"j": 2}, "x": 1, "y": 2}"#
+        );
+        assert_eq!(
+            render(
+                "{{ a | combine(b, recursive=true, list_merge='append_rp') | to_json }}",
+                ctx
+            ),
+            r#"{"list": [1, 2, 3], "nested": {"k": 1, "j": 2}, "x": 1, "y": 2}"#
+        );
+    }
+
+    #[test]
+    fn test_combine_and_to_json_keep_key_order() {
+        assert_eq!(
+            render(
+                "{{ {'z': 1, 'a': {'y': 1} } | combine({'m': 2, 'z': 3, 'a': {'b': 1} }, recursive=true) | to_json }}",
+                Value::from(())
+            ),
+            r#"{"z": 3, "a": {"y": 1, "b": 1}, "m": 2}"#
+        );
+        assert_eq!(
+            render(
+                "{{ {'b': 1, 'a': [2]} | to_nice_json(indent=2, sort_keys=false) }}",
+                Value::from(())
+            ),
+            "{\n  \"b\": 1,\n  \"a\": [\n    2\n  ]\n}"
+        );
+        assert_eq!(
+            render("{{ {'b': 1, 'a': 2} | to_nice_json(2) }}", Value::from(())),
+            "{\n  \"a\": 2,\n  \"b\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn test_dict2items_and_items2dict() {
+        let ctx = Value::from_serialize(serde_json::json!({"tags": {"env": "prod", "app": "web"}}));
+
+        assert_eq!(
+            render(
+                "{{ tags | dict2items(key_name='k', value_name='v') | to_json }}",
+                ctx.clone()
+            ),
+            r#"[{"k": "app", "v": "web"}, {"k": "env", "v": "prod"}]"#
+        );
+        assert_eq!(
+            render("{{ tags | dict2items | items2dict | to_json }}", ctx),
+            r#"{"app": "web", "env": "prod"}"#
+        );
+    }
+
+    #[test]
+    fn test_json_and_yaml() {
+        let ctx = Value::from_serialize(serde_json::json!({"data": {"a": [1, "x"]}}));
+
+        assert_eq!(
+            render("{{ data | to_nice_json }}", ctx.clone()),
+            "{\n    \"a\": [\n        1,\n        \"x\"\n    ]\n}"
+        );
+        assert_eq!(
+            render("{{ data | to_yaml }}", ctx.clone()),
+            "a:\n- 1\n- x\n"
+        );
+        assert_eq!(
+            render("{{ ('{\"b\": 2}' | from_json).b }}", ctx.clone()),
+            "2"
+        );
+        assert_eq!(render("{{ ('b: [3]' | from_yaml).b[0] }}", ctx), "3");
+    }
+
+    #[test]
+    fn test_to_nice_yaml() {
+        let ctx = Value::from_serialize(serde_json::json!({
+            "web": {"port": 80, "tags": ["a", {"b": 2, "c": [3]}], "empty": {}},
+            "app": "line1\nline2",
+        }));
+
+        assert_eq!(
+            render("{{ web | to_nice_yaml }}", ctx.clone()),
+            "empty: {}\nport: 80\ntags:\n-   a\n-   b: 2\n    c:\n    -   3\n"
+        );
+        assert_eq!(
+            render("{{ {'web': web} | to_nice_yaml(indent=2) }}", ctx.clone()),
+            "web:\n  empty: {}\n  port: 80\n  tags:\n  - a\n  - b: 2\n    c:\n    - 3\n"
+        );
+        assert_eq!(
+            render(
+                "{{ {'z': 1, 'a': app} | to_nice_yaml(sort_keys=false) }}",
+                ctx.clone()
+            ),
+            "z: 1\na: \"line1\\nline2\"\n"
+        );
+
+        let mut env = Environment::new();
+        register(&mut env);
+        for template in [
+            "{{ {} | to_nice_yaml(indent=1) }}",
+            "{{ {} | to_nice_yaml(width=80) }}",
+        ] {
+            assert!(env.render_str(template, ()).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn test_regex_filters() {
+        let ctx = Value::from_serialize(serde_json::json!({"host": "web-01.example.com"}));
+
+        assert_eq!(
+            render(
+                r"{{ host | regex_replace('^(\\w+)-(\\d+)', '\\2-\\1') }}",
+                ctx.clone()
+            ),
+            "01-web.example.com"
+        );
+        assert_eq!(
+            render(
+                r"{{ host | regex_replace('(?P<n>\\d+)', 'n\\g<n>$') }}",
+                ctx.clone()
+            ),
+            "web-n01$.example.com"
+        );
+        assert_eq!(
+            render(r"{{ host | regex_search('\\d+') }}", ctx.clone()),
+            "01"
+        );
+        assert_eq!(
+            render(
+                r"{{ host | regex_search('(\\w+)-(\\d+)', '\\2') }}",
+                ctx.clone()
+            ),
+            r#"["01"]"#
+        );
+        assert_eq!(
+            render(
+                "{{ host | regex_search('WEB', ignorecase=true) }}",
+                ctx.clone()
+            ),
+            "web"
+        );
+        assert_eq!(
+            render(r"{{ host | regex_findall('\\w+') | length }}", ctx),
+            "4"
+        );
+    }
+
+    #[test]
+    fn test_encoding_and_hash_filters() {
+        let ctx = Value::from(());
+
+        assert_eq!(render("{{ 'cogrs' | b64encode }}", ctx.clone()), "Y29ncnM=");
+        assert_eq!(render("{{ 'Y29ncnM=' | b64decode }}", ctx.clone()), "cogrs");
+        assert_eq!(
+            render("{{ 'test' | hash('sha1') }}", ctx.clone()),
+            "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3"
+        );
+        assert_eq!(
+            render("{{ 'test' | hash('md5') }}", ctx.clone()),
+            "098f6bcd4621d373cade4e832627b4f6"
+        );
+        assert_eq!(
+            render("{{ '' | hash('md5') }}", ctx.clone()),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+        assert_eq!(
+            render("{{ ('a' * 100) | hash('md5') }}", ctx.clone()),
+            "36a92cc94a9e0fa21f625f8bfb007adf"
+        );
+        assert_eq!(
+            render("{{ 'Hello world!' | password_hash('sha512', 'saltstring') }}", ctx.clone()),
+            "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O817G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1"
+        );
+        assert_eq!(
+            render(
+                "{{ 'Hello world!' | password_hash('sha256', 'saltstring') }}",
+                ctx
+            ),
+            "$5$saltstring$5B8vYYiY.CVt1RlTTf8KbXBH3hsxY/GNooZaBBGWEc5"
+        );
+    }
+
+    #[test]
+    fn test_ipaddr_filters() {
+        let ctx = Value::from_serialize(serde_json::json!({
+            "ips": ["10.0.0.1", "fe80::1", "not-an-ip", "192.168.1.10/24"]
+        }));
+
+        assert_eq!(
+            render("{{ '192.168.1.10/24' | ipaddr('network') }}", ctx.clone()),
+            "192.168.1.0"
+        );
+        assert_eq!(
+            render("{{ '192.168.1.10/24' | ipaddr('netmask') }}", ctx.clone()),
+            "255.255.255.0"
+        );
+        assert_eq!(render("{{ 'foo' | ipaddr }}", ctx.clone()), "false");
+        assert_eq!(
+            render("{{ ips | ipv4 | to_json }}", ctx.clone()),
+            r#"["10.0.0.1", "192.168.1.10/24"]"#
+        );
+        assert_eq!(render("{{ ips | ipv6 | to_json }}", ctx), r#"["fe80::1"]"#);
+    }
+}
@@ -0,0 +1,232 @@
+use minijinja::value::{Kwargs, ValueKind};
+use minijinja::{Environment, Error, ErrorKind, Value};
+use regex::RegexBuilder;
+use std::cmp::Ordering;
+
+/// Registers the ansible compatible jinja tests (`is failed`, `is version`, ...).
+pub(crate) fn register(env: &mut Environment<'static>) {
+    env.add_test("failed", failed);
+    env.add_test("failure", failed);
+    env.add_test("succeeded", succeeded);
+    env.add_test("success", succeeded);
+    env.add_test("successful", succeeded);
+    env.add_test("changed", changed);
+    env.add_test("change", changed);
+    env.add_test("skipped", skipped);
+    env.add_test("skip", skipped);
+    env.add_test("version", version);
+    env.add_test("version_compare", version);
+    env.add_test("match", regex_match);
+    env.add_test("search", regex_search);
+    env.add_test("regex", regex_search);
+}
+
+fn result_flag(result: &Value, test: &str, flag: &str) -> Result<bool, Error> {
+    if result.kind() != ValueKind::Map {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("The '{}' test expects a dictionary", test),
+        ));
+    }
+
+    Ok(result.get_attr(flag)?.is_true())
+}
+
+fn failed(result: Value) -> Result<bool, Error> {
+    result_flag(&result, "failed", "failed")
+}
+
+fn succeeded(result: Value) -> Result<bool, Error> {
+    Ok(!result_flag(&result, "succeeded", "failed")?)
+}
+
+fn changed(result: Value) -> Result<bool, Error> {
+    result_flag(&result, "changed", "changed")
+}
+
+fn skipped(result: Value) -> Result<bool, Error> {
+    result_flag(&result, "skipped", "skipped")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VersionPart {
+    Number(u64),
+    Text(String),
+}
+
+impl PartialOrd for VersionPart {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for VersionPart {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (VersionPart::Number(a), VersionPart::Number(b)) => a.cmp(b),
+            (VersionPart::Text(a), VersionPart::Text(b)) => a.cmp(b),
+            // like LooseVersion, numbers sort after pre-release words (1.0a < 1.0.1)
+            (VersionPart::Number(_), VersionPart::Text(_)) => Ordering::Greater,
+            (VersionPart::Text(_), VersionPart::Number(_)) => Ordering::Less,
+        }
+    }
+}
+
+/// Splits a version into numeric and alphabetic parts, the way python's `LooseVersion` does.
+fn parse_loose_version(version: &str) -> Vec<VersionPart> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+
+    let flush = |current: &mut String, parts: &mut Vec<VersionPart>| {
+        if current.is_empty() {
+            return;
+        }
+        let part = match current.parse::<u64>() {
+            Ok(number) => VersionPart::Number(number),
+            Err(_) => VersionPart::Text(current.clone()),
+        };
+        parts.push(part);
+        current.clear();
+    };
+
+    for c in version.chars() {
+        if !c.is_alphanumeric() {
+            flush(&mut current, &mut parts);
+        } else if current
+            .chars()
+            .last()
+            .is_some_and(|last| last.is_ascii_digit() != c.is_ascii_digit())
+        {
+            flush(&mut current, &mut parts);
+            current.push(c);
+        } else {
+            current.push(c);
+        }
+    }
+    flush(&mut current, &mut parts);
+
+    parts
+}
+
+/// `version(other, operator='eq')`, compares versions loosely.
+fn version(
+    value: &str,
+    other: &str,
+    operator: Option<&str>,
+    kwargs: Kwargs,
+) -> Result<bool, Error> {
+    let operator = match operator {
+        Some(operator) => operator.to_string(),
+        None => kwargs
+            .get::<Option<String>>("operator")?
+            .unwrap_or_else(|| "eq".to_string()),
+    };
+    kwargs.assert_all_used()?;
+
+    let ordering = parse_loose_version(value).cmp(&parse_loose_version(other));
+
+    let result = match operator.as_str() {
+        "==" | "=" | "eq" => ordering == Ordering::Equal,
+        "!=" | "<>" | "ne" => ordering != Ordering::Equal,
+        "<" | "lt" => ordering == Ordering::Less,
+        "<=" | "le" => ordering != Ordering::Greater,
+        ">" | "gt" => ordering == Ordering::Greater,
+        ">=" | "ge" => ordering != Ordering::Less,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("Invalid operator type ({})", operator),
+            ))
+        }
+    };
+
+    Ok(result)
+}
+
+fn regex_test(value: &str, pattern: &str, kwargs: Kwargs, anchored: bool) -> Result<bool, Error> {
+    let ignorecase = kwargs.get::<Option<bool>>("ignorecase")?.unwrap_or(false);
+    let multiline = kwargs.get::<Option<bool>>("multiline")?.unwrap_or(false);
+    kwargs.assert_all_used()?;
+
+    let pattern = if anchored {
+        format!(r"\A(?:{})", pattern)
+    } else {
+        pattern.to_string()
+    };
+
+    let re = RegexBuilder::new(&pattern)
+        .case_insensitive(ignorecase)
+        .multi_line(multiline)
+        .build()
+        .map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("invalid regular expression '{}': {}", pattern, e),
+            )
+        })?;
+
+    Ok(re.is_match(value))
+}
+
+/// `match` only matches at the beginning of the string, like python's `re.match`.
+fn regex_match(value: &str, pattern: &str, kwargs: Kwargs) -> Result<bool, Error> {
+    regex_test(value, pattern, kwargs, true)
+}
+
+fn regex_search(value: &str, pattern: &str, kwargs: Kwargs) -> Result<bool, Error> {
+    regex_test(value, pattern, kwargs, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str) -> String {
+        let mut env = Environment::new();
+        register(&mut env);
+        let ctx = Value::from_serialize(serde_json::json!({
+            "ok": {"changed": true, "failed": false},
+            "bad": {"failed": true},
+            "skip": {"skipped": true},
+        }));
+        env.render_str(template, ctx).unwrap()
+    }
+
+    #[test]
+    fn test_result_tests() {
+        assert_eq!(
+            render("{{ ok is changed }} {{ ok is failed }}"),
+            "true false"
+        );
+        assert_eq!(
+            render("{{ bad is failed }} {{ bad is succeeded }}"),
+            "true false"
+        );
+        assert_eq!(
+            render("{{ skip is skipped }} {{ ok is skipped }}"),
+            "true false"
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(render("{{ '2.10.1' is version('2.9', '>=') }}"), "true");
+        assert_eq!(
+            render("{{ '2.10.1' is version('2.9', operator='lt') }}"),
+            "false"
+        );
+        assert_eq!(render("{{ '1.0a' is version('1.0.1', '<') }}"), "true");
+        assert_eq!(render("{{ '1.2.0' is version('1.2.0') }}"), "true");
+    }
+
+    #[test]
+    fn test_regex_tests() {
+        assert_eq!(render("{{ 'web-01' is match('web') }}"), "true");
+        assert_eq!(render("{{ 'web-01' is match('01') }}"), "false");
+        assert_eq!(render("{{ 'web-01' is search('01') }}"), "true");
+        assert_eq!(
+            render("{{ 'WEB' is search('web', ignorecase=true) }}"),
+            "true"
+        );
+    }
+}
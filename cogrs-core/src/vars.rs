@@ -1,2 +1,4 @@
+pub mod fact_cache;
+pub mod hostvars;
 pub mod manager;
 pub mod variable;
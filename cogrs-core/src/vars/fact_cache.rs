@@ -0,0 +1,132 @@
+use crate::cache::{get_builtin_cache_plugin, get_cache_plugin};
+use crate::config::manager::ConfigManager;
+use crate::vars::variable::Variable;
+use anyhow::{bail, Result};
+use cogrs_plugins::cache::{CachePlugin, CacheSettings};
+use indexmap::IndexMap;
+use std::path::PathBuf;
+
+const DEFAULT_CACHE_PLUGIN: &str = "memory";
+const DEFAULT_CACHE_PREFIX: &str = "cogrs_facts";
+const DEFAULT_CACHE_TIMEOUT: u64 = 86400;
+
+/// Facts of each host, stored in the configured cache backend (`CACHE_PLUGIN`), so they can
+/// outlive the run which gathered them.
+pub struct FactCache {
+    plugin: Box<dyn CachePlugin>,
+}
+
+impl Default for FactCache {
+    fn default() -> Self {
+        let plugin = get_builtin_cache_plugin(DEFAULT_CACHE_PLUGIN)
+            .expect("the memory cache plugin is always available");
+        FactCache { plugin }
+    }
+}
+
+impl FactCache {
+    pub fn new(plugin: Box<dyn CachePlugin>) -> Self {
+        FactCache { plugin }
+    }
+
+    /// Creates the cache backend from the `CACHE_PLUGIN*` settings.
+    pub async fn from_config(config_manager: &ConfigManager) -> Result<Self> {
+        let name = config_manager
+            .get_config_value::<String>("CACHE_PLUGIN")?
+            .map_or_else(|| DEFAULT_CACHE_PLUGIN.to_string(), |(name, _)| name);
+
+        let settings = CacheSettings {
+            location: config_manager
+                .get_config_value::<PathBuf>("CACHE_PLUGIN_CONNECTION")?
+                .map(|(location, _)| location),
+            timeout: config_manager
+                .get_config_value::<u64>("CACHE_PLUGIN_TIMEOUT")?
+                .map_or(DEFAULT_CACHE_TIMEOUT, |(timeout, _)| timeout),
+            prefix: config_manager
+                .get_config_value::<String>("CACHE_PLUGIN_PREFIX")?
+                .map_or_else(|| DEFAULT_CACHE_PREFIX.to_string(), |(prefix, _)| prefix),
+        };
+
+        let mut plugin = get_cache_plugin(&name).await?;
+        plugin.initialize(&settings)?;
+
+        Ok(FactCache { plugin })
+    }
+
+    /// Returns the cached facts of a host, `None` if there are none or they expired.
+    pub fn get(&self, host: &str) -> Result<Option<IndexMap<String, Variable>>> {
+        let Some(value) = self.plugin.get(host)? else {
+            return Ok(None);
+        };
+
+        match Variable::try_from(&serde_yaml::to_value(value)?)? {
+            Variable::Mapping(mapping) => Ok(Some(mapping.into_map())),
+            _ => bail!("Cached facts of host {} are not a dictionary", host),
+        }
+    }
+
+    pub fn set(&self, host: &str, facts: &IndexMap<String, Variable>) -> Result<()> {
+        self.plugin.set(host, &serde_json::to_value(facts)?)
+    }
+
+    /// Checks if the host has cached facts that did not expire yet.
+    pub fn contains(&self, host: &str) -> Result<bool> {
+        self.plugin.contains(host)
+    }
+
+    pub fn delete(&self, host: &str) -> Result<()> {
+        self.plugin.delete(host)
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.plugin.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::manager::VariableManager;
+
+    #[test]
+    fn test_facts_persist_in_file_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = CacheSettings {
+            location: Some(dir.path().to_path_buf()),
+            timeout: 3600,
+            prefix: DEFAULT_CACHE_PREFIX.to_string(),
+        };
+        let fact_cache = || {
+            let mut plugin = get_builtin_cache_plugin("jsonfile").unwrap();
+            plugin.initialize(&settings).unwrap();
+            FactCache::new(plugin)
+        };
+
+        let mut variable_manager = VariableManager::new(&dir.path().to_path_buf());
+        variable_manager.set_fact_cache(fact_cache());
+        variable_manager
+            .set_host_facts(
+                "web1",
+                IndexMap::from([("os_family".to_string(), Variable::String("Debian".into()))]),
+            )
+            .unwrap();
+        variable_manager
+            .set_host_facts(
+                "web1",
+                IndexMap::from([("cpus".to_string(), Variable::String("4".into()))]),
+            )
+            .unwrap();
+
+        // a later run reads the facts back from the cache directory
+        let cache = fact_cache();
+        assert!(cache.contains("web1").unwrap());
+        assert!(!cache.contains("web2").unwrap());
+
+        let facts = cache.get("web1").unwrap().unwrap();
+        assert_eq!(facts["os_family"], Variable::String("Debian".to_string()));
+        assert_eq!(facts["cpus"], Variable::String("4".to_string()));
+
+        cache.flush().unwrap();
+        assert!(cache.get("web1").unwrap().is_none());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::inventory::manager::InventoryManager;
+use crate::playbook::play::Play;
+use crate::template::Templar;
+use crate::vars::manager::VariableManager;
+use crate::vars::variable::Variable;
+use anyhow::Result;
+use indexmap::IndexMap;
+use log::warn;
+use minijinja::value::{Enumerator, Object};
+use minijinja::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Lazy view over the variables of every inventory host, backs the `hostvars` magic variable.
+/// Variables for a host are only computed the first time they are looked up, so templating
+/// `hostvars['db1'].ip` does not compute the variables of the whole inventory.
+pub struct HostVars {
+    variable_manager: Arc<VariableManager>,
+    inventory_manager: Arc<InventoryManager>,
+    play: Option<Play>,
+    cache: Mutex<HashMap<String, Arc<IndexMap<String, Variable>>>>,
+}
+
+impl HostVars {
+    pub fn new(
+        variable_manager: Arc<VariableManager>,
+        inventory_manager: Arc<InventoryManager>,
+        play: Option<Play>,
+    ) -> Self {
+        HostVars {
+            variable_manager,
+            inventory_manager,
+            play,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the variables of a host, `None` if the host is not in the inventory.
+    pub fn get(&self, host_name: &str) -> Result<Option<Arc<IndexMap<String, Variable>>>> {
+        if let Some(vars) = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(host_name).cloned())
+        {
+            return Ok(Some(vars));
+        }
+
+        let Some(host) = self.inventory_manager.get_host(host_name) else {
+            return Ok(None);
+        };
+
+        let vars = Arc::new(self.variable_manager.get_vars(
+            self.play.as_ref(),
+            Some(host),
+            None,
+            Some(&self.inventory_manager),
+            false,
+        )?);
+
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(host_name.to_string(), vars.clone());
+        }
+
+        Ok(Some(vars))
+    }
+
+    pub fn contains_key(&self, host_name: &str) -> bool {
+        self.inventory_manager.get_host(host_name).is_some()
+    }
+
+    /// Names of all inventory hosts, without computing their variables.
+    pub fn host_names(&self) -> Vec<&str> {
+        self.inventory_manager
+            .hosts()
+            .keys()
+            .map(|k| k.as_str())
+            .collect()
+    }
+
+    /// Drops cached variables, e.g. after facts for a host have changed.
+    pub fn invalidate(&self, host_name: Option<&str>) {
+        if let Ok(mut cache) = self.cache.lock() {
+            match host_name {
+                Some(host_name) => {
+                    cache.remove(host_name);
+                }
+                None => cache.clear(),
+            }
+        }
+    }
+
+    pub fn cached_host_count(&self) -> usize {
+        self.cache.lock().map(|cache| cache.len()).unwrap_or(0)
+    }
+
+    /// Builds the `hostvars` template value. The variables of a host are only computed when
+    /// a template accesses it, and rendered with `templar` when a template accesses them.
+    pub fn to_template_value(self: &Arc<Self>, templar: &Templar) -> Value {
+        Value::from_object(HostVarsObject {
+            hostvars: self.clone(),
+            templar: templar.clone(),
+        })
+    }
+}
+
+impl fmt::Debug for HostVars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostVars")
+            .field("cached_hosts", &self.cached_host_count())
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct HostVarsObject {
+    hostvars: Arc<HostVars>,
+    templar: Templar,
+}
+
+impl Object for HostVarsObject {
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        let host_name = key.as_str()?;
+        match self.hostvars.get(host_name) {
+            Ok(vars) => Some(self.templar.lazy_vars(vars?)),
+            Err(e) => {
+                warn!("Unable to get the variables of {host_name}: {e:#}");
+                None
+            }
+        }
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        Enumerator::Values(
+            self.hostvars
+                .host_names()
+                .into_iter()
+                .map(Value::from)
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::variable::Number;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_hostvars_are_resolved_lazily() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/inventory");
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&base_dir);
+        let hostvars = HostVars::new(
+            Arc::new(variable_manager),
+            Arc::new(inventory_manager),
+            None,
+        );
+
+        assert_eq!(hostvars.cached_host_count(), 0);
+        assert!(hostvars.contains_key("one.example.com"));
+        assert!(hostvars.host_names().contains(&"bar.example.com"));
+
+        let bar = hostvars.get("bar.example.com").unwrap().unwrap();
+        assert_eq!(bar["port"], Variable::Number(Number::Int(4443)));
+        assert_eq!(
+            bar["inventory_hostname"],
+            Variable::String("bar.example.com".to_string())
+        );
+        assert_eq!(hostvars.cached_host_count(), 1);
+
+        assert!(hostvars.get("missing.example.com").unwrap().is_none());
+
+        hostvars.invalidate(None);
+        assert_eq!(hostvars.cached_host_count(), 0);
+    }
+
+    #[test]
+    fn test_hostvars_template_value() {
+        let base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/inventory");
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&base_dir);
+        let hostvars = Arc::new(HostVars::new(
+            Arc::new(variable_manager),
+            Arc::new(inventory_manager),
+            None,
+        ));
+
+        let mut templar = Templar::new();
+        templar.set_global("hostvars", hostvars.to_template_value(&templar));
+        assert_eq!(hostvars.cached_host_count(), 0);
+
+        let rendered = templar
+            .template(
+                &Variable::String("{{ hostvars['one.example.com'].port }}".to_string()),
+                &IndexMap::new(),
+            )
+            .unwrap();
+        assert_eq!(rendered, Variable::Number(Number::Int(3306)));
+        // only the host the template accessed had its variables computed
+        assert_eq!(hostvars.cached_host_count(), 1);
+
+        let rendered = templar
+            .template(
+                &Variable::String("{{ hostvars['missing.example.com'] is defined }}".to_string()),
+                &IndexMap::new(),
+            )
+            .unwrap();
+        assert_eq!(rendered, Variable::Bool(false));
+    }
+}
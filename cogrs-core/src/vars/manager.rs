@@ -1,36 +1,249 @@
+use crate::constants::COGRS_VERSION;
 use crate::inventory::host::Host;
 use crate::inventory::manager::InventoryManager;
+use crate::parsing::loader::DataLoader;
 use crate::playbook::play::Play;
 use crate::playbook::task::Task;
-use crate::vars::variable::{combine_variables, ConflictResolution, Mapping, Variable};
+use crate::vars::fact_cache::FactCache;
+use crate::vars::variable::{
+    combine_variables, get_inventory_vars_dirs, get_vars_from_path, load_vars_from_file,
+    ConflictResolution, Mapping, Variable, VarsEntity,
+};
+use crate::vault::secrets::VaultSecrets;
+use anyhow::Result;
 use indexmap::IndexMap;
-use log::debug;
+use log::{debug, warn};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The precedence layer a variable value was taken from, recorded in debug mode.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarSource {
+    RoleDefaults(String),
+    GroupVars(String),
+    GroupVarsFiles(String, PathBuf),
+    HostVars,
+    HostVarsFiles(PathBuf),
+    Facts,
+    PlayVars,
+    VarsFile(PathBuf),
+    RoleVars(String),
+    TaskVars,
+    IncludeVars,
+    SetFact,
+    RoleParams(String),
+    IncludeParams,
+    ExtraVars,
+    Magic,
+}
+
+impl fmt::Display for VarSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarSource::RoleDefaults(role) => write!(f, "role '{}' defaults", role),
+            VarSource::GroupVars(group) => write!(f, "group vars, precedence entry '{}'", group),
+            VarSource::GroupVarsFiles(group, dir) => {
+                write!(f, "group_vars/{} in '{}'", group, dir.display())
+            }
+            VarSource::HostVars => write!(f, "host vars"),
+            VarSource::HostVarsFiles(dir) => write!(f, "host_vars in '{}'", dir.display()),
+            VarSource::Facts => write!(f, "facts"),
+            VarSource::PlayVars => write!(f, "play vars"),
+            VarSource::VarsFile(path) => write!(f, "play vars_files from '{}'", path.display()),
+            VarSource::RoleVars(role) => write!(f, "role '{}' vars", role),
+            VarSource::TaskVars => write!(f, "task vars"),
+            VarSource::IncludeVars => write!(f, "include_vars"),
+            VarSource::SetFact => write!(f, "set_fact"),
+            VarSource::RoleParams(role) => write!(f, "role '{}' params", role),
+            VarSource::IncludeParams => write!(f, "include params"),
+            VarSource::ExtraVars => write!(f, "extra vars"),
+            VarSource::Magic => write!(f, "magic vars"),
+        }
+    }
+}
 
 pub struct VariableManager {
     playbook_dir: PathBuf,
+    loader: DataLoader,
+    hash_behaviour: ConflictResolution,
+    extra_vars: IndexMap<String, Variable>,
+    fact_cache: FactCache,
+    vars_cache: HashMap<String, IndexMap<String, Variable>>,
+    nonpersistent_fact_cache: HashMap<String, IndexMap<String, Variable>>,
+    track_origins: bool,
+    vars_origins: Mutex<IndexMap<String, VarSource>>,
+    check_mode: bool,
+    play_hosts: Mutex<PlayHosts>,
+}
+
+/// Hosts targeted by the running play, exposed as the `cogrs_play_*` magic variables.
+#[derive(Debug, Clone, Default)]
+struct PlayHosts {
+    hosts_all: Vec<String>,
+    hosts: Vec<String>,
 }
 
 impl VariableManager {
     pub fn new(playbook_dir: &PathBuf) -> Self {
         VariableManager {
             playbook_dir: playbook_dir.to_path_buf(),
+            loader: DataLoader::new(),
+            hash_behaviour: ConflictResolution::Replace,
+            extra_vars: IndexMap::new(),
+            fact_cache: FactCache::default(),
+            vars_cache: HashMap::new(),
+            nonpersistent_fact_cache: HashMap::new(),
+            track_origins: false,
+            vars_origins: Mutex::new(IndexMap::new()),
+            check_mode: false,
+            play_hosts: Mutex::new(PlayHosts::default()),
+        }
+    }
+
+    pub fn loader(&self) -> &DataLoader {
+        &self.loader
+    }
+
+    /// Sets the passwords used to decrypt vault encrypted vars files.
+    pub fn set_vault_secrets(&mut self, secrets: VaultSecrets) {
+        self.loader.set_vault_secrets(secrets);
+    }
+
+    /// Sets how dictionary variables defined in several layers are combined (`hash_behaviour`).
+    pub fn set_hash_behaviour(&mut self, hash_behaviour: ConflictResolution) {
+        self.hash_behaviour = hash_behaviour;
+    }
+
+    /// When enabled, every `get_vars` call records the layer each final value came from,
+    /// available afterwards through `vars_origins`.
+    pub fn set_track_origins(&mut self, value: bool) {
+        self.track_origins = value;
+    }
+
+    /// Returns the origins recorded by the most recent `get_vars` call in debug mode.
+    pub fn vars_origins(&self) -> IndexMap<String, VarSource> {
+        self.vars_origins
+            .lock()
+            .map(|origins| origins.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_check_mode(&mut self, check_mode: bool) {
+        self.check_mode = check_mode;
+    }
+
+    /// Updates the hosts of the running play: every host matching the play pattern and the
+    /// hosts that are still active (not failed or unreachable). Until `serial` is supported
+    /// the current batch is the same as the active hosts.
+    pub fn set_play_hosts(&self, hosts_all: &[String], hosts: &[String]) {
+        if let Ok(mut play_hosts) = self.play_hosts.lock() {
+            play_hosts.hosts_all = hosts_all.to_vec();
+            play_hosts.hosts = hosts.to_vec();
+        }
+    }
+
+    pub fn extra_vars(&self) -> &IndexMap<String, Variable> {
+        &self.extra_vars
+    }
+
+    pub fn set_extra_vars(&mut self, extra_vars: IndexMap<String, Variable>) {
+        self.extra_vars = extra_vars;
+    }
+
+    /// Replaces the in-memory fact cache, e.g. with one using a persistent backend.
+    pub fn set_fact_cache(&mut self, fact_cache: FactCache) {
+        self.fact_cache = fact_cache;
+    }
+
+    pub fn fact_cache(&self) -> &FactCache {
+        &self.fact_cache
+    }
+
+    /// Stores gathered facts for a host, these persist across plays (and runs, depending
+    /// on the cache backend).
+    pub fn set_host_facts(&self, host: &str, facts: IndexMap<String, Variable>) -> Result<()> {
+        let host_facts = self.fact_cache.get(host)?.unwrap_or_default();
+        let host_facts = combine_variables(&host_facts, &facts, &self.hash_behaviour);
+        self.fact_cache.set(host, &host_facts)
+    }
+
+    /// Stores facts that only live for the current run (`set_fact`, `register`).
+    pub fn set_nonpersistent_facts(&mut self, host: &str, facts: IndexMap<String, Variable>) {
+        let host_facts = self
+            .nonpersistent_fact_cache
+            .entry(host.to_string())
+            .or_default();
+        *host_facts = combine_variables(host_facts, &facts, &self.hash_behaviour);
+    }
+
+    /// Sets a variable loaded through `include_vars` for the given host.
+    pub fn set_host_variable(&mut self, host: &str, key: &str, value: Variable) {
+        let host_vars = self.vars_cache.entry(host.to_string()).or_default();
+        let new_vars = IndexMap::from([(key.to_string(), value)]);
+        *host_vars = combine_variables(host_vars, &new_vars, &self.hash_behaviour);
+    }
+
+    /// Directories searched for `group_vars` and `host_vars`: the inventory source
+    /// directories, and the playbook directory unless it is one of them.
+    fn get_vars_dirs(
+        &self,
+        inventory_manager: Option<&InventoryManager>,
+    ) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        let inventory_dirs = match inventory_manager {
+            Some(inventory_manager) => get_inventory_vars_dirs(Some(inventory_manager.sources()))?,
+            None => Vec::new(),
+        };
+
+        let playbook_dirs = if inventory_dirs.contains(&self.playbook_dir) {
+            Vec::new()
+        } else {
+            vec![self.playbook_dir.clone()]
+        };
+
+        Ok((inventory_dirs, playbook_dirs))
+    }
+
+    fn combine_vars_files(
+        &self,
+        vars: &IndexMap<String, Variable>,
+        vars_dirs: &[PathBuf],
+        entity: VarsEntity,
+        origins: &mut Option<IndexMap<String, VarSource>>,
+    ) -> Result<IndexMap<String, Variable>> {
+        let mut vars = vars.clone();
+
+        for dir in vars_dirs {
+            let file_vars = get_vars_from_path(dir, entity, &self.loader, &self.hash_behaviour)?;
+            let source = match entity {
+                VarsEntity::Group(name) => VarSource::GroupVarsFiles(name.to_string(), dir.clone()),
+                VarsEntity::Host(_) => VarSource::HostVarsFiles(dir.clone()),
+            };
+            vars = self.combine_and_track(&vars, &file_vars, source, origins);
         }
+
+        Ok(vars)
     }
 
     fn combine_and_track(
         &self,
         vars: &IndexMap<String, Variable>,
         new_vars: &IndexMap<String, Variable>,
+        source: VarSource,
+        origins: &mut Option<IndexMap<String, VarSource>>,
     ) -> IndexMap<String, Variable> {
         if new_vars.is_empty() {
             return vars.clone();
         }
 
-        // TODO: see if we need to add tracking of sources here
+        if let Some(origins) = origins {
+            for key in new_vars.keys() {
+                origins.insert(key.to_string(), source.clone());
+            }
+        }
 
-        combine_variables(vars, new_vars, &ConflictResolution::Replace)
+        combine_variables(vars, new_vars, &self.hash_behaviour)
     }
 
     /// Returns the variables, with optional "context" given via the parameters
@@ -39,21 +252,27 @@ impl VariableManager {
     ///
     /// # Order of Precedence:
     /// 1. `play->roles->get_default_vars` - (if there is a play context)
-    /// 2. `group_vars_files[host]` - (if there is a host context)
-    /// 3. `host_vars_files[host]` - (if there is a host context)
-    /// 4. `host->get_vars` - (if there is a host context)
-    /// 5. `fact_cache[host]` - (if there is a host context)
-    /// 6. `play vars` - (if there is a play context)
-    /// 7. `play vars_files` - (if there’s no host context, ignoring file names that cannot be templated)
-    /// 8. `task->get_vars` - (if there is a task context)
-    /// 9. `vars_cache[host]` - (if there is a host context)
-    /// 10. `extra vars`
+    /// 2. `group vars` - inventory group vars, ordered by group depth and priority (if there is a host context)
+    /// 3. `group_vars/all` - next to the inventory sources, then next to the playbook
+    /// 4. `group_vars/*` - other groups next to the inventory sources, then all of them next to the playbook
+    /// 5. `host->get_vars` - (if there is a host context)
+    /// 6. `host_vars/*` - next to the inventory sources, then next to the playbook
+    /// 7. `fact_cache[host]` - (if there is a host context)
+    /// 8. `play vars` - (if there is a play context)
+    /// 9. `play vars_files` - (if there is a play context)
+    /// 10. `play->roles->get_vars` - (if there is a play context)
+    /// 11. `task->get_vars` - block and task vars (if there is a task context)
+    /// 12. `vars_cache[host]` - include_vars (if there is a host context)
+    /// 13. `nonpersistent_fact_cache[host]` - set_fact and register (if there is a host context)
+    /// 14. `task->role->get_role_params` and `task->get_include_params` - (if there is a task context)
+    /// 15. `extra vars`
+    ///
+    /// Dictionaries defined in several layers are replaced or merged according to `hash_behaviour`.
     ///
     /// # Parameters:
     /// - `play`: Optional context for play-specific variables.
     /// - `host`: Optional context for host-specific variables.
     /// - `task`: Optional context for task-specific variables.
-    /// - `include_hostvars`: Indicates whether to include host variables.
     /// - `use_cache`: Use cached variables if available.
     ///
     /// # Returns:
@@ -65,21 +284,42 @@ impl VariableManager {
         host: Option<&Host>,
         task: Option<&Task>,
         inventory_manager: Option<&InventoryManager>,
-        include_hostvars: bool,
         use_cache: bool,
-    ) -> IndexMap<String, Variable> {
+    ) -> Result<IndexMap<String, Variable>> {
         let mut all_vars = IndexMap::new();
+        let mut origins = if self.track_origins {
+            Some(IndexMap::new())
+        } else {
+            None
+        };
 
-        let magic_vars = self.get_magic_vars(play, host, task, inventory_manager, include_hostvars);
+        let magic_vars = self.get_magic_vars(play, host, task, inventory_manager);
 
         if let Some(play) = play {
             // get role defaults (lowest precedence)
             for role in play.roles() {
-                // TODO: process roles
+                all_vars = self.combine_and_track(
+                    &all_vars,
+                    role.default_vars(),
+                    VarSource::RoleDefaults(role.name().to_string()),
+                    &mut origins,
+                );
             }
         }
 
         if let Some(task) = task {
+            // task role defaults, if the task role is not part of the play roles
+            if let Some(role) = task.role() {
+                if play.is_none_or(|play| play.roles().iter().all(|r| r.uuid() != role.uuid())) {
+                    all_vars = self.combine_and_track(
+                        &all_vars,
+                        role.default_vars(),
+                        VarSource::RoleDefaults(role.name().to_string()),
+                        &mut origins,
+                    );
+                }
+            }
+
             all_vars.insert(
                 String::from("task_uuid"),
                 Variable::String(task.uuid().to_string()),
@@ -87,19 +327,97 @@ impl VariableManager {
         }
 
         if let Some(host) = host {
-            // TODO: process host
+            let (inventory_dirs, playbook_dirs) = self.get_vars_dirs(inventory_manager)?;
+            let vars_dirs = [inventory_dirs.as_slice(), playbook_dirs.as_slice()].concat();
+
+            // group vars, from the most general group to the most specific one: the vars of
+            // the inventory sources, then `group_vars/all`, then the `group_vars` of the other
+            // groups next to the inventory sources and finally next to the playbook
+            if let Some(inventory_manager) = inventory_manager {
+                let groups = inventory_manager.get_host_groups(host);
+                let (all_groups, other_groups): (Vec<_>, Vec<_>) =
+                    groups.into_iter().partition(|group| group.name() == "all");
+
+                for group in all_groups.iter().chain(&other_groups) {
+                    all_vars = self.combine_and_track(
+                        &all_vars,
+                        group.get_vars(),
+                        VarSource::GroupVars(group.name().to_string()),
+                        &mut origins,
+                    );
+                }
+
+                for group in &all_groups {
+                    all_vars = self.combine_vars_files(
+                        &all_vars,
+                        &vars_dirs,
+                        VarsEntity::Group(group.name()),
+                        &mut origins,
+                    )?;
+                }
+
+                for dirs in [&inventory_dirs, &playbook_dirs] {
+                    for group in &other_groups {
+                        all_vars = self.combine_vars_files(
+                            &all_vars,
+                            dirs,
+                            VarsEntity::Group(group.name()),
+                            &mut origins,
+                        )?;
+                    }
+                }
+            }
+
+            all_vars =
+                self.combine_and_track(&all_vars, host.vars(), VarSource::HostVars, &mut origins);
+            all_vars = self.combine_vars_files(
+                &all_vars,
+                &vars_dirs,
+                VarsEntity::Host(host.name()),
+                &mut origins,
+            )?;
+
+            if let Some(facts) = self.fact_cache.get(host.name())? {
+                all_vars =
+                    self.combine_and_track(&all_vars, &facts, VarSource::Facts, &mut origins);
+                let facts_var = IndexMap::from([(
+                    String::from("cogrs_facts"),
+                    Variable::Mapping(facts.into()),
+                )]);
+                all_vars =
+                    self.combine_and_track(&all_vars, &facts_var, VarSource::Facts, &mut origins);
+            }
         }
 
         if let Some(play) = play {
-            all_vars = self.combine_and_track(&all_vars, play.vars());
+            all_vars =
+                self.combine_and_track(&all_vars, play.vars(), VarSource::PlayVars, &mut origins);
+
+            for var_file in play.vars_files() {
+                let path = self.resolve_vars_file_path(var_file);
 
-            let var_files = play.vars_files();
-            for var_file in var_files {
-                // TODO: process var files
+                // TODO: template vars_files names once host vars are available to the templar
+                if var_file.contains("{{") {
+                    warn!("Skipping templated vars_files entry: {}", var_file);
+                    continue;
+                }
+
+                let file_vars = load_vars_from_file(&path, &self.loader)?;
+                all_vars = self.combine_and_track(
+                    &all_vars,
+                    &file_vars,
+                    VarSource::VarsFile(path),
+                    &mut origins,
+                );
             }
 
             for role in play.roles() {
-                // TODO: process play roles
+                all_vars = self.combine_and_track(
+                    &all_vars,
+                    role.vars(),
+                    VarSource::RoleVars(role.name().to_string()),
+                    &mut origins,
+                );
             }
         }
 
@@ -107,26 +425,67 @@ impl VariableManager {
         // follow the role dependency chain, and then we merge in the tasks
         // vars (which will look at parent blocks/task includes)
         if let Some(task) = task {
-            // TODO: process task vars
+            if let Some(role) = task.role() {
+                all_vars = self.combine_and_track(
+                    &all_vars,
+                    role.vars(),
+                    VarSource::RoleVars(role.name().to_string()),
+                    &mut origins,
+                );
+            }
+
+            all_vars = self.combine_and_track(
+                &all_vars,
+                task.get_vars(),
+                VarSource::TaskVars,
+                &mut origins,
+            );
         }
 
         // next, we merge in the vars cache (include vars) and nonpersistent
         // facts cache (set_fact/register), in that order
         if let Some(host) = host {
-            // TODO
+            if let Some(vars) = self.vars_cache.get(host.name()) {
+                all_vars =
+                    self.combine_and_track(&all_vars, vars, VarSource::IncludeVars, &mut origins);
+            }
+
+            if let Some(facts) = self.nonpersistent_fact_cache.get(host.name()) {
+                all_vars =
+                    self.combine_and_track(&all_vars, facts, VarSource::SetFact, &mut origins);
+            }
         }
 
         // next, we merge in role params and task include params
         if let Some(task) = task {
-            // TODO
+            if let Some(role) = task.role() {
+                all_vars = self.combine_and_track(
+                    &all_vars,
+                    role.params(),
+                    VarSource::RoleParams(role.name().to_string()),
+                    &mut origins,
+                );
+            }
+
+            all_vars = self.combine_and_track(
+                &all_vars,
+                task.include_params(),
+                VarSource::IncludeParams,
+                &mut origins,
+            );
         }
 
-        // add extra vars
-        // TODO: load and merge extra vars
+        // extra vars always win
+        all_vars = self.combine_and_track(
+            &all_vars,
+            &self.extra_vars,
+            VarSource::ExtraVars,
+            &mut origins,
+        );
 
         // TODO: check for any reserved vars
 
-        all_vars = self.combine_and_track(&mut all_vars, &magic_vars);
+        all_vars = self.combine_and_track(&all_vars, &magic_vars, VarSource::Magic, &mut origins);
 
         // special case for the 'environment' magic variable, as someone
         // may have set it as a variable and we don't want to stomp on it
@@ -135,7 +494,53 @@ impl VariableManager {
             //all_vars.insert(String::from("environment"), task.environment())
         }
 
-        all_vars
+        if let Some(origins) = origins {
+            for (key, source) in &origins {
+                debug!("var '{}' from {}", key, source);
+            }
+
+            if let Ok(mut vars_origins) = self.vars_origins.lock() {
+                *vars_origins = origins;
+            }
+        }
+
+        Ok(all_vars)
+    }
+
+    /// Returns the variables of a host coming from the inventory: group vars, `group_vars`,
+    /// host vars and `host_vars`, without the magic variables.
+    pub fn get_inventory_vars(
+        &self,
+        host: &Host,
+        inventory_manager: &InventoryManager,
+    ) -> Result<IndexMap<String, Variable>> {
+        let mut vars = self.get_vars(None, Some(host), None, Some(inventory_manager), false)?;
+        let magic_vars = self.get_magic_vars(None, Some(host), None, Some(inventory_manager));
+        vars.retain(|key, _| !magic_vars.contains_key(key));
+
+        Ok(vars)
+    }
+
+    /// Returns the variables defined for a group or a host in `group_vars` or `host_vars`
+    /// files, next to the inventory sources and the playbook.
+    pub fn get_vars_files(
+        &self,
+        entity: VarsEntity,
+        inventory_manager: &InventoryManager,
+    ) -> Result<IndexMap<String, Variable>> {
+        let (inventory_dirs, playbook_dirs) = self.get_vars_dirs(Some(inventory_manager))?;
+        let vars_dirs = [inventory_dirs, playbook_dirs].concat();
+        self.combine_vars_files(&IndexMap::new(), &vars_dirs, entity, &mut None)
+    }
+
+    fn resolve_vars_file_path(&self, var_file: &str) -> PathBuf {
+        let path = Path::new(var_file);
+
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.playbook_dir.join(path)
+        }
     }
 
     fn get_magic_vars(
@@ -144,7 +549,6 @@ impl VariableManager {
         host: Option<&Host>,
         task: Option<&Task>,
         inventory_manager: Option<&InventoryManager>,
-        include_hostvars: bool,
     ) -> IndexMap<String, Variable> {
         let mut magic_vars: IndexMap<String, Variable> = IndexMap::new();
 
@@ -152,6 +556,14 @@ impl VariableManager {
             String::from("playbook_dir"),
             Variable::Path(self.playbook_dir.clone()),
         );
+        magic_vars.insert(
+            String::from("cogrs_check_mode"),
+            Variable::Bool(self.check_mode),
+        );
+        magic_vars.insert(
+            String::from("cogrs_version"),
+            Variable::String(COGRS_VERSION.to_string()),
+        );
 
         if let Some(play) = play {
             // TODO: get all role names and assign to cogrs_role_names
@@ -173,6 +585,29 @@ impl VariableManager {
                 String::from("cogrs_play_name"),
                 Variable::String(play.name().to_string()),
             );
+
+            let play_hosts = self
+                .play_hosts
+                .lock()
+                .map(|play_hosts| play_hosts.clone())
+                .unwrap_or_default();
+            let to_sequence = |hosts: &[String]| {
+                Variable::Sequence(hosts.iter().map(|h| Variable::String(h.clone())).collect())
+            };
+
+            magic_vars.insert(
+                String::from("cogrs_play_hosts_all"),
+                to_sequence(&play_hosts.hosts_all),
+            );
+            magic_vars.insert(
+                String::from("cogrs_play_hosts"),
+                to_sequence(&play_hosts.hosts),
+            );
+            magic_vars.insert(
+                String::from("cogrs_play_batch"),
+                to_sequence(&play_hosts.hosts),
+            );
+            magic_vars.insert(String::from("play_hosts"), to_sequence(&play_hosts.hosts));
         }
 
         if let Some(task) = task {
@@ -192,14 +627,444 @@ impl VariableManager {
             }
         }
 
+        if let Some(host) = host {
+            magic_vars.insert(
+                String::from("inventory_hostname"),
+                Variable::String(host.name().to_string()),
+            );
+            magic_vars.insert(
+                String::from("inventory_hostname_short"),
+                Variable::String(
+                    host.name()
+                        .split('.')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                ),
+            );
+
+            if let Some(inventory_manager) = inventory_manager {
+                let mut group_names: Vec<&str> = inventory_manager
+                    .get_host_groups(host)
+                    .iter()
+                    .map(|group| group.name())
+                    .filter(|name| *name != "all")
+                    .collect();
+                group_names.sort();
+
+                magic_vars.insert(
+                    String::from("group_names"),
+                    Variable::Sequence(
+                        group_names
+                            .into_iter()
+                            .map(|name| Variable::String(name.to_string()))
+                            .collect(),
+                    ),
+                );
+            }
+        }
+
         if let Some(inventory_manager) = inventory_manager {
-            let groups: Mapping = inventory_manager.get_groups_dict().into();
+            let groups = inventory_manager.get_groups_mapping();
             debug!("magic_vars groups: {:?}", groups);
-            magic_vars.insert(String::from("groups"), Variable::Mapping(groups));
+            magic_vars.insert(
+                String::from("groups"),
+                Variable::Mapping(Mapping::clone(&groups)),
+            );
         }
 
-        // TODO: set groups var
-
         magic_vars
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playbook::block::{Block, BlockEntry};
+    use crate::playbook::role::Role;
+    use crate::playbook::task::{Action, TaskBuilder};
+    use crate::vars::variable::Number;
+
+    fn vars(pairs: &[(&str, Variable)]) -> IndexMap<String, Variable> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn string(value: &str) -> Variable {
+        Variable::String(value.to_string())
+    }
+
+    fn get_playbook_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/inventory")
+    }
+
+    #[test]
+    fn test_play_vars_override_role_defaults() {
+        let mut role = Role::new("web", &PathBuf::from("/roles/web"));
+        role.set_default_vars(vars(&[
+            ("port", Variable::Number(Number::Int(80))),
+            ("user", string("www")),
+        ]));
+
+        let play = Play::builder("test", &[role])
+            .vars(vars(&[("port", Variable::Number(Number::Int(8080)))]))
+            .build();
+
+        let variable_manager = VariableManager::new(&get_playbook_dir());
+        let all_vars = variable_manager
+            .get_vars(Some(&play), None, None, None, false)
+            .unwrap();
+
+        assert_eq!(all_vars["port"], Variable::Number(Number::Int(8080)));
+        assert_eq!(all_vars["user"], string("www"));
+    }
+
+    #[test]
+    fn test_task_and_block_vars_precedence() {
+        let mut role = Role::new("web", &PathBuf::from("/roles/web"));
+        role.set_vars(vars(&[
+            ("level", string("role")),
+            ("role_only", string("yes")),
+        ]));
+        role.set_params(vars(&[("param", string("role_param"))]));
+
+        let task = TaskBuilder::new("task", "ssh", Action::Meta("noop".to_string()))
+            .role(role)
+            .vars(vars(&[("level", string("task"))]))
+            .build();
+
+        let mut block = Block::new();
+        block.add_to_block(BlockEntry::Task(task));
+        block.set_vars(vars(&[
+            ("level", string("block")),
+            ("block_only", string("yes")),
+            ("param", string("block")),
+        ]));
+
+        let Some(BlockEntry::Task(task)) = block.get_block_entry(0) else {
+            panic!("expected task entry");
+        };
+
+        let variable_manager = VariableManager::new(&get_playbook_dir());
+        let all_vars = variable_manager
+            .get_vars(None, None, Some(task), None, false)
+            .unwrap();
+
+        assert_eq!(all_vars["level"], string("task"));
+        assert_eq!(all_vars["block_only"], string("yes"));
+        assert_eq!(all_vars["role_only"], string("yes"));
+        assert_eq!(all_vars["param"], string("role_param"));
+    }
+
+    #[test]
+    fn test_host_vars_override_group_vars() {
+        let base_dir = get_playbook_dir();
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&base_dir);
+
+        let foo = inventory_manager.get_host("foo.example.com").unwrap();
+        let foo_vars = variable_manager
+            .get_vars(None, Some(foo), None, Some(&inventory_manager), false)
+            .unwrap();
+        assert_eq!(foo_vars["port"], Variable::Number(Number::Int(8080)));
+
+        let bar = inventory_manager.get_host("bar.example.com").unwrap();
+        let bar_vars = variable_manager
+            .get_vars(None, Some(bar), None, Some(&inventory_manager), false)
+            .unwrap();
+        assert_eq!(bar_vars["port"], Variable::Number(Number::Int(4443)));
+    }
+
+    #[test]
+    fn test_groups_magic_var_sees_runtime_changes() {
+        let base_dir = get_playbook_dir();
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        inventory_manager
+            .add_host("new.example.com", &["new".to_string()], &IndexMap::new())
+            .unwrap();
+        inventory_manager
+            .group_by("foo.example.com", "debian", &["all".to_string()])
+            .unwrap();
+
+        let variable_manager = VariableManager::new(&base_dir);
+        let foo = inventory_manager.get_host("foo.example.com").unwrap();
+        let foo_vars = variable_manager
+            .get_vars(None, Some(foo), None, Some(&inventory_manager), false)
+            .unwrap();
+
+        let Variable::Mapping(groups) = &foo_vars["groups"] else {
+            panic!("groups is not a mapping");
+        };
+        let groups: IndexMap<&String, &Variable> = groups.iter().collect();
+        assert_eq!(
+            groups[&"new".to_string()],
+            &Variable::Sequence(vec![string("new.example.com")])
+        );
+        assert_eq!(
+            groups[&"debian".to_string()],
+            &Variable::Sequence(vec![string("foo.example.com")])
+        );
+    }
+
+    #[test]
+    fn test_group_vars_and_host_vars_directories() {
+        let inventory_dir = tempfile::tempdir().unwrap();
+        let playbook_dir = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, name: &str, content: &str| {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+
+        write(
+            inventory_dir.path(),
+            "hosts.yaml",
+            "webservers:\n  vars:\n    port: 80\n  hosts:\n    foo.example.com:\n",
+        );
+        write(
+            inventory_dir.path(),
+            "group_vars/all.yml",
+            "port: 1\nenv: all\nowner: ops\n",
+        );
+        write(
+            inventory_dir.path(),
+            "group_vars/webservers/10-main.yml",
+            "port: 8080\n",
+        );
+        write(
+            inventory_dir.path(),
+            "group_vars/webservers/20-tls.yaml",
+            "tls: true\nenv: web\n",
+        );
+        write(
+            inventory_dir.path(),
+            "group_vars/webservers/.hidden.yml",
+            "port: 0\n",
+        );
+        write(
+            inventory_dir.path(),
+            "host_vars/foo.example.com.json",
+            "{\"port\": 8443}",
+        );
+        write(
+            playbook_dir.path(),
+            "group_vars/webservers",
+            "env: playbook\n",
+        );
+        write(playbook_dir.path(), "group_vars/all.yaml", "owner: dev\n");
+
+        let mut inventory_manager = InventoryManager::new(&inventory_dir.path().to_path_buf());
+        let sources = vec![inventory_dir
+            .path()
+            .join("hosts.yaml")
+            .to_str()
+            .unwrap()
+            .to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&playbook_dir.path().to_path_buf());
+        let foo = inventory_manager.get_host("foo.example.com").unwrap();
+        let all_vars = variable_manager
+            .get_vars(None, Some(foo), None, Some(&inventory_manager), false)
+            .unwrap();
+
+        assert_eq!(all_vars["port"], Variable::Number(Number::Int(8443)));
+        assert_eq!(all_vars["tls"], Variable::Bool(true));
+        assert_eq!(all_vars["env"], string("playbook"));
+        assert_eq!(all_vars["owner"], string("dev"));
+    }
+
+    /// Writes an inventory with `prod` as a child of `app` and the given files next to it
+    /// and to the playbook, returns the variables of its host.
+    fn group_vars_with_files(
+        inventory_files: &[(&str, &str)],
+        playbook_files: &[(&str, &str)],
+    ) -> IndexMap<String, Variable> {
+        let inventory_dir = tempfile::tempdir().unwrap();
+        let playbook_dir = tempfile::tempdir().unwrap();
+        let write = |dir: &Path, name: &str, content: &str| {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+
+        write(
+            inventory_dir.path(),
+            "hosts.yaml",
+            "app:\n  vars:\n    listen: app\n  children:\n    prod:\n      hosts:\n        foo.example.com:\n",
+        );
+        for (name, content) in inventory_files {
+            write(inventory_dir.path(), name, content);
+        }
+        for (name, content) in playbook_files {
+            write(playbook_dir.path(), name, content);
+        }
+
+        let mut inventory_manager = InventoryManager::new(&inventory_dir.path().to_path_buf());
+        let sources = vec![inventory_dir
+            .path()
+            .join("hosts.yaml")
+            .to_str()
+            .unwrap()
+            .to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let variable_manager = VariableManager::new(&playbook_dir.path().to_path_buf());
+        let foo = inventory_manager.get_host("foo.example.com").unwrap();
+        variable_manager
+            .get_vars(None, Some(foo), None, Some(&inventory_manager), false)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_group_vars_all_override_inventory_group_vars() {
+        let all_vars = group_vars_with_files(
+            &[("group_vars/all.yml", "listen: all\nowner: all\n")],
+            &[("group_vars/all.yml", "owner: playbook\n")],
+        );
+
+        assert_eq!(all_vars["listen"], string("all"));
+        assert_eq!(all_vars["owner"], string("playbook"));
+    }
+
+    #[test]
+    fn test_group_vars_next_to_playbook_override_those_next_to_inventory() {
+        let all_vars = group_vars_with_files(
+            &[
+                ("group_vars/prod.yml", "tier: prod\nsize: prod\n"),
+                ("group_vars/app.yml", "tier: app\nsize: app\n"),
+            ],
+            &[("group_vars/app.yml", "tier: playbook\n")],
+        );
+
+        // the child group wins next to the inventory, the playbook files apply after all of them
+        assert_eq!(all_vars["size"], string("prod"));
+        assert_eq!(all_vars["tier"], string("playbook"));
+    }
+
+    #[test]
+    fn test_magic_vars() {
+        let base_dir = get_playbook_dir();
+        let mut inventory_manager = InventoryManager::new(&base_dir);
+        let sources = vec![base_dir.join("basic.yaml").to_str().unwrap().to_string()];
+        inventory_manager.parse_sources(Some(&sources)).unwrap();
+
+        let mut variable_manager = VariableManager::new(&base_dir);
+        variable_manager.set_check_mode(true);
+        variable_manager.set_play_hosts(
+            &["foo.example.com".to_string(), "bar.example.com".to_string()],
+            &["bar.example.com".to_string()],
+        );
+
+        let play = Play::builder("test", &[]).build();
+        let bar = inventory_manager.get_host("bar.example.com").unwrap();
+        let all_vars = variable_manager
+            .get_vars(
+                Some(&play),
+                Some(bar),
+                None,
+                Some(&inventory_manager),
+                false,
+            )
+            .unwrap();
+
+        assert_eq!(all_vars["inventory_hostname"], string("bar.example.com"));
+        assert_eq!(all_vars["inventory_hostname_short"], string("bar"));
+        assert_eq!(
+            all_vars["group_names"],
+            Variable::Sequence(vec![string("webservers")])
+        );
+        assert_eq!(all_vars["cogrs_check_mode"], Variable::Bool(true));
+        assert_eq!(all_vars["cogrs_version"], string(COGRS_VERSION));
+        assert_eq!(
+            all_vars["cogrs_play_hosts_all"],
+            Variable::Sequence(vec![string("foo.example.com"), string("bar.example.com")])
+        );
+        assert_eq!(
+            all_vars["play_hosts"],
+            Variable::Sequence(vec![string("bar.example.com")])
+        );
+    }
+
+    #[test]
+    fn test_extra_vars_have_highest_precedence() {
+        let play = Play::builder("test", &[])
+            .vars(vars(&[("env", string("play"))]))
+            .build();
+
+        let host = Host::new("web1");
+        let mut variable_manager = VariableManager::new(&get_playbook_dir());
+        variable_manager.set_nonpersistent_facts("web1", vars(&[("env", string("set_fact"))]));
+        variable_manager.set_extra_vars(vars(&[("env", string("extra"))]));
+
+        let all_vars = variable_manager
+            .get_vars(Some(&play), Some(&host), None, None, false)
+            .unwrap();
+
+        assert_eq!(all_vars["env"], string("extra"));
+    }
+
+    #[test]
+    fn test_hash_behaviour_merge() {
+        let mut role = Role::new("web", &PathBuf::from("/roles/web"));
+        let defaults = vars(&[("a", string("default")), ("b", string("default"))]);
+        role.set_default_vars(vars(&[("settings", Variable::Mapping(defaults.into()))]));
+
+        let play_settings = vars(&[("b", string("play"))]);
+        let play = Play::builder("test", &[role])
+            .vars(vars(&[(
+                "settings",
+                Variable::Mapping(play_settings.into()),
+            )]))
+            .build();
+
+        let mut variable_manager = VariableManager::new(&get_playbook_dir());
+        let replaced = variable_manager
+            .get_vars(Some(&play), None, None, None, false)
+            .unwrap();
+        assert_eq!(
+            replaced["settings"],
+            Variable::Mapping(vars(&[("b", string("play"))]).into())
+        );
+
+        variable_manager.set_hash_behaviour(ConflictResolution::Merge);
+        let merged = variable_manager
+            .get_vars(Some(&play), None, None, None, false)
+            .unwrap();
+        assert_eq!(
+            merged["settings"],
+            Variable::Mapping(vars(&[("a", string("default")), ("b", string("play"))]).into())
+        );
+    }
+
+    #[test]
+    fn test_track_origins() {
+        let play = Play::builder("test", &[])
+            .vars(vars(&[
+                ("from_play", string("yes")),
+                ("env", string("play")),
+            ]))
+            .build();
+
+        let mut variable_manager = VariableManager::new(&get_playbook_dir());
+        variable_manager.set_extra_vars(vars(&[("env", string("extra"))]));
+        variable_manager.set_track_origins(true);
+
+        variable_manager
+            .get_vars(Some(&play), None, None, None, false)
+            .unwrap();
+
+        let origins = variable_manager.vars_origins();
+        assert_eq!(origins["from_play"], VarSource::PlayVars);
+        assert_eq!(origins["env"], VarSource::ExtraVars);
+        assert_eq!(origins["playbook_dir"], VarSource::Magic);
+    }
+}
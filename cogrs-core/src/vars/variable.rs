@@ -1,9 +1,16 @@
+use crate::parsing::host_list::is_host_list;
+use crate::parsing::loader::DataLoader;
+use crate::parsing::splitter::parse_kv;
+use crate::vault::encrypted::{EncryptedString, VAULT_JSON_KEY};
 use anyhow::bail;
 use anyhow::Result;
 use indexmap::IndexMap;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use serde_yaml::Value;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
 pub type Sequence = Vec<Variable>;
 
@@ -23,14 +30,22 @@ impl Number {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize)]
+/// A mapping of variables. Its entries are shared between clones until one of them is
+/// modified, so large mappings such as the `groups` magic variable are cheap to hand out.
+#[derive(Debug, PartialEq, Clone)]
 pub struct Mapping {
-    pub(crate) map: IndexMap<String, Variable>,
+    pub(crate) map: Arc<IndexMap<String, Variable>>,
+}
+
+impl Serialize for Mapping {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.map.serialize(serializer)
+    }
 }
 
 impl From<IndexMap<String, Variable>> for Mapping {
     fn from(map: IndexMap<String, Variable>) -> Self {
-        Mapping { map }
+        Mapping { map: Arc::new(map) }
     }
 }
 
@@ -57,12 +72,17 @@ impl Mapping {
 
     pub fn new() -> Self {
         Mapping {
-            map: IndexMap::new(),
+            map: Arc::new(IndexMap::new()),
         }
     }
 
     pub fn insert(&mut self, key: String, value: Variable) {
-        self.map.insert(key, value);
+        Arc::make_mut(&mut self.map).insert(key, value);
+    }
+
+    /// Returns the entries, they are only copied when shared with another clone.
+    pub(crate) fn into_map(self) -> IndexMap<String, Variable> {
+        Arc::unwrap_or_clone(self.map)
     }
 }
 
@@ -97,21 +117,126 @@ pub enum Variable {
     Mapping(Mapping),
     String(String),
     Path(PathBuf),
+    /// A value tagged `!unsafe`, it is never templated, not even nested values.
+    Unsafe(Box<Variable>),
+    /// A value tagged `!vault`, decrypted on first use.
+    Vault(EncryptedString),
+}
+
+/// An inventory entity whose vars can be defined in `host_vars/` or `group_vars/` directories.
+#[derive(Debug, Clone, Copy)]
+pub enum VarsEntity<'a> {
+    Host(&'a str),
+    Group(&'a str),
+}
+
+impl VarsEntity<'_> {
+    fn sub_dir(&self) -> &str {
+        match self {
+            VarsEntity::Host(_) => "host_vars",
+            VarsEntity::Group(_) => "group_vars",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            VarsEntity::Host(name) | VarsEntity::Group(name) => name,
+        }
+    }
 }
 
-pub fn get_vars_from_path(path: &Path) -> Result<IndexMap<String, Variable>> {
-    todo!()
+const VARS_FILE_EXTENSIONS: [&str; 3] = ["yml", "yaml", "json"];
+
+fn has_vars_file_extension(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => VARS_FILE_EXTENSIONS.contains(&ext),
+        None => true,
+    }
 }
 
-pub fn get_vars_from_inventory_sources(
-    sources: Option<&[String]>,
+/// Collects vars files from a directory recursively, sorted by name. Hidden files
+/// and editor backups are skipped.
+fn find_vars_files_in_dir(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+
+    for entry in entries {
+        let file_name = entry
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+
+        if file_name.starts_with('.') || file_name.ends_with('~') {
+            continue;
+        }
+
+        if entry.is_dir() {
+            find_vars_files_in_dir(&entry, found)?;
+        } else if has_vars_file_extension(&entry) {
+            found.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the vars files for `name` in a `host_vars`/`group_vars` directory: the
+/// `name` file itself (with or without a `.yml`, `.yaml` or `.json` extension), or
+/// every file inside a `name/` directory.
+fn find_vars_files(base_dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let path = base_dir.join(name);
+
+    if path.is_dir() {
+        find_vars_files_in_dir(&path, &mut found)?;
+    } else if path.is_file() {
+        found.push(path);
+    }
+
+    for extension in VARS_FILE_EXTENSIONS {
+        let path = base_dir.join(format!("{}.{}", name, extension));
+        if path.is_file() {
+            found.push(path);
+        }
+    }
+
+    Ok(found)
+}
+
+/// Loads the vars defined for an entity in `<path>/host_vars` or `<path>/group_vars`.
+pub fn get_vars_from_path(
+    path: &Path,
+    entity: VarsEntity,
+    loader: &DataLoader,
+    hash_behaviour: &ConflictResolution,
 ) -> Result<IndexMap<String, Variable>> {
     let mut vars = IndexMap::new();
+    let base_dir = path.join(entity.sub_dir());
+
+    if !base_dir.is_dir() {
+        return Ok(vars);
+    }
+
+    for file in find_vars_files(&base_dir, entity.name())? {
+        let file_vars = load_vars_from_file(&file, loader)?;
+        vars = combine_variables(&vars, &file_vars, hash_behaviour);
+    }
+
+    Ok(vars)
+}
+
+/// Returns the directories next to the inventory sources that may hold
+/// `host_vars` and `group_vars`.
+pub fn get_inventory_vars_dirs(sources: Option<&[String]>) -> Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
 
     if let Some(sources) = sources {
         for source in sources {
-            // TODO: revisit this logic, what if its real path and host separated by comma?
-            if source.contains(",") {
+            // inline host lists have no directory to hold host_vars and group_vars
+            if is_host_list(source) {
                 continue;
             }
 
@@ -124,18 +249,114 @@ pub fn get_vars_from_inventory_sources(
                 ))?;
             }
 
-            //vars = combine_variables(&vars, &get_vars_from_path(path)?);
+            if !dirs.iter().any(|dir| dir == path) {
+                dirs.push(path.to_path_buf());
+            }
         }
     }
 
+    Ok(dirs)
+}
+
+/// Loads a YAML (or JSON) file whose top level is a mapping of variables.
+/// An empty file yields no variables.
+pub fn load_vars_from_file(path: &Path, loader: &DataLoader) -> Result<IndexMap<String, Variable>> {
+    let data = loader.load_from_file(path)?;
+
+    match Variable::try_from(&data)? {
+        Variable::Null => Ok(IndexMap::new()),
+        Variable::Mapping(mapping) => Ok(mapping.into_map()),
+        _ => bail!(
+            "Vars file {} must contain a dictionary of variables",
+            path.display()
+        ),
+    }
+}
+
+/// Loads variables passed with `-e/--extra-vars`. Each entry is either `@path` to a
+/// vars file, inline YAML/JSON starting with `{` or `[`, or `key=value` pairs.
+/// Later entries override earlier ones.
+pub fn load_extra_vars(
+    extra_vars: &[String],
+    loader: &DataLoader,
+    hash_behaviour: &ConflictResolution,
+) -> Result<IndexMap<String, Variable>> {
+    let mut vars = IndexMap::new();
+
+    for extra_vars_opt in extra_vars {
+        let extra_vars_opt = extra_vars_opt.trim();
+
+        let data = if let Some(path) = extra_vars_opt.strip_prefix('@') {
+            if path.is_empty() {
+                bail!("The @ sign in extra vars must be followed by a file name");
+            }
+            load_vars_from_file(Path::new(path), loader)?
+        } else if extra_vars_opt.starts_with('{') || extra_vars_opt.starts_with('[') {
+            let value: Value = serde_yaml::from_str(extra_vars_opt).map_err(|e| {
+                anyhow::format_err!("Unable to parse extra vars '{}': {}", extra_vars_opt, e)
+            })?;
+
+            match Variable::try_from(&value)? {
+                Variable::Mapping(mapping) => mapping.into_map(),
+                _ => bail!(
+                    "Invalid extra vars data supplied. '{}' could not be made into a dictionary",
+                    extra_vars_opt
+                ),
+            }
+        } else if extra_vars_opt.is_empty() {
+            bail!("Empty string is not allowed in extra vars");
+        } else {
+            parse_kv(extra_vars_opt)
+                .map_err(|e| {
+                    anyhow::format_err!(
+                        "Invalid extra vars data supplied. '{}' could not be made into a dictionary: {}",
+                        extra_vars_opt,
+                        e
+                    )
+                })?
+                .into_iter()
+                .map(|(key, value)| (key, Variable::String(value)))
+                .collect()
+        };
+
+        vars = combine_variables(&vars, &data, hash_behaviour);
+    }
+
     Ok(vars)
 }
 
+/// How two values for the same variable are combined, configured through `hash_behaviour`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum ConflictResolution {
+    #[default]
     Replace,
     Merge,
 }
 
+impl FromStr for ConflictResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "replace" => Ok(ConflictResolution::Replace),
+            "merge" => Ok(ConflictResolution::Merge),
+            _ => bail!(
+                "Invalid hash_behaviour value '{}', expected 'replace' or 'merge'",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ConflictResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictResolution::Replace => write!(f, "replace"),
+            ConflictResolution::Merge => write!(f, "merge"),
+        }
+    }
+}
+
 pub fn combine_variables(
     a: &IndexMap<String, Variable>,
     b: &IndexMap<String, Variable>,
@@ -153,7 +374,7 @@ pub fn combine_variables(
                     (result.get(key), value)
                 {
                     let merged = combine_variables(&a_map.map, &b_map.map, strategy);
-                    result.insert(key.clone(), Variable::Mapping(Mapping { map: merged }));
+                    result.insert(key.clone(), Variable::Mapping(merged.into()));
                 } else {
                     result.insert(key.clone(), value.clone());
                 }
@@ -186,6 +407,11 @@ impl TryFrom<&serde_yaml::Value> for Variable {
                 sequence.map(Variable::Sequence)
             }
             Value::Mapping(m) => {
+                // vault values serialized to JSON, see `EncryptedString`
+                if let (1, Some(Value::String(vaulttext))) = (m.len(), m.get(VAULT_JSON_KEY)) {
+                    return Ok(Variable::Vault(EncryptedString::new(vaulttext)));
+                }
+
                 let map: Result<IndexMap<String, Variable>, _> = m
                     .into_iter()
                     .map(|(k, v)| {
@@ -196,13 +422,49 @@ impl TryFrom<&serde_yaml::Value> for Variable {
                         }
                     })
                     .collect();
-                map.map(|map| Variable::Mapping(Mapping { map }))
+                map.map(|map| Variable::Mapping(map.into()))
+            }
+            Value::Tagged(t) if t.tag == "unsafe" => {
+                Ok(Variable::Unsafe(Box::new(Variable::try_from(&t.value)?)))
             }
+            Value::Tagged(t) if t.tag == "vault" => match &t.value {
+                Value::String(vaulttext) => Ok(Variable::Vault(EncryptedString::new(vaulttext))),
+                _ => bail!("A !vault value must be a string, got: {:?}", t.value),
+            },
             Value::Tagged(t) => bail!("Unsupported type: {:?}", t),
         }
     }
 }
 
+/// The reverse of [`Variable::try_from`], `!unsafe` and `!vault` values keep their tags
+/// so vault encrypted values are not decrypted.
+impl From<&Variable> for serde_yaml::Value {
+    fn from(variable: &Variable) -> Self {
+        match variable {
+            Variable::Null => Value::Null,
+            Variable::Bool(b) => Value::Bool(*b),
+            Variable::Number(Number::Int(i)) => Value::Number((*i).into()),
+            Variable::Number(Number::Float(f)) => Value::Number((*f).into()),
+            Variable::Sequence(s) => Value::Sequence(s.iter().map(Value::from).collect()),
+            Variable::Mapping(m) => Value::Mapping(
+                m.iter()
+                    .map(|(k, v)| (Value::String(k.to_string()), Value::from(v)))
+                    .collect(),
+            ),
+            Variable::String(s) => Value::String(s.to_string()),
+            Variable::Path(p) => Value::String(p.to_string_lossy().to_string()),
+            Variable::Unsafe(v) => Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+                tag: serde_yaml::value::Tag::new("unsafe"),
+                value: Value::from(v.as_ref()),
+            })),
+            Variable::Vault(v) => Value::Tagged(Box::new(serde_yaml::value::TaggedValue {
+                tag: serde_yaml::value::Tag::new("vault"),
+                value: Value::String(v.vaulttext().to_string()),
+            })),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -364,10 +626,7 @@ mod tests {
         expected_map.insert("key1".to_string(), Variable::Bool(true));
         expected_map.insert("key2".to_string(), Variable::Number(Number::Int(42)));
 
-        assert_eq!(
-            result.unwrap(),
-            Variable::Mapping(Mapping { map: expected_map })
-        );
+        assert_eq!(result.unwrap(), Variable::Mapping(expected_map.into()));
     }
 
     #[test]
@@ -390,4 +649,81 @@ mod tests {
         let result = Variable::try_from(&value);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_yaml_value_round_trip_keeps_tags() {
+        let value: Value = serde_yaml::from_str(
+            "port: 22\nratio: 0.5\nraw: !unsafe '{{ x }}'\nsecret: !vault |\n  $ANSIBLE_VAULT;1.1;AES256\n  3132\nlist: [a, true, ~]\n",
+        )
+        .unwrap();
+        let variable = Variable::try_from(&value).unwrap();
+
+        assert_eq!(
+            Variable::try_from(&Value::from(&variable)).unwrap(),
+            variable
+        );
+    }
+
+    #[test]
+    fn test_load_extra_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("extra.yml");
+        std::fs::write(&path, "env: file\nport: 22\n").unwrap();
+
+        let extra_vars = vec![
+            "env=kv port=80".to_string(),
+            r#"{"port": 8080, "tags": ["a", "b"]}"#.to_string(),
+            format!("@{}", path.display()),
+            "env='from kv'".to_string(),
+        ];
+
+        let vars = load_extra_vars(
+            &extra_vars,
+            &DataLoader::new(),
+            &ConflictResolution::Replace,
+        )
+        .unwrap();
+
+        assert_eq!(vars["env"], Variable::String("from kv".to_string()));
+        assert_eq!(vars["port"], Variable::Number(Number::Int(22)));
+        assert_eq!(
+            vars["tags"],
+            Variable::Sequence(vec![
+                Variable::String("a".to_string()),
+                Variable::String("b".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_load_extra_vars_invalid() {
+        let loader = DataLoader::new();
+        let invalid = ["[1, 2]", "@", "", "verbose"];
+
+        for extra_vars_opt in invalid {
+            let result = load_extra_vars(
+                &[extra_vars_opt.to_string()],
+                &loader,
+                &ConflictResolution::Replace,
+            );
+            assert!(result.is_err(), "'{}' should be rejected", extra_vars_opt);
+        }
+    }
+
+    #[test]
+    fn test_get_inventory_vars_dirs_skips_host_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let inventory_dir = dir.path().join("site,eu");
+        std::fs::create_dir(&inventory_dir).unwrap();
+        let inventory = inventory_dir.join("hosts.ini");
+        std::fs::write(&inventory, "web1\n").unwrap();
+
+        let sources = vec![
+            "web1,web2:2222,".to_string(),
+            inventory.to_string_lossy().to_string(),
+        ];
+        let dirs = get_inventory_vars_dirs(Some(&sources)).unwrap();
+
+        assert_eq!(dirs, vec![inventory_dir]);
+    }
 }
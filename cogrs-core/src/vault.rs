@@ -1,8 +1,39 @@
 pub mod aes256;
+pub mod editor;
+pub mod encrypted;
+pub mod secrets;
 
 use crate::constants::VAULT_HEADER;
-use crate::vault::aes256::AES256;
-use anyhow::{bail, Result};
+use crate::vault::aes256::{DecryptReader, AES256};
+use crate::vault::secrets::VaultSecrets;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Vault text is wrapped at this width, like ansible does.
+const VAULT_LINE_WIDTH: usize = 80;
+const DEFAULT_VAULT_ID: &str = "default";
+const DEFAULT_CIPHER: &str = "AES256";
+
+/// Builds the vault envelope: the header line followed by the ciphertext wrapped at 80
+/// columns. Version 1.2 headers carry the vault id, unless it is the default one.
+fn format_vaulttext_envelope(ciphertext: &str, cipher: &str, vault_id: Option<&str>) -> String {
+    let mut envelope = match vault_id {
+        Some(vault_id) if vault_id != DEFAULT_VAULT_ID => {
+            format!("{};1.2;{};{}\n", VAULT_HEADER, cipher, vault_id)
+        }
+        _ => format!("{};1.1;{}\n", VAULT_HEADER, cipher),
+    };
+
+    for line in ciphertext.as_bytes().chunks(VAULT_LINE_WIDTH) {
+        // the ciphertext is hex encoded, chunks are always valid utf-8
+        envelope.push_str(&String::from_utf8_lossy(line));
+        envelope.push('\n');
+    }
+
+    envelope
+}
 
 #[derive(Default, Debug)]
 pub struct Vault {}
@@ -45,11 +76,50 @@ impl Vault {
             vault_id = Some(parts[3].to_string());
         }
 
-        let remaining_text = lines.collect::<Vec<&str>>().join("\n");
+        let remaining_text = lines.collect::<Vec<&str>>().concat();
 
         Ok((remaining_text, header, version, cipher, vault_id))
     }
 
+    /// Encrypts `plaintext` into vault text compatible with `ansible-vault` (format 1.1,
+    /// or 1.2 when a non default vault id is given).
+    pub fn encrypt(&self, plaintext: &str, secret: &str, vault_id: Option<&str>) -> Result<String> {
+        self.encrypt_bytes(plaintext.as_bytes(), secret, vault_id)
+    }
+
+    pub fn encrypt_bytes(
+        &self,
+        plaintext: &[u8],
+        secret: &str,
+        vault_id: Option<&str>,
+    ) -> Result<String> {
+        let ciphertext = AES256::encrypt_bytes(plaintext, secret)?;
+        Ok(format_vaulttext_envelope(
+            &ciphertext,
+            DEFAULT_CIPHER,
+            vault_id,
+        ))
+    }
+
+    /// Encrypts everything read from `input` into vault text written to `output`,
+    /// without buffering the whole input.
+    pub fn encrypt_stream<R: Read + Seek, W: Write>(
+        &self,
+        input: R,
+        mut output: W,
+        secret: &str,
+        vault_id: Option<&str>,
+    ) -> Result<()> {
+        output.write_all(format_vaulttext_envelope("", DEFAULT_CIPHER, vault_id).as_bytes())?;
+        Ok(AES256::encrypt_stream(input, output, secret)?)
+    }
+
+    /// Returns the vault id from a 1.2 header, `None` for 1.1 vault text.
+    pub fn vault_id(&self, data: &str) -> Result<Option<String>> {
+        let (_, _, _, _, vault_id) = self.parse_vaulttext_envelope(data)?;
+        Ok(vault_id)
+    }
+
     pub fn decrypt(&self, data: &str, secret: &str) -> Result<String> {
         let (vault_text, _header, _version, cipher, _vault_id) =
             self.parse_vaulttext_envelope(data)?;
@@ -61,6 +131,172 @@ impl Vault {
 
         Ok(plain_text)
     }
+
+    /// Decrypts vault text into raw bytes, for binary content such as keystores.
+    pub fn decrypt_bytes(&self, data: &str, secret: &str) -> Result<Vec<u8>> {
+        let (vault_text, _header, _version, cipher, _vault_id) =
+            self.parse_vaulttext_envelope(data)?;
+
+        match cipher.as_str() {
+            "AES256" => Ok(AES256::decrypt_bytes(&vault_text, secret)?),
+            _ => bail!("Unsupported cipher: {}", cipher),
+        }
+    }
+
+    /// Checks streamed data for the vault header, `input` is left where it was.
+    pub fn is_encrypted_stream<R: Read + Seek>(&self, input: &mut R) -> Result<bool> {
+        let start = input.stream_position()?;
+        let mut header = Vec::with_capacity(VAULT_HEADER.len());
+        input
+            .by_ref()
+            .take(VAULT_HEADER.len() as u64)
+            .read_to_end(&mut header)?;
+        input.seek(SeekFrom::Start(start))?;
+
+        Ok(header == VAULT_HEADER.as_bytes())
+    }
+
+    /// Reads the header line of streamed vault text, leaving `input` at the vault body.
+    fn read_stream_header<R: Read>(&self, input: &mut R) -> Result<(String, Option<String>)> {
+        let mut header = Vec::new();
+        let mut byte = [0u8];
+
+        while input.read(&mut byte)? == 1 && byte[0] != b'\n' {
+            if header.len() > VAULT_LINE_WIDTH {
+                bail!("Invalid vault text header format");
+            }
+            header.push(byte[0]);
+        }
+
+        let header = String::from_utf8(header).context("Invalid vault text header format")?;
+        if !self.is_encrypted(&header) {
+            bail!("Invalid vault text header format");
+        }
+        let (_, _, _, cipher, vault_id) = self.parse_vaulttext_envelope(&header)?;
+
+        Ok((cipher, vault_id))
+    }
+
+    /// Returns the vault id of streamed vault text, `input` is left where it was.
+    pub fn stream_vault_id<R: Read + Seek>(&self, input: &mut R) -> Result<Option<String>> {
+        let start = input.stream_position()?;
+        let (_, vault_id) = self.read_stream_header(input)?;
+        input.seek(SeekFrom::Start(start))?;
+
+        Ok(vault_id)
+    }
+
+    /// Checks that `secret` decrypts the streamed vault text, `input` is left where it was.
+    pub fn verify_stream<R: Read + Seek>(&self, input: &mut R, secret: &str) -> Result<()> {
+        let start = input.stream_position()?;
+        let (cipher, _) = self.read_stream_header(input)?;
+        if cipher != DEFAULT_CIPHER {
+            bail!("Unsupported cipher: {}", cipher);
+        }
+
+        AES256::verify_stream(input, secret)?;
+        input.seek(SeekFrom::Start(start))?;
+
+        Ok(())
+    }
+
+    /// Returns a reader decrypting the vault text read from `input` on the fly, so large
+    /// files never have to be fully buffered nor their plaintext written to disk.
+    pub fn decrypt_stream<R: Read + Seek>(
+        &self,
+        mut input: R,
+        secret: &str,
+    ) -> Result<DecryptReader<R>> {
+        let (cipher, _) = self.read_stream_header(&mut input)?;
+        if cipher != DEFAULT_CIPHER {
+            bail!("Unsupported cipher: {}", cipher);
+        }
+
+        Ok(AES256::decrypt_stream(input, secret)?)
+    }
+}
+
+/// Opens a local file to upload to a remote host, such as the source of a copy. Vault
+/// encrypted files are decrypted on the fly with `secrets`, their plaintext is never
+/// written to disk.
+pub fn open_transfer_source(path: &Path, secrets: &VaultSecrets) -> Result<Box<dyn Read + Send>> {
+    let mut file =
+        fs::File::open(path).with_context(|| format!("Unable to read {}", path.display()))?;
+
+    if !Vault::new().is_encrypted_stream(&mut file)? {
+        return Ok(Box::new(file));
+    }
+
+    let plaintext = secrets
+        .decrypt_stream(file)
+        .with_context(|| format!("Unable to decrypt {}", path.display()))?;
+    Ok(Box::new(plaintext))
+}
+
+/// Reads a vault password from a file, ignoring surrounding whitespace.
+pub fn read_vault_password_file(path: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        anyhow::format_err!(
+            "Unable to read vault password file {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let password = content.trim();
+
+    if password.is_empty() {
+        bail!("Vault password file {} is empty", path.display());
+    }
+
+    Ok(password.to_string())
+}
+
+/// Prompts for a vault password on the terminal, without echoing it.
+pub fn prompt_vault_password(prompt: &str) -> Result<String> {
+    let mut tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .context("Unable to prompt for a vault password, no terminal available")?;
+
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+
+    #[cfg(unix)]
+    let original = {
+        use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg};
+
+        let original = tcgetattr(&tty)?;
+        let mut no_echo = original.clone();
+        no_echo.local_flags.remove(LocalFlags::ECHO);
+        tcsetattr(&tty, SetArg::TCSANOW, &no_echo)?;
+        original
+    };
+
+    let mut password = String::new();
+    let result = BufReader::new(&tty).read_line(&mut password);
+
+    #[cfg(unix)]
+    nix::sys::termios::tcsetattr(&tty, nix::sys::termios::SetArg::TCSANOW, &original)?;
+    writeln!(tty)?;
+
+    result?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Prompts for a new vault password twice, failing if the passwords differ or are empty.
+pub fn prompt_new_vault_password(prompt: &str) -> Result<String> {
+    let password = prompt_vault_password(prompt)?;
+    let confirmation = prompt_vault_password(&format!("Confirm {}", prompt))?;
+
+    if password != confirmation {
+        bail!("Passwords do not match");
+    }
+    if password.is_empty() {
+        bail!("A vault password must be specified");
+    }
+
+    Ok(password)
 }
 
 #[cfg(test)]
@@ -153,4 +389,95 @@ mod tests {
             "Data integrity compromised: Failed to verify HMAC tag"
         );
     }
+
+    // generated by ansible's VaultLib with a salt of bytes 0..32 and "cogrs-secret"
+    const ANSIBLE_VAULT_1_1: &str = "$ANSIBLE_VAULT;1.1;AES256
+30303031303230333034303530363037303830393061306230633064306530663130313131323133
+3134313531363137313831393161316231633164316531660a656631333732663762613134353937
+32386465383039363466643630636134346230366537323038336134313634346364616662643734
+6431616662623133300a376134663761316562306264336466623662633236666335333538383135
+33303630616332653634396237613264373834646465653331383432343861633733
+";
+    const ANSIBLE_VAULT_1_2: &str = "$ANSIBLE_VAULT;1.2;AES256;prod
+30303031303230333034303530363037303830393061306230633064306530663130313131323133
+3134313531363137313831393161316231633164316531660a623362643730623138613261373535
+33643637336563663765626663323634323136653032376462316431363831643264323335663835
+3830633230663764340a363632323261363164656331343138333062626630346630316165663666
+3531
+";
+
+    #[test]
+    fn test_encrypt_is_ansible_compatible() {
+        let salt: Vec<u8> = (0..32).collect();
+
+        let ciphertext =
+            AES256::encrypt_with_salt(b"db_password: hunter2\n", "cogrs-secret", &salt).unwrap();
+        assert_eq!(
+            format_vaulttext_envelope(&ciphertext, DEFAULT_CIPHER, None),
+            ANSIBLE_VAULT_1_1
+        );
+
+        let ciphertext = AES256::encrypt_with_salt(b"x", "cogrs-secret", &salt).unwrap();
+        assert_eq!(
+            format_vaulttext_envelope(&ciphertext, DEFAULT_CIPHER, Some("prod")),
+            ANSIBLE_VAULT_1_2
+        );
+    }
+
+    #[test]
+    fn test_open_transfer_source_decrypts_binary_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let secret = secrets::VaultSecret::new("certs", "cert-secret");
+        let mut secrets = VaultSecrets::new();
+        secrets.add(secret.clone());
+
+        let plain_path = dir.path().join("keystore.jks");
+        fs::write(&plain_path, &data).unwrap();
+        let vault_path = dir.path().join("keystore.jks.vault");
+        Vault::new()
+            .encrypt_stream(
+                fs::File::open(&plain_path).unwrap(),
+                fs::File::create(&vault_path).unwrap(),
+                secret.secret(),
+                Some(secret.vault_id()),
+            )
+            .unwrap();
+
+        for path in [&plain_path, &vault_path] {
+            let mut transferred = Vec::new();
+            open_transfer_source(path, &secrets)
+                .unwrap()
+                .read_to_end(&mut transferred)
+                .unwrap();
+            assert_eq!(transferred, data);
+        }
+
+        assert_eq!(
+            secrets
+                .decrypt_bytes(&fs::read_to_string(&vault_path).unwrap())
+                .unwrap(),
+            data
+        );
+        assert!(open_transfer_source(&vault_path, &VaultSecrets::new()).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_ansible_vault() {
+        let vault = Vault::new();
+
+        assert_eq!(
+            vault.decrypt(ANSIBLE_VAULT_1_1, "cogrs-secret").unwrap(),
+            "db_password: hunter2\n"
+        );
+        assert_eq!(
+            vault.decrypt(ANSIBLE_VAULT_1_2, "cogrs-secret").unwrap(),
+            "x"
+        );
+        assert_eq!(vault.vault_id(ANSIBLE_VAULT_1_1).unwrap(), None);
+        assert_eq!(
+            vault.vault_id(ANSIBLE_VAULT_1_2).unwrap().as_deref(),
+            Some("prod")
+        );
+    }
 }
@@ -1,3 +1,7 @@
+mod stream;
+
+pub use stream::DecryptReader;
+
 use aes::Aes256;
 use cipher::{KeyIvInit, StreamCipher};
 use ctr::Ctr128BE;
@@ -16,6 +20,7 @@ const KEY_LEN: usize = 32; // AES-256 and HMAC-SHA256 require 32-byte keys
 const IV_LEN: usize = 16; // AES-CTR uses a 16-byte initialization vector
 const PBKDF2_ITERATIONS: u32 = 10_000;
 const HMAC_LEN: usize = 32; // For SHA-256, the output size is always 32 bytes
+const BLOCK_LEN: usize = 16; // AES block size, ansible pads the plaintext to it (PKCS7)
 
 pub struct HexUtils;
 
@@ -116,8 +121,38 @@ pub struct AES256;
 pub type Aes256Ctr = Ctr128BE<Aes256>;
 
 impl AES256 {
+    /// PKCS7 padding, as done by ansible before the (stream) encryption.
+    fn pad(data: &[u8]) -> Vec<u8> {
+        let padding = BLOCK_LEN - data.len() % BLOCK_LEN;
+        let mut padded = Vec::with_capacity(data.len() + padding);
+        padded.extend_from_slice(data);
+        padded.resize(data.len() + padding, padding as u8);
+        padded
+    }
+
+    fn unpad(mut data: Vec<u8>) -> Result<Vec<u8>, AES256Error> {
+        let padding = data.last().copied().unwrap_or(0) as usize;
+
+        if padding == 0
+            || padding > BLOCK_LEN
+            || padding > data.len()
+            || !data[data.len() - padding..]
+                .iter()
+                .all(|&b| b as usize == padding)
+        {
+            return Err(AES256Error::InvalidFormat(
+                "Invalid padding in decrypted data".to_string(),
+            ));
+        }
+
+        data.truncate(data.len() - padding);
+        Ok(data)
+    }
+
     fn parse_encrypted_data(data: &str) -> Result<ParsedEncryptedData, AES256Error> {
-        let encrypted_bytes = HexUtils::decode(data)?;
+        // vault text is wrapped at 80 columns, the line breaks are not part of the data
+        let data: String = data.split_ascii_whitespace().collect();
+        let encrypted_bytes = HexUtils::decode(&data)?;
         if encrypted_bytes.len() < SALT_LEN + HMAC_LEN + 1 {
             Err(AES256Error::InvalidFormat(
                 "Encrypted data is too short to contain salt, HMAC, and ciphertext".to_string(),
@@ -143,6 +178,12 @@ impl AES256 {
     }
 
     pub fn decrypt_aes256(data: &str, secret: &str) -> Result<String, AES256Error> {
+        let plaintext = AES256::decrypt_bytes(data, secret)?;
+        String::from_utf8(plaintext).map_err(|_| AES256Error::InvalidUtf8Data)
+    }
+
+    /// Decrypts a vault body into raw bytes, for binary content such as keystores.
+    pub fn decrypt_bytes(data: &str, secret: &str) -> Result<Vec<u8>, AES256Error> {
         // Decode hex-encoded data
         let parsed_encrypted_data = AES256::parse_encrypted_data(data)?;
         let derived_keys =
@@ -159,22 +200,32 @@ impl AES256 {
 
         let mut plaintext = parsed_encrypted_data.ciphertext.to_vec();
         cipher.apply_keystream(&mut plaintext);
-
-        // Convert to string
-        String::from_utf8(plaintext).map_err(|_| AES256Error::InvalidUtf8Data)
+        AES256::unpad(plaintext)
     }
 
     pub fn encrypt_aes256(data: &str, secret: &str) -> Result<String, AES256Error> {
+        AES256::encrypt_bytes(data.as_bytes(), secret)
+    }
+
+    pub fn encrypt_bytes(data: &[u8], secret: &str) -> Result<String, AES256Error> {
         let mut salt = [0u8; SALT_LEN];
         OsRng
             .try_fill_bytes(&mut salt)
             .or(Err(AES256Error::RngError))?;
 
-        let derived_keys = KeyDeriver::derive(secret.as_bytes(), salt.as_slice())?;
+        AES256::encrypt_with_salt(data, secret, &salt)
+    }
+
+    pub(crate) fn encrypt_with_salt(
+        data: &[u8],
+        secret: &str,
+        salt: &[u8],
+    ) -> Result<String, AES256Error> {
+        let derived_keys = KeyDeriver::derive(secret.as_bytes(), salt)?;
 
         let mut cipher = Aes256Ctr::new((&derived_keys.key1).into(), (&derived_keys.iv).into());
 
-        let mut ciphertext = data.as_bytes().to_vec();
+        let mut ciphertext = AES256::pad(data);
         cipher.apply_keystream(&mut ciphertext);
 
         // Compute HMAC-SHA256 for the ciphertext
@@ -186,7 +237,7 @@ impl AES256 {
         // Combine salt, HMAC, and ciphertext, separated by newlines
         let result = format!(
             "{}\n{}\n{}",
-            HexUtils::encode(&salt),
+            HexUtils::encode(salt),
             HexUtils::encode(&hmac_tag),
             HexUtils::encode(&ciphertext)
         );
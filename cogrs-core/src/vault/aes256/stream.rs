@@ -0,0 +1,437 @@
+//! Streaming variants of the AES256 vault cipher, so large (binary) files are never fully
+//! buffered. The vault body is hex encoded twice, the outer layer wrapped at 80 columns:
+//! `hex(hex(salt) \n hex(hmac) \n hex(ciphertext))`. The HMAC comes before the ciphertext,
+//! so both directions read their input twice: once for the HMAC, once for the data.
+
+use super::{
+    AES256Error, Aes256Ctr, DerivedKeys, HexUtils, KeyDeriver, AES256, BLOCK_LEN, SALT_LEN,
+};
+use cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, KeyInit, Mac};
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use sha2::Sha256;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+const CHUNK_LEN: usize = 64 * 1024;
+const VAULT_LINE_WIDTH: usize = 80;
+/// Hex encoded salts and HMAC tags are 64 characters, anything much longer is not vault data.
+const MAX_FIELD_LEN: usize = 256;
+
+impl From<io::Error> for AES256Error {
+    fn from(e: io::Error) -> Self {
+        AES256Error::InvalidFormat(e.to_string())
+    }
+}
+
+/// Hex encodes everything written to it, optionally wrapping lines at `width` characters.
+struct HexWriter<W: Write> {
+    inner: W,
+    width: Option<usize>,
+    column: usize,
+}
+
+impl<W: Write> HexWriter<W> {
+    fn new(inner: W, width: Option<usize>) -> Self {
+        HexWriter {
+            inner,
+            width,
+            column: 0,
+        }
+    }
+
+    /// Terminates the last line, every line of vault text ends with a newline.
+    fn finish(mut self) -> io::Result<W> {
+        if self.column > 0 {
+            self.inner.write_all(b"\n")?;
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let encoded = HexUtils::encode(buf);
+
+        match self.width {
+            None => self.inner.write_all(encoded.as_bytes())?,
+            Some(width) => {
+                let mut encoded = encoded.as_bytes();
+                while !encoded.is_empty() {
+                    let len = encoded.len().min(width - self.column);
+                    self.inner.write_all(&encoded[..len])?;
+                    self.column += len;
+                    encoded = &encoded[len..];
+
+                    if self.column == width {
+                        self.inner.write_all(b"\n")?;
+                        self.column = 0;
+                    }
+                }
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decodes hex read from `inner`, the outer layer of vault text also skips line breaks.
+struct HexReader<R: Read> {
+    inner: R,
+    skip_whitespace: bool,
+    nibble: Option<u8>,
+}
+
+impl<R: Read> HexReader<R> {
+    fn new(inner: R, skip_whitespace: bool) -> Self {
+        HexReader {
+            inner,
+            skip_whitespace,
+            nibble: None,
+        }
+    }
+
+    /// Reads up to and excluding the next newline, used for the salt and HMAC fields.
+    fn read_field(&mut self) -> Result<Vec<u8>, AES256Error> {
+        let mut field = Vec::new();
+        let mut byte = [0u8];
+
+        loop {
+            if self.read(&mut byte)? == 0 {
+                return Err(AES256Error::InvalidFormat(
+                    "Encrypted data is missing salt, hmac_tag or ciphertext".to_string(),
+                ));
+            }
+            if byte[0] == b'\n' {
+                return HexUtils::decode(std::str::from_utf8(&field)?);
+            }
+            if field.len() > MAX_FIELD_LEN {
+                return Err(AES256Error::InvalidFormat(
+                    "Encrypted data has an invalid salt or hmac_tag".to_string(),
+                ));
+            }
+            field.push(byte[0]);
+        }
+    }
+}
+
+fn hex_value(c: u8) -> io::Result<u8> {
+    (c as char)
+        .to_digit(16)
+        .map(|value| value as u8)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid hex character: {:?}", c as char),
+            )
+        })
+}
+
+impl<R: Read> Read for HexReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut raw = vec![0u8; (buf.len() * 2).min(CHUNK_LEN)];
+        loop {
+            let len = self.inner.read(&mut raw)?;
+            if len == 0 {
+                if self.nibble.is_some() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Hex string has an invalid length. Must be even.",
+                    ));
+                }
+                return Ok(0);
+            }
+
+            let mut written = 0;
+            for &c in &raw[..len] {
+                if self.skip_whitespace && c.is_ascii_whitespace() {
+                    continue;
+                }
+                let value = hex_value(c)?;
+                match self.nibble.take() {
+                    Some(high) => {
+                        buf[written] = high << 4 | value;
+                        written += 1;
+                    }
+                    None => self.nibble = Some(value),
+                }
+            }
+
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+/// The inner hex layer over the outer one, yields the raw ciphertext.
+type CiphertextReader<R> = HexReader<HexReader<R>>;
+
+/// Reads as much as possible into `buf`, returns less than its length only at the end.
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match input.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+/// Encrypts `input` chunk by chunk, PKCS7 padding the last one, passing each ciphertext
+/// chunk to `f`.
+fn encrypt_chunks<R: Read>(
+    input: &mut R,
+    keys: &DerivedKeys,
+    mut f: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut cipher = Aes256Ctr::new((&keys.key1).into(), (&keys.iv).into());
+    let mut buf = vec![0u8; CHUNK_LEN + BLOCK_LEN];
+    let mut total = 0;
+
+    loop {
+        let len = read_full(input, &mut buf[..CHUNK_LEN])?;
+        total += len;
+
+        if len < CHUNK_LEN {
+            let padding = BLOCK_LEN - total % BLOCK_LEN;
+            buf[len..len + padding].fill(padding as u8);
+            let chunk = &mut buf[..len + padding];
+            cipher.apply_keystream(chunk);
+            return f(chunk);
+        }
+
+        cipher.apply_keystream(&mut buf[..len]);
+        f(&buf[..len])?;
+    }
+}
+
+/// Decrypts vault ciphertext as it is read. The last block is held back until the end
+/// of the input, so its padding can be removed.
+pub struct DecryptReader<R: Read> {
+    ciphertext: CiphertextReader<BufReader<R>>,
+    cipher: Aes256Ctr,
+    plaintext: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    fn fill(&mut self) -> io::Result<()> {
+        while !self.done && self.plaintext.len() - self.pos <= BLOCK_LEN {
+            if self.pos > CHUNK_LEN {
+                self.plaintext.drain(..self.pos);
+                self.pos = 0;
+            }
+
+            let start = self.plaintext.len();
+            self.plaintext.resize(start + CHUNK_LEN, 0);
+            let len = self.ciphertext.read(&mut self.plaintext[start..])?;
+            self.plaintext.truncate(start + len);
+
+            if len == 0 {
+                self.done = true;
+                self.unpad()?;
+            } else {
+                self.cipher.apply_keystream(&mut self.plaintext[start..]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unpad(&mut self) -> io::Result<()> {
+        let remaining = self.plaintext.len() - self.pos;
+        let padding = self.plaintext.last().copied().unwrap_or(0) as usize;
+
+        if padding == 0
+            || padding > BLOCK_LEN
+            || padding > remaining
+            || !self.plaintext[self.plaintext.len() - padding..]
+                .iter()
+                .all(|&b| b as usize == padding)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid padding in decrypted data",
+            ));
+        }
+
+        self.plaintext.truncate(self.plaintext.len() - padding);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill()?;
+
+        let available = match self.done {
+            true => self.plaintext.len(),
+            false => self.plaintext.len() - BLOCK_LEN,
+        };
+        let len = buf.len().min(available - self.pos);
+        buf[..len].copy_from_slice(&self.plaintext[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// A vault body whose salt and HMAC were parsed, positioned at the ciphertext.
+struct VaultBody<R: Read> {
+    salt: Vec<u8>,
+    hmac_tag: Vec<u8>,
+    ciphertext: CiphertextReader<R>,
+}
+
+impl<R: Read> VaultBody<R> {
+    fn parse(input: R) -> Result<Self, AES256Error> {
+        let mut outer = HexReader::new(input, true);
+        let salt = outer.read_field()?;
+        let hmac_tag = outer.read_field()?;
+
+        Ok(VaultBody {
+            salt,
+            hmac_tag,
+            ciphertext: HexReader::new(outer, false),
+        })
+    }
+}
+
+impl AES256 {
+    /// Encrypts everything read from `input` and writes the vault body (without the
+    /// `$ANSIBLE_VAULT` header) to `output`, wrapped at 80 columns.
+    pub fn encrypt_stream<R: Read + Seek, W: Write>(
+        mut input: R,
+        output: W,
+        secret: &str,
+    ) -> Result<(), AES256Error> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng
+            .try_fill_bytes(&mut salt)
+            .or(Err(AES256Error::RngError))?;
+
+        let keys = KeyDeriver::derive(secret.as_bytes(), &salt)?;
+        let start = input.stream_position()?;
+
+        let mut hmac = Hmac::<Sha256>::new_from_slice(keys.key2.as_slice())
+            .map_err(|_| AES256Error::HmacFailure)?;
+        encrypt_chunks(&mut input, &keys, |chunk| {
+            hmac.update(chunk);
+            Ok(())
+        })?;
+        let hmac_tag = hmac.finalize().into_bytes();
+
+        input.seek(SeekFrom::Start(start))?;
+
+        let mut outer = HexWriter::new(output, Some(VAULT_LINE_WIDTH));
+        write!(
+            outer,
+            "{}\n{}\n",
+            HexUtils::encode(&salt),
+            HexUtils::encode(&hmac_tag)
+        )?;
+
+        let mut inner = HexWriter::new(outer, None);
+        encrypt_chunks(&mut input, &keys, |chunk| inner.write_all(chunk))?;
+        inner.finish()?.finish()?.flush()?;
+
+        Ok(())
+    }
+
+    /// Checks the HMAC of the vault body read from `input`, then seeks back to where
+    /// the body started. Used to find the secret of a vault before decrypting it.
+    pub fn verify_stream<R: Read + Seek>(input: &mut R, secret: &str) -> Result<(), AES256Error> {
+        let start = input.stream_position()?;
+        let mut body = VaultBody::parse(BufReader::new(&mut *input))?;
+        let keys = KeyDeriver::derive(secret.as_bytes(), &body.salt)?;
+
+        let mut hmac = Hmac::<Sha256>::new_from_slice(keys.key2.as_slice())
+            .map_err(|_| AES256Error::HmacFailure)?;
+        let mut buf = vec![0u8; CHUNK_LEN];
+        loop {
+            match body.ciphertext.read(&mut buf)? {
+                0 => break,
+                len => hmac.update(&buf[..len]),
+            }
+        }
+        hmac.verify_slice(&body.hmac_tag)
+            .map_err(|_| AES256Error::IntegrityError("Failed to verify HMAC tag".to_string()))?;
+
+        input.seek(SeekFrom::Start(start))?;
+        Ok(())
+    }
+
+    /// Verifies the vault body read from `input` and returns a reader decrypting it
+    /// on the fly.
+    pub fn decrypt_stream<R: Read + Seek>(
+        mut input: R,
+        secret: &str,
+    ) -> Result<DecryptReader<R>, AES256Error> {
+        AES256::verify_stream(&mut input, secret)?;
+
+        let body = VaultBody::parse(BufReader::new(input))?;
+        let keys = KeyDeriver::derive(secret.as_bytes(), &body.salt)?;
+
+        Ok(DecryptReader {
+            ciphertext: body.ciphertext,
+            cipher: Aes256Ctr::new((&keys.key1).into(), (&keys.iv).into()),
+            plaintext: Vec::new(),
+            pos: 0,
+            done: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn encrypt_stream(data: &[u8], secret: &str) -> String {
+        let mut output = Vec::new();
+        AES256::encrypt_stream(Cursor::new(data), &mut output, secret).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn decrypt_stream(vaulttext: &str, secret: &str) -> Result<Vec<u8>, AES256Error> {
+        let mut plaintext = Vec::new();
+        AES256::decrypt_stream(Cursor::new(vaulttext.as_bytes()), secret)?
+            .read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn test_stream_round_trip_binary_data() {
+        // a few sizes around the block and chunk boundaries
+        for len in [0, 1, 15, 16, 17, CHUNK_LEN - 1, CHUNK_LEN, CHUNK_LEN + 16] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 7 % 256) as u8).collect();
+            let vaulttext = encrypt_stream(&data, "secret");
+
+            assert!(vaulttext.lines().all(|line| line.len() <= VAULT_LINE_WIDTH));
+            assert!(vaulttext.ends_with('\n'));
+            assert_eq!(decrypt_stream(&vaulttext, "secret").unwrap(), data);
+            assert_eq!(AES256::decrypt_bytes(&vaulttext, "secret").unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_stream_matches_buffered_format() {
+        let data = b"\x00\xffkeystore\x80";
+        let vaulttext = AES256::encrypt_bytes(data, "secret").unwrap();
+        assert_eq!(decrypt_stream(&vaulttext, "secret").unwrap(), data);
+
+        let err = decrypt_stream(&vaulttext, "wrong").unwrap_err();
+        assert!(matches!(err, AES256Error::IntegrityError(_)), "{:?}", err);
+    }
+}
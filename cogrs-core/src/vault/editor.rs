@@ -0,0 +1,428 @@
+use crate::vault::secrets::{VaultSecret, VaultSecrets};
+use crate::vault::Vault;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, BufWriter, Cursor, Read, Seek, Write};
+use std::path::Path;
+use std::process::Command;
+
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Reads, transforms and writes vault encrypted files, the backend of `cogrs vault`.
+/// A path of `-` stands for stdin when reading and stdout when writing.
+pub struct VaultEditor {
+    vault: Vault,
+    editor: String,
+}
+
+impl Default for VaultEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VaultEditor {
+    pub fn new() -> Self {
+        VaultEditor {
+            vault: Vault::new(),
+            editor: std::env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string()),
+        }
+    }
+
+    /// Overrides the editor command (`$EDITOR` by default) used by `edit_file`.
+    pub fn editor(mut self, editor: &str) -> Self {
+        self.editor = editor.to_string();
+        self
+    }
+
+    pub fn encrypt_file(
+        &self,
+        path: &Path,
+        secret: &VaultSecret,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        let mut input = open_input(path)?;
+
+        if self.vault.is_encrypted_stream(&mut input)? {
+            bail!("input is already encrypted");
+        }
+
+        write_output(output.unwrap_or(path), |out| {
+            self.vault
+                .encrypt_stream(input, out, secret.secret(), Some(secret.vault_id()))
+        })
+    }
+
+    pub fn decrypt_file(
+        &self,
+        path: &Path,
+        secrets: &VaultSecrets,
+        output: Option<&Path>,
+    ) -> Result<()> {
+        let mut input = open_input(path)?;
+
+        if !self.vault.is_encrypted_stream(&mut input)? {
+            bail!("input is not vault encrypted data for {}", path.display());
+        }
+
+        // binary content is decrypted as it is written, never fully buffered
+        let mut plaintext = secrets
+            .decrypt_stream(input)
+            .with_context(|| format!("Decryption failed on {}", path.display()))?;
+        write_output(output.unwrap_or(path), |out| {
+            std::io::copy(&mut plaintext, out)?;
+            Ok(())
+        })
+    }
+
+    /// Returns the decrypted content of a vault file, used by `view`.
+    pub fn plaintext(&self, path: &Path, secrets: &VaultSecrets) -> Result<String> {
+        self.decrypt(path, secrets).map(|(plaintext, _)| plaintext)
+    }
+
+    fn decrypt<'a>(
+        &self,
+        path: &Path,
+        secrets: &'a VaultSecrets,
+    ) -> Result<(String, &'a VaultSecret)> {
+        let vaulttext = read_data(path)?;
+
+        if !self.vault.is_encrypted(&vaulttext) {
+            bail!("input is not vault encrypted data for {}", path.display());
+        }
+
+        secrets
+            .decrypt_with_secret(&vaulttext)
+            .with_context(|| format!("Decryption failed on {}", path.display()))
+    }
+
+    /// Decrypts a vault file into a private temporary file, opens it in the editor and
+    /// encrypts the result back with the secret that decrypted it. The file is only
+    /// rewritten if its content changed.
+    pub fn edit_file(&self, path: &Path, secrets: &VaultSecrets) -> Result<()> {
+        let (plaintext, secret) = self.decrypt(path, secrets)?;
+
+        // tempfile creates the file with 0600 permissions and a random name
+        let mut builder = tempfile::Builder::new();
+        builder.prefix("cogrs-vault-");
+        let suffix = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()));
+        if let Some(suffix) = &suffix {
+            builder.suffix(suffix);
+        }
+        let mut tmp_file = builder.tempfile()?;
+        tmp_file.write_all(plaintext.as_bytes())?;
+        tmp_file.flush()?;
+
+        let result = self.run_editor(tmp_file.path()).and_then(|_| {
+            fs::read_to_string(tmp_file.path())
+                .with_context(|| format!("Failed to read {}", tmp_file.path().display()))
+        });
+
+        // editors saving by rename leave the file written here behind a new one, both
+        // are overwritten
+        shred(tmp_file.path())?;
+        shred_file(tmp_file.as_file_mut())?;
+        let edited = result?;
+
+        if edited == plaintext {
+            return Ok(());
+        }
+
+        write_data(&secret.encrypt(&edited)?, path)
+    }
+
+    /// Re-encrypts a vault file with a new secret, the file gets the vault id of that secret.
+    pub fn rekey_file(
+        &self,
+        path: &Path,
+        secrets: &VaultSecrets,
+        new_secret: &VaultSecret,
+    ) -> Result<()> {
+        let plaintext = self.plaintext(path, secrets)?;
+        write_data(&new_secret.encrypt(&plaintext)?, path)
+    }
+
+    /// Encrypts a string into an inline `!vault` YAML block, `name` makes it a variable.
+    pub fn encrypt_string(
+        &self,
+        plaintext: &str,
+        secret: &VaultSecret,
+        name: Option<&str>,
+    ) -> Result<String> {
+        Ok(format_ciphertext_yaml(&secret.encrypt(plaintext)?, name))
+    }
+
+    fn run_editor(&self, path: &Path) -> Result<()> {
+        let mut args = shlex::split(&self.editor)
+            .filter(|args| !args.is_empty())
+            .with_context(|| format!("Invalid editor command: {}", self.editor))?;
+        let program = args.remove(0);
+
+        let status = Command::new(&program)
+            .args(args)
+            .arg(path)
+            .status()
+            .with_context(|| format!("Failed to run editor {}", program))?;
+
+        if !status.success() {
+            bail!("Editor {} exited with {}", program, status);
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats vault text as an inline `!vault` YAML block, indented by 10 spaces like ansible.
+pub fn format_ciphertext_yaml(vaulttext: &str, name: Option<&str>) -> String {
+    let mut lines = vec![match name {
+        Some(name) => format!("{}: !vault |", name),
+        None => "!vault |".to_string(),
+    }];
+    lines.extend(vaulttext.lines().map(|line| format!("{:10}{}", "", line)));
+
+    lines.join("\n")
+}
+
+fn read_data(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut data = String::new();
+        std::io::stdin().read_to_string(&mut data)?;
+        return Ok(data);
+    }
+
+    fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opens a file for streaming, stdin is buffered as it can't be read twice.
+fn open_input(path: &Path) -> Result<Box<dyn ReadSeek>> {
+    if path == Path::new("-") {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        return Ok(Box::new(Cursor::new(data)));
+    }
+
+    let file =
+        fs::File::open(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    Ok(Box::new(file))
+}
+
+/// Writes a file through a temporary file in the same directory, replacing the original
+/// only once everything was written. New files are only readable by the owner, existing
+/// files keep their permissions.
+fn write_output(path: &Path, write: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+    if path == Path::new("-") {
+        let mut stdout = std::io::stdout().lock();
+        write(&mut stdout)?;
+        return Ok(stdout.flush()?);
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut tmp_file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Unable to write {}", path.display()))?;
+
+    let mut out = BufWriter::new(tmp_file.as_file_mut());
+    write(&mut out)?;
+    out.flush()?;
+    drop(out);
+
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(tmp_file.path(), metadata.permissions())?;
+    }
+    tmp_file
+        .persist(path)
+        .with_context(|| format!("Unable to write {}", path.display()))?;
+
+    Ok(())
+}
+
+fn write_data(data: &str, path: &Path) -> Result<()> {
+    write_output(path, |out| Ok(out.write_all(data.as_bytes())?))
+}
+
+/// Overwrites the plaintext in a temporary file before it is removed.
+fn shred(path: &Path) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    shred_file(&mut file)
+}
+
+/// Overwrites the whole file with zeros in place, its blocks are not freed before.
+fn shred_file(file: &mut fs::File) -> Result<()> {
+    let len = file.metadata()?.len();
+    file.rewind()?;
+    io::copy(&mut io::repeat(0).take(len), file)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "cogrs-secret";
+
+    fn secrets(vault_id: &str, secret: &str) -> VaultSecrets {
+        let mut secrets = VaultSecrets::new();
+        secrets.add(VaultSecret::new(vault_id, secret));
+        secrets
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_and_rekey_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.yml");
+        fs::write(&path, "db_password: hunter2\n").unwrap();
+        let editor = VaultEditor::new();
+        let default = secrets("default", SECRET);
+        let secret = default.encrypt_secret(None).unwrap();
+
+        editor.encrypt_file(&path, secret, None).unwrap();
+        let vaulttext = fs::read_to_string(&path).unwrap();
+        assert!(vaulttext.starts_with("$ANSIBLE_VAULT;1.1;AES256\n"));
+        assert!(vaulttext.lines().skip(1).all(|line| line.len() <= 80));
+        assert!(editor.encrypt_file(&path, secret, None).is_err());
+
+        let prod = secrets("prod", "new-secret");
+        editor
+            .rekey_file(&path, &default, prod.encrypt_secret(None).unwrap())
+            .unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("$ANSIBLE_VAULT;1.2;AES256;prod\n"));
+        assert!(editor.plaintext(&path, &default).is_err());
+
+        let output = dir.path().join("plain.yml");
+        editor.decrypt_file(&path, &prod, Some(&output)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "db_password: hunter2\n"
+        );
+        assert!(editor.decrypt_file(&output, &prod, None).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_binary_file_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cert.p12");
+        let data: Vec<u8> = (0..=255).rev().cycle().take(70_000).collect();
+        fs::write(&path, &data).unwrap();
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o640)).unwrap();
+        let editor = VaultEditor::new();
+        let default = secrets("default", SECRET);
+
+        editor
+            .encrypt_file(&path, default.encrypt_secret(None).unwrap(), None)
+            .unwrap();
+        let vaulttext = fs::read_to_string(&path).unwrap();
+        assert!(vaulttext.starts_with("$ANSIBLE_VAULT;1.1;AES256\n"));
+        assert_eq!(default.decrypt_bytes(&vaulttext).unwrap(), data);
+
+        editor.decrypt_file(&path, &default, None).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path).unwrap().permissions())
+                & 0o777,
+            0o640
+        );
+    }
+
+    #[test]
+    fn test_edit_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.yml");
+        fs::write(&path, "a: 1\n").unwrap();
+        let editor = VaultEditor::new().editor("sh -c 'echo b: 2 >> \"$0\"'");
+        let dev = secrets("dev", SECRET);
+
+        editor
+            .encrypt_file(&path, dev.encrypt_secret(None).unwrap(), None)
+            .unwrap();
+        editor.edit_file(&path, &dev).unwrap();
+
+        let vaulttext = fs::read_to_string(&path).unwrap();
+        assert!(vaulttext.starts_with("$ANSIBLE_VAULT;1.2;AES256;dev\n"));
+        assert_eq!(editor.plaintext(&path, &dev).unwrap(), "a: 1\nb: 2\n");
+
+        // an unchanged file is not rewritten
+        let unchanged = VaultEditor::new().editor("true");
+        unchanged.edit_file(&path, &dev).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), vaulttext);
+
+        assert!(VaultEditor::new()
+            .editor("false")
+            .edit_file(&path, &dev)
+            .is_err());
+    }
+
+    #[test]
+    fn test_shred() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plain.yml");
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"db_password: hunter2\n").unwrap();
+
+        // the handle is at the end of the plaintext, like in edit_file
+        shred_file(&mut file).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0u8; 21]);
+
+        fs::write(&path, "api_key: secret\n").unwrap();
+        shred(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), vec![0u8; 16]);
+    }
+
+    #[test]
+    fn test_edit_file_saved_by_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.yml");
+        fs::write(&path, "a: 1\n").unwrap();
+        let editor =
+            VaultEditor::new().editor("sh -c 'echo b: 2 > \"$0.new\" && mv \"$0.new\" \"$0\"'");
+        let dev = secrets("dev", SECRET);
+
+        editor
+            .encrypt_file(&path, dev.encrypt_secret(None).unwrap(), None)
+            .unwrap();
+        editor.edit_file(&path, &dev).unwrap();
+
+        assert_eq!(editor.plaintext(&path, &dev).unwrap(), "b: 2\n");
+    }
+
+    #[test]
+    fn test_encrypt_string() {
+        let editor = VaultEditor::new();
+        let yaml = editor
+            .encrypt_string(
+                "hunter2",
+                &VaultSecret::new("default", SECRET),
+                Some("db_password"),
+            )
+            .unwrap();
+
+        let mut lines = yaml.lines();
+        assert_eq!(lines.next(), Some("db_password: !vault |"));
+        assert_eq!(lines.next(), Some("          $ANSIBLE_VAULT;1.1;AES256"));
+
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let serde_yaml::Value::Tagged(tagged) = &value["db_password"] else {
+            panic!("expected a tagged value, got {:?}", value);
+        };
+        assert_eq!(tagged.tag, "vault");
+        assert_eq!(
+            Vault::new()
+                .decrypt(tagged.value.as_str().unwrap(), SECRET)
+                .unwrap(),
+            "hunter2"
+        );
+    }
+}
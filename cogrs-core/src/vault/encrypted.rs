@@ -0,0 +1,113 @@
+use crate::vault::secrets::{vault_secrets, VaultSecrets};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::sync::Arc;
+
+/// The key of vault encrypted values serialized to JSON.
+pub const VAULT_JSON_KEY: &str = "__ansible_vault";
+
+/// A value tagged `!vault`, kept encrypted until it is used. The plaintext is decrypted
+/// once, on first use, so runs that never read the value don't need its vault password.
+#[derive(Clone)]
+pub struct EncryptedString {
+    vaulttext: String,
+    plaintext: Arc<OnceCell<String>>,
+}
+
+impl EncryptedString {
+    pub fn new(vaulttext: &str) -> Self {
+        EncryptedString {
+            vaulttext: vaulttext.to_string(),
+            plaintext: Arc::new(OnceCell::new()),
+        }
+    }
+
+    pub fn vaulttext(&self) -> &str {
+        &self.vaulttext
+    }
+
+    pub fn is_decrypted(&self) -> bool {
+        self.plaintext.get().is_some()
+    }
+
+    /// Decrypts the value with the secrets given on the command line.
+    pub fn decrypt(&self) -> Result<&str> {
+        self.decrypt_with(&vault_secrets())
+    }
+
+    pub fn decrypt_with(&self, secrets: &VaultSecrets) -> Result<&str> {
+        self.plaintext
+            .get_or_try_init(|| secrets.decrypt(&self.vaulttext))
+            .map(String::as_str)
+    }
+}
+
+impl PartialEq for EncryptedString {
+    fn eq(&self, other: &Self) -> bool {
+        self.vaulttext == other.vaulttext
+    }
+}
+
+impl fmt::Debug for EncryptedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // never leak the plaintext into logs
+        f.debug_tuple("EncryptedString")
+            .field(&"<vault encrypted>")
+            .finish()
+    }
+}
+
+/// Serialized like Ansible does in JSON, as `{"__ansible_vault": "<vaulttext>"}`, so task
+/// vars handed to plugins carry the encrypted value and only decrypt it where it is used.
+impl Serialize for EncryptedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(VAULT_JSON_KEY, &self.vaulttext)?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vars::variable::Variable;
+    use crate::vault::secrets::VaultSecret;
+
+    #[test]
+    fn test_decrypts_once_on_first_use() {
+        let secret = VaultSecret::new("dev", "dev-secret");
+        let value = EncryptedString::new(&secret.encrypt("hunter2").unwrap());
+        let copy = value.clone();
+        assert!(!value.is_decrypted());
+        assert!(!format!("{:?}", value).contains("hunter2"));
+
+        assert!(value.decrypt_with(&VaultSecrets::new()).is_err());
+        assert!(!value.is_decrypted());
+
+        let mut secrets = VaultSecrets::new();
+        secrets.add(secret);
+        assert_eq!(value.decrypt_with(&secrets).unwrap(), "hunter2");
+
+        // clones share the decrypted plaintext
+        assert!(copy.is_decrypted());
+        assert_eq!(copy.decrypt_with(&VaultSecrets::new()).unwrap(), "hunter2");
+    }
+
+    #[test]
+    fn test_serializes_the_vaulttext() {
+        let secret = VaultSecret::new("dev", "dev-secret");
+        let vaulttext = secret.encrypt("hunter2").unwrap();
+        let value = EncryptedString::new(&vaulttext);
+
+        let json = serde_json::to_value(&value).unwrap();
+        assert_eq!(json, serde_json::json!({ VAULT_JSON_KEY: vaulttext }));
+        assert!(!value.is_decrypted());
+
+        // serialized values are read back as vault values
+        let variable = Variable::try_from(&serde_yaml::to_value(&json).unwrap()).unwrap();
+        assert_eq!(variable, Variable::Vault(value));
+    }
+}
@@ -0,0 +1,382 @@
+use crate::vault::aes256::DecryptReader;
+use crate::vault::{prompt_vault_password, read_vault_password_file, Vault, DEFAULT_VAULT_ID};
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+
+/// Secrets given on the command line. Inline `!vault` values are decrypted long after
+/// they were loaded, wherever they are first used, so the secrets are kept process wide.
+static VAULT_SECRETS: Lazy<RwLock<Arc<VaultSecrets>>> =
+    Lazy::new(|| RwLock::new(Arc::new(VaultSecrets::new())));
+
+/// Sets the secrets used to decrypt vault encrypted files and inline `!vault` values.
+pub fn set_vault_secrets(secrets: VaultSecrets) {
+    if let Ok(mut vault_secrets) = VAULT_SECRETS.write() {
+        *vault_secrets = Arc::new(secrets);
+    }
+}
+
+pub fn vault_secrets() -> Arc<VaultSecrets> {
+    VAULT_SECRETS
+        .read()
+        .map(|secrets| secrets.clone())
+        .unwrap_or_default()
+}
+
+/// Where the password of a vault id comes from, the part after `@` in `--vault-id label@source`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VaultSecretSource {
+    /// Asks for the password on the terminal.
+    Prompt,
+    /// Reads the password from a file.
+    File(PathBuf),
+    /// Runs an executable and reads the password from its stdout. Scripts named
+    /// `*-client` or `*-client.*` are client scripts, they get `--vault-id <label>`
+    /// so one script can serve the passwords of several vault ids.
+    Script(PathBuf),
+}
+
+impl VaultSecretSource {
+    pub fn parse(source: &str) -> Self {
+        if source == "prompt" {
+            return VaultSecretSource::Prompt;
+        }
+
+        let path = PathBuf::from(source);
+        if is_executable(&path) {
+            VaultSecretSource::Script(path)
+        } else {
+            VaultSecretSource::File(path)
+        }
+    }
+
+    fn load(&self, vault_id: &str) -> Result<String> {
+        match self {
+            VaultSecretSource::Prompt => {
+                let prompt = match vault_id {
+                    DEFAULT_VAULT_ID => "Vault password: ".to_string(),
+                    _ => format!("Vault password ({}): ", vault_id),
+                };
+                prompt_vault_password(&prompt)
+            }
+            VaultSecretSource::File(path) => read_vault_password_file(path),
+            VaultSecretSource::Script(path) => run_password_script(path, vault_id),
+        }
+    }
+}
+
+/// A vault password and the vault id label it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VaultSecret {
+    vault_id: String,
+    secret: String,
+}
+
+impl VaultSecret {
+    pub fn new(vault_id: &str, secret: &str) -> Self {
+        VaultSecret {
+            vault_id: vault_id.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+
+    /// Loads a secret from a `label@source` vault id, a bare source uses the `default` label.
+    pub fn load(vault_id: &str) -> Result<Self> {
+        let (label, source) = match vault_id.split_once('@') {
+            Some((label, source)) => (label, source),
+            None => (DEFAULT_VAULT_ID, vault_id),
+        };
+
+        if label.is_empty() || source.is_empty() {
+            bail!(
+                "Invalid vault id '{}', expected label@source or source",
+                vault_id
+            );
+        }
+
+        let secret = VaultSecretSource::parse(source).load(label)?;
+        if secret.is_empty() {
+            bail!("Empty vault password for vault id '{}'", label);
+        }
+
+        Ok(VaultSecret::new(label, &secret))
+    }
+
+    pub fn vault_id(&self) -> &str {
+        &self.vault_id
+    }
+
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// Encrypts `plaintext`, the vault id is written into the header unless it is `default`.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String> {
+        Vault::new().encrypt(plaintext, &self.secret, Some(&self.vault_id))
+    }
+}
+
+/// The vault passwords known to a run, in the order they were given.
+#[derive(Debug, Clone, Default)]
+pub struct VaultSecrets {
+    secrets: Vec<VaultSecret>,
+}
+
+impl VaultSecrets {
+    pub fn new() -> Self {
+        VaultSecrets::default()
+    }
+
+    /// Collects the secrets of `--vault-id` and `--vault-password-file` options,
+    /// `ask_vault_password` adds a prompt for the `default` vault id.
+    pub fn from_options(
+        vault_ids: &[String],
+        vault_password_files: &[PathBuf],
+        ask_vault_password: bool,
+    ) -> Result<Self> {
+        let mut secrets = VaultSecrets::new();
+
+        if ask_vault_password {
+            secrets.add(VaultSecret::load("prompt")?);
+        }
+        for path in vault_password_files {
+            let secret = read_vault_password_file(path)?;
+            secrets.add(VaultSecret::new(DEFAULT_VAULT_ID, &secret));
+        }
+        for vault_id in vault_ids {
+            secrets.add(VaultSecret::load(vault_id)?);
+        }
+
+        Ok(secrets)
+    }
+
+    pub fn add(&mut self, secret: VaultSecret) {
+        self.secrets.push(secret);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.secrets.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VaultSecret> {
+        self.secrets.iter()
+    }
+
+    /// Returns the secret used for encryption: the one labelled `vault_id` if given,
+    /// otherwise the only secret. Several secrets without a `vault_id` are ambiguous.
+    pub fn encrypt_secret(&self, vault_id: Option<&str>) -> Result<&VaultSecret> {
+        match vault_id {
+            Some(vault_id) => self
+                .secrets
+                .iter()
+                .find(|secret| secret.vault_id == vault_id)
+                .with_context(|| format!("No vault secret found for vault id '{}'", vault_id)),
+            None => match self.secrets.as_slice() {
+                [] => bail!("A vault password must be specified to encrypt data"),
+                [secret] => Ok(secret),
+                _ => bail!(
+                    "The vault id to encrypt with must be specified with --encrypt-vault-id \
+                     when several vault secrets are given"
+                ),
+            },
+        }
+    }
+
+    /// Orders the secrets to try for vault text: those whose label matches the vault id of
+    /// its header first, then every other secret, so vault text encrypted without a label
+    /// can still be read.
+    fn candidates(&self, vault_id: Option<String>) -> Result<(String, Vec<&VaultSecret>)> {
+        if self.secrets.is_empty() {
+            bail!("A vault password must be specified to decrypt data");
+        }
+
+        let vault_id = vault_id.unwrap_or_else(|| DEFAULT_VAULT_ID.to_string());
+        let (mut matching, others): (Vec<_>, Vec<_>) = self
+            .secrets
+            .iter()
+            .partition(|secret| secret.vault_id == vault_id);
+        matching.extend(others);
+
+        Ok((vault_id, matching))
+    }
+
+    /// Runs `decrypt` with each candidate secret until one succeeds.
+    fn try_decrypt<T>(
+        &self,
+        vault_id: Option<String>,
+        mut decrypt: impl FnMut(&VaultSecret) -> Result<T>,
+    ) -> Result<(T, &VaultSecret)> {
+        let (vault_id, candidates) = self.candidates(vault_id)?;
+
+        let mut last_error = None;
+        for secret in candidates {
+            match decrypt(secret) {
+                Ok(plaintext) => return Ok((plaintext, secret)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error
+            .unwrap_or_else(|| anyhow::format_err!("No vault secrets found"))
+            .context(format!(
+                "Decryption failed, no vault secrets could decrypt vault id '{}'",
+                vault_id
+            )))
+    }
+
+    /// Decrypts vault text, returning the plaintext and the secret that decrypted it.
+    pub fn decrypt_with_secret(&self, vaulttext: &str) -> Result<(String, &VaultSecret)> {
+        let vault = Vault::new();
+        self.try_decrypt(vault.vault_id(vaulttext)?, |secret| {
+            vault.decrypt(vaulttext, &secret.secret)
+        })
+    }
+
+    pub fn decrypt(&self, vaulttext: &str) -> Result<String> {
+        self.decrypt_with_secret(vaulttext)
+            .map(|(plaintext, _)| plaintext)
+    }
+
+    pub fn decrypt_bytes(&self, vaulttext: &str) -> Result<Vec<u8>> {
+        let vault = Vault::new();
+        self.try_decrypt(vault.vault_id(vaulttext)?, |secret| {
+            vault.decrypt_bytes(vaulttext, &secret.secret)
+        })
+        .map(|(plaintext, _)| plaintext)
+    }
+
+    /// Returns a reader decrypting the vault text read from `input` on the fly.
+    pub fn decrypt_stream<R: Read + Seek>(&self, mut input: R) -> Result<DecryptReader<R>> {
+        let vault = Vault::new();
+        let vault_id = vault.stream_vault_id(&mut input)?;
+        let (_, secret) = self.try_decrypt(vault_id, |secret| {
+            vault.verify_stream(&mut input, &secret.secret)
+        })?;
+
+        vault.decrypt_stream(input, &secret.secret)
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        path.metadata()
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+fn is_client_script(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.ends_with("-client"))
+}
+
+fn run_password_script(path: &Path, vault_id: &str) -> Result<String> {
+    let mut command = Command::new(path);
+    if is_client_script(path) {
+        command.args(["--vault-id", vault_id]);
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("Problem running vault password script {}", path.display()))?;
+
+    if !output.status.success() {
+        bail!(
+            "Vault password script {} returned non-zero ({}): {}",
+            path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)
+        .with_context(|| {
+            format!(
+                "Vault password script {} returned invalid utf-8",
+                path.display()
+            )
+        })?
+        .trim()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_script(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_load_vault_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("dev.txt");
+        fs::write(&file, "dev-secret\n").unwrap();
+        let script = write_script(dir.path(), "prod.sh", "#!/bin/sh\necho prod-secret\n");
+        let client = write_script(
+            dir.path(),
+            "vault-client.sh",
+            "#!/bin/sh\n[ \"$1\" = --vault-id ] && echo \"secret-$2\"\n",
+        );
+
+        let secret = VaultSecret::load(&format!("dev@{}", file.display())).unwrap();
+        assert_eq!(secret, VaultSecret::new("dev", "dev-secret"));
+
+        let secret = VaultSecret::load(&script.to_string_lossy()).unwrap();
+        assert_eq!(secret, VaultSecret::new("default", "prod-secret"));
+
+        let secret = VaultSecret::load(&format!("staging@{}", client.display())).unwrap();
+        assert_eq!(secret, VaultSecret::new("staging", "secret-staging"));
+
+        let failing = write_script(dir.path(), "fail.sh", "#!/bin/sh\nexit 3\n");
+        assert!(VaultSecret::load(&failing.to_string_lossy()).is_err());
+        assert!(VaultSecret::load("@prompt").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_chooses_secret_by_label() {
+        let mut secrets = VaultSecrets::new();
+        secrets.add(VaultSecret::new("dev", "dev-secret"));
+        secrets.add(VaultSecret::new("prod", "prod-secret"));
+
+        let prod = secrets.encrypt_secret(Some("prod")).unwrap();
+        let vaulttext = prod.encrypt("prod data").unwrap();
+        assert!(vaulttext.starts_with("$ANSIBLE_VAULT;1.2;AES256;prod\n"));
+
+        let (plaintext, secret) = secrets.decrypt_with_secret(&vaulttext).unwrap();
+        assert_eq!(plaintext, "prod data");
+        assert_eq!(secret.vault_id(), "prod");
+
+        // unlabelled vault text falls back to trying every secret
+        let vaulttext = VaultSecret::new("default", "prod-secret")
+            .encrypt("legacy")
+            .unwrap();
+        assert_eq!(secrets.decrypt(&vaulttext).unwrap(), "legacy");
+
+        let vaulttext = VaultSecret::new("prod", "other").encrypt("x").unwrap();
+        let err = secrets.decrypt(&vaulttext).unwrap_err();
+        assert!(err.to_string().contains("vault id 'prod'"), "{}", err);
+
+        assert!(secrets.encrypt_secret(None).is_err());
+        assert!(secrets.encrypt_secret(Some("qa")).is_err());
+        assert!(VaultSecrets::new().decrypt(&vaulttext).is_err());
+    }
+}
@@ -2,6 +2,7 @@ use crate::task_result::TaskResult;
 use anyhow::Result;
 use cogrs_plugins::connection::{CommandOutput, ConnectionPlugin};
 use cogrs_plugins::shell::ShellPlugin;
+use std::io::Read;
 use std::path::Path;
 
 pub struct ActionHandler {
@@ -71,6 +72,12 @@ impl ActionHandler {
         Ok(output)
     }
 
+    /// Uploads a file to the remote host. Sources are opened by the caller, so vault
+    /// encrypted files can be decrypted on the controller without touching its disk.
+    pub fn transfer_data(&self, data: &mut (dyn Read + Send), remote_path: &str) -> Result<()> {
+        self.connection.put_data(data, remote_path)
+    }
+
     fn make_tmp_path(&self) -> Result<()> {
         Ok(())
     }
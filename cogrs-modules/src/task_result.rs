@@ -1,3 +1,5 @@
+use serde_json::Value;
+
 pub struct TaskResult {
     host_name: String,
     task_uuid: String,
@@ -8,6 +10,7 @@ pub struct TaskResult {
     failed_when_result: bool,
     attempts: u32,
     retries: u32,
+    facts: Option<Value>,
 }
 
 impl TaskResult {
@@ -22,6 +25,20 @@ impl TaskResult {
             failed_when_result: false,
             attempts: 0,
             retries: 0,
+            facts: None,
         }
     }
+
+    pub fn host_name(&self) -> &str {
+        &self.host_name
+    }
+
+    /// The facts the module returned (`ansible_facts`), e.g. those of `setup`.
+    pub fn facts(&self) -> Option<&Value> {
+        self.facts.as_ref()
+    }
+
+    pub fn set_facts(&mut self, facts: Value) {
+        self.facts = Some(facts);
+    }
 }
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Settings shared by all cache backends, read from the `CACHE_PLUGIN_*` configuration.
+#[derive(Debug, Clone, Default)]
+pub struct CacheSettings {
+    /// Backend specific location, e.g. the directory of file based caches.
+    pub location: Option<PathBuf>,
+    /// Seconds after which entries expire, `0` keeps them forever.
+    pub timeout: u64,
+    /// Prepended to keys by backends sharing a location between several caches.
+    pub prefix: String,
+}
+
+pub trait CachePlugin: Send + Sync {
+    fn initialize(&mut self, settings: &CacheSettings) -> Result<()>;
+
+    /// Returns the value of a key, `None` if it is missing or expired.
+    fn get(&self, key: &str) -> Result<Option<Value>>;
+
+    fn set(&self, key: &str, value: &Value) -> Result<()>;
+
+    /// Keys of every entry which has not expired.
+    fn keys(&self) -> Result<Vec<String>>;
+
+    fn contains(&self, key: &str) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Removes every entry from the cache.
+    fn flush(&self) -> Result<()>;
+}
+
+/// Macro for generating plugin metadata and FFI exports
+#[macro_export]
+macro_rules! create_cache_plugin_exports {
+    (
+        $plugin_name:ident, // Struct name of the plugin
+        $plugin_name_str:expr, // Plugin's name as a string
+        $versions:expr // Supported versions (HashMap)
+    ) => {
+        use cogrs_plugins::cache::CachePlugin;
+        use cogrs_plugins::plugin_type::PluginType;
+
+        #[no_mangle]
+        pub fn create_plugin() -> Box<dyn CachePlugin> {
+            Box::new($plugin_name::default())
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_type() -> u64 {
+            PluginType::Cache.id()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_name() -> *const std::os::raw::c_char {
+            // Ensure the string is null-terminated explicitly
+            concat!($plugin_name_str, "\0").as_ptr() as *const std::os::raw::c_char
+        }
+
+        #[no_mangle]
+        pub extern "C" fn cogrs_versions() -> *const std::os::raw::c_char {
+            let versions = serde_json::to_string(&$versions).unwrap();
+
+            std::ffi::CString::new(versions).unwrap().into_raw()
+        }
+    };
+}
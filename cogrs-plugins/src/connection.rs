@@ -2,6 +2,7 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use cogrs_schema::validation::validate_input;
 use serde_json::Value;
+use std::io::Read;
 
 pub struct CommandOutput {
     stdout: String,
@@ -33,6 +34,10 @@ pub trait ConnectionPlugin: Send + Sync {
     /// Uploads a file to the remote system
     fn put_file(&self, source_path: &str, dest_path: &str) -> Result<()>;
 
+    /// Uploads data read from memory to the remote system, used for content that must not
+    /// touch the controller's disk, like decrypted vault files
+    fn put_data(&self, data: &mut (dyn Read + Send), dest_path: &str) -> Result<()>;
+
     /// Fetches a file from the remote system
     fn fetch_file(&self, source_path: &str, dest_path: &str) -> Result<()>;
 
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+
+/// The inventory an inventory plugin adds groups, hosts and variables to. Values are YAML
+/// values so tagged `!vault` and `!unsafe` values keep their meaning.
+pub trait InventoryData {
+    /// Adds a group, nothing happens if it already exists.
+    fn add_group(&mut self, group: &str) -> Result<()>;
+
+    /// Adds a host, optionally as a member of `group` which is added if needed.
+    fn add_host(&mut self, host: &str, group: Option<&str>) -> Result<()>;
+
+    /// Makes `child` a child group of `group`, both are added if needed.
+    fn add_child(&mut self, group: &str, child: &str) -> Result<()>;
+
+    fn set_group_variable(&mut self, group: &str, key: &str, value: &Value) -> Result<()>;
+
+    fn set_host_variable(&mut self, host: &str, key: &str, value: &Value) -> Result<()>;
+
+    /// Names of every host added so far, by this source or earlier ones.
+    fn hosts(&self) -> Vec<String>;
+
+    /// Variables of a host merged over the variables of its groups.
+    fn host_vars(&self, host: &str) -> Result<Mapping>;
+}
+
+pub trait InventoryPlugin: Send + Sync {
+    /// Parses the inventory source, a file whose top level `plugin` key names this plugin.
+    fn parse(&self, source: &str, inventory: &mut dyn InventoryData) -> Result<()>;
+}
+
+/// Macro for generating plugin metadata and FFI exports
+#[macro_export]
+macro_rules! create_inventory_plugin_exports {
+    (
+        $plugin_name:ident, // Struct name of the plugin
+        $plugin_name_str:expr, // Plugin's name as a string
+        $versions:expr // Supported versions (HashMap)
+    ) => {
+        use cogrs_plugins::inventory::InventoryPlugin;
+        use cogrs_plugins::plugin_type::PluginType;
+
+        #[no_mangle]
+        pub fn create_plugin() -> Box<dyn InventoryPlugin> {
+            Box::new($plugin_name::default())
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_type() -> u64 {
+            PluginType::Inventory.id()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_name() -> *const std::os::raw::c_char {
+            // Ensure the string is null-terminated explicitly
+            concat!($plugin_name_str, "\0").as_ptr() as *const std::os::raw::c_char
+        }
+
+        #[no_mangle]
+        pub extern "C" fn cogrs_versions() -> *const std::os::raw::c_char {
+            let versions = serde_json::to_string(&$versions).unwrap();
+
+            std::ffi::CString::new(versions).unwrap().into_raw()
+        }
+    };
+}
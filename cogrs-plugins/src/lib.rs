@@ -1,5 +1,8 @@
+pub mod cache;
 pub mod callback;
 pub mod connection;
+pub mod inventory;
+pub mod lookup;
 pub mod plugin_loader;
 pub mod plugin_type;
 pub mod shell;
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+/// Access to the templating context a lookup is called from.
+pub trait LookupContext {
+    /// Returns the value of a variable, `None` if it is not defined.
+    fn get_var(&self, name: &str) -> Option<Value>;
+
+    /// Renders a template string using the variables of the calling context,
+    /// `extra_vars` take precedence over those variables.
+    fn template(&self, source: &str, extra_vars: Option<&Map<String, Value>>) -> Result<String>;
+
+    /// Searches for a file relative to the current role and playbook, looking into `sub_dir`
+    /// (e.g. `files` or `templates`) first. Absolute paths are returned if they exist.
+    fn find_file(&self, sub_dir: &str, name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.exists().then(|| path.to_path_buf());
+        }
+
+        let mut base_dirs = Vec::new();
+        for var in ["role_path", "playbook_dir"] {
+            if let Some(Value::String(dir)) = self.get_var(var) {
+                base_dirs.push(PathBuf::from(dir));
+            }
+        }
+        base_dirs.push(PathBuf::from("."));
+
+        base_dirs
+            .iter()
+            .flat_map(|dir| [dir.join(sub_dir).join(path), dir.join(path)])
+            .find(|candidate| candidate.exists())
+    }
+}
+
+pub trait LookupPlugin: Send + Sync {
+    /// Runs the lookup for the given terms, `options` are the keyword arguments of the
+    /// `lookup()` call. Returns a list of values, even for lookups returning a single value.
+    fn run(
+        &self,
+        terms: &[Value],
+        options: &Map<String, Value>,
+        context: &dyn LookupContext,
+    ) -> Result<Vec<Value>>;
+}
+
+/// Macro for generating plugin metadata and FFI exports
+#[macro_export]
+macro_rules! create_lookup_plugin_exports {
+    (
+        $plugin_name:ident, // Struct name of the plugin
+        $plugin_name_str:expr, // Plugin's name as a string
+        $versions:expr // Supported versions (HashMap)
+    ) => {
+        use cogrs_plugins::lookup::LookupPlugin;
+        use cogrs_plugins::plugin_type::PluginType;
+
+        #[no_mangle]
+        pub fn create_plugin() -> Box<dyn LookupPlugin> {
+            Box::new($plugin_name::default())
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_type() -> u64 {
+            PluginType::Lookup.id()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn plugin_name() -> *const std::os::raw::c_char {
+            // Ensure the string is null-terminated explicitly
+            concat!($plugin_name_str, "\0").as_ptr() as *const std::os::raw::c_char
+        }
+
+        #[no_mangle]
+        pub extern "C" fn cogrs_versions() -> *const std::os::raw::c_char {
+            let versions = serde_json::to_string(&$versions).unwrap();
+
+            std::ffi::CString::new(versions).unwrap().into_raw()
+        }
+    };
+}
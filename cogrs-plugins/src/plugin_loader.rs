@@ -1,5 +1,8 @@
+use crate::cache::CachePlugin;
 use crate::callback::CallbackPlugin;
 use crate::connection::ConnectionPlugin;
+use crate::inventory::InventoryPlugin;
+use crate::lookup::LookupPlugin;
 use crate::plugin_type::PluginType;
 use crate::shell::ShellPlugin;
 use anyhow::{bail, Context, Result};
@@ -95,7 +98,11 @@ impl PluginLoader {
                                         .and_modify(|paths| paths.push(plugin_path.to_path_buf()))
                                         .or_insert_with(|| vec![plugin_path]);
                                 }
-                                PluginType::Connection | PluginType::Shell => {
+                                PluginType::Connection
+                                | PluginType::Shell
+                                | PluginType::Lookup
+                                | PluginType::Cache
+                                | PluginType::Inventory => {
                                     let plugin_name =
                                         unsafe { self.get_plugin_name(&plugin_path)? };
                                     self.named_plugin_paths
@@ -283,6 +290,36 @@ impl PluginLoader {
             .await
     }
 
+    pub async fn get_lookup_plugin(&mut self, name: &str) -> Result<Box<dyn LookupPlugin>> {
+        self.get_named_plugin(PluginType::Lookup, name, PluginLoader::load_lookup_plugin)
+            .await
+    }
+
+    pub async fn get_cache_plugin(&mut self, name: &str) -> Result<Box<dyn CachePlugin>> {
+        self.get_named_plugin(PluginType::Cache, name, PluginLoader::load_cache_plugin)
+            .await
+    }
+
+    pub async fn get_inventory_plugin(&mut self, name: &str) -> Result<Box<dyn InventoryPlugin>> {
+        self.get_named_plugin(
+            PluginType::Inventory,
+            name,
+            PluginLoader::load_inventory_plugin,
+        )
+        .await
+    }
+
+    /// Names of all discovered plugins of a named plugin type.
+    pub fn get_plugin_names(&self, plugin_type: &PluginType) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .named_plugin_paths
+            .get(plugin_type)
+            .map(|paths| paths.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
     unsafe fn get_plugin_name(&self, path: &PathBuf) -> Result<String> {
         let lib =
             Library::new(path).with_context(|| format!("Failed to load plugin at {:?}", path))?;
@@ -360,6 +397,36 @@ impl PluginLoader {
         )
     }
 
+    unsafe fn load_lookup_plugin(
+        &self,
+        path: &Path,
+        name: &str,
+    ) -> Result<Option<Box<dyn LookupPlugin>>> {
+        self.load_named_plugin(path, name, PluginType::Lookup, b"create_plugin", |lib| {
+            lib.get(b"plugin_name").map_err(anyhow::Error::from)
+        })
+    }
+
+    unsafe fn load_cache_plugin(
+        &self,
+        path: &Path,
+        name: &str,
+    ) -> Result<Option<Box<dyn CachePlugin>>> {
+        self.load_named_plugin(path, name, PluginType::Cache, b"create_plugin", |lib| {
+            lib.get(b"plugin_name").map_err(anyhow::Error::from)
+        })
+    }
+
+    unsafe fn load_inventory_plugin(
+        &self,
+        path: &Path,
+        name: &str,
+    ) -> Result<Option<Box<dyn InventoryPlugin>>> {
+        self.load_named_plugin(path, name, PluginType::Inventory, b"create_plugin", |lib| {
+            lib.get(b"plugin_name").map_err(anyhow::Error::from)
+        })
+    }
+
     pub async fn get_callback_plugins(&self) -> Result<Vec<Arc<dyn CallbackPlugin>>> {
         let mut plugins: Vec<Arc<dyn CallbackPlugin>> = Vec::new();
 
@@ -8,6 +8,9 @@ pub enum PluginType {
     Callback,
     Connection,
     Shell,
+    Lookup,
+    Cache,
+    Inventory,
 }
 
 impl fmt::Display for PluginType {
@@ -18,6 +21,9 @@ impl fmt::Display for PluginType {
             PluginType::Callback => "Callback",
             PluginType::Connection => "Connection",
             PluginType::Shell => "Shell",
+            PluginType::Lookup => "Lookup",
+            PluginType::Cache => "Cache",
+            PluginType::Inventory => "Inventory",
         };
         write!(f, "{}", variant_name)
     }
@@ -38,6 +44,9 @@ impl PluginType {
             PluginType::Callback,
             PluginType::Connection,
             PluginType::Shell,
+            PluginType::Lookup,
+            PluginType::Cache,
+            PluginType::Inventory,
         ]
         .into_iter()
         .find(|variant| variant.id() == n)
@@ -1,10 +1,21 @@
-use clap::Parser;
+use crate::inventory::InventoryArgs;
+use crate::vault::VaultArgs;
+use clap::{Parser, Subcommand};
 use std::fs;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
-#[command(version = env!("APP_VERSION"), about, long_about = None)]
+#[command(
+    version = env!("APP_VERSION"),
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(long, action)]
     /// outputs a list of matching hosts; does not execute anything else
     pub list_hosts: bool,
@@ -38,6 +49,11 @@ pub struct Cli {
     /// further limit selected hosts to an additional pattern
     pub limit: Option<String>,
 
+    #[arg(long, value_name = "EXPRESSION")]
+    /// further limit selected hosts to those for which the conditional expression is true,
+    /// evaluated with the host's variables and cached facts
+    pub limit_expr: Option<String>,
+
     #[arg(long, value_name = "SECONDS")]
     /// set task timeout limit in seconds, must be positive integer
     pub task_timeout: Option<u64>,
@@ -50,6 +66,24 @@ pub struct Cli {
     /// use this file to authenticate the connection
     pub private_key_file: Option<PathBuf>,
 
+    #[arg(short = 'e', long = "extra-vars", value_name = "EXTRA_VARS")]
+    /// set additional variables as key=value or YAML/JSON, if filename prepend with @;
+    /// can be repeated
+    pub extra_vars: Vec<String>,
+
+    #[arg(long, value_name = "VAULT_PASSWORD_FILE")]
+    /// vault password file, can be repeated
+    pub vault_password_file: Vec<PathBuf>,
+
+    #[arg(long, value_name = "VAULT_ID")]
+    /// the vault identity to use as label@source, the source being `prompt`, a password
+    /// file or an executable script printing the password; can be repeated
+    pub vault_id: Vec<String>,
+
+    #[arg(short = 'J', long, action)]
+    /// ask for vault password
+    pub ask_vault_password: bool,
+
     #[arg(short = 'B', long, value_name = "SECONDS")]
     /// run asynchronously, failing after X seconds
     pub async_val: Option<u64>,
@@ -69,18 +103,31 @@ pub struct Cli {
     #[arg(short, long)]
     pub one_line: bool,
 
+    #[arg(required = true)]
     /// host pattern
-    pub pattern: String,
+    pub pattern: Option<String>,
 
     #[arg(short, long)]
     /// specify inventory host path
     pub inventory: Option<Vec<String>>,
 
+    #[arg(long, action)]
+    /// clear the inventory cache, dynamic inventory sources are run again
+    pub flush_cache: bool,
+
     #[arg(short, long, value_name = "FILE", group = "action")]
     /// specify playbook you want to run
     pub playbook: Option<PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// show the inventory as seen by a play, as a graph or in JSON, YAML or TOML
+    Inventory(InventoryArgs),
+    /// encryption/decryption utility for cogrs data files
+    Vault(VaultArgs),
+}
+
 impl Cli {
     /// Resolves the playbook_dir to an absolute path
     pub fn resolved_playbook_dir(&self) -> PathBuf {
@@ -0,0 +1,179 @@
+use anyhow::{bail, Result};
+use clap::{ArgGroup, Args};
+use cogrs_core::cli::Cli;
+use cogrs_core::config::manager::ConfigManager;
+use cogrs_core::inventory::export::{render, ExportFormat, InventoryExporter};
+use cogrs_core::inventory::lint::{InventoryLinter, Severity};
+use cogrs_core::inventory::manager::InventoryManager;
+use cogrs_core::vars::manager::VariableManager;
+use cogrs_core::vars::variable::ConflictResolution;
+use cogrs_core::vault::secrets::{set_vault_secrets, VaultSecrets};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("mode").required(true).args(["list", "host", "graph", "lint"])))]
+pub struct InventoryArgs {
+    #[arg(long, action)]
+    /// output all hosts info, works as inventory script
+    pub list: bool,
+
+    #[arg(long, value_name = "HOST")]
+    /// output specific host info, works as inventory script
+    pub host: Option<String>,
+
+    #[arg(long, value_name = "GROUP", num_args = 0..=1, default_missing_value = "all")]
+    /// create inventory graph, if supplying pattern it must be a valid group name
+    pub graph: Option<String>,
+
+    #[arg(long, action, requires = "graph")]
+    /// add vars to graph display
+    pub vars: bool,
+
+    #[arg(long, action, conflicts_with_all = ["yaml", "toml", "export"])]
+    /// check the inventory sources for problems, exits with an error when any is an error
+    pub lint: bool,
+
+    #[arg(short, long, action, conflicts_with_all = ["toml", "graph"])]
+    /// use YAML format instead of default JSON
+    pub yaml: bool,
+
+    #[arg(long, action, conflicts_with = "graph")]
+    /// use TOML format instead of default JSON
+    pub toml: bool,
+
+    #[arg(long, action)]
+    /// only keep the variables set on each group and host, instead of how they are seen by
+    /// a play, for inventories that can be used as inventory sources
+    pub export: bool,
+
+    #[arg(long, value_name = "OUTPUT_FILE")]
+    /// send the inventory output to a file instead of stdout
+    pub output: Option<PathBuf>,
+
+    #[arg(short, long)]
+    /// specify inventory host path
+    pub inventory: Option<Vec<String>>,
+
+    #[arg(long, action)]
+    /// clear the inventory cache, dynamic inventory sources are run again
+    pub flush_cache: bool,
+
+    #[arg(long, value_name = "BASEDIR", value_parser, default_value = ".")]
+    pub playbook_dir: PathBuf,
+
+    #[arg(long, value_name = "VAULT_PASSWORD_FILE")]
+    /// vault password file, can be repeated
+    pub vault_password_file: Vec<PathBuf>,
+
+    #[arg(long, value_name = "VAULT_ID")]
+    /// the vault identity to use as label@source, the source being `prompt`, a password
+    /// file or an executable script printing the password; can be repeated
+    pub vault_id: Vec<String>,
+
+    #[arg(short = 'J', long, action)]
+    /// ask for vault password
+    pub ask_vault_password: bool,
+}
+
+impl InventoryArgs {
+    fn format(&self) -> ExportFormat {
+        match (self.yaml, self.toml) {
+            (true, _) => ExportFormat::Yaml,
+            (_, true) => ExportFormat::Toml,
+            _ => ExportFormat::Json,
+        }
+    }
+}
+
+struct InventoryCli;
+
+impl Cli for InventoryCli {}
+
+/// Runs `cogrs inventory`, showing the inventory as seen by a play.
+pub async fn run(args: InventoryArgs) -> Result<()> {
+    set_vault_secrets(VaultSecrets::from_options(
+        &args.vault_id,
+        &args.vault_password_file,
+        args.ask_vault_password,
+    )?);
+    InventoryCli::init().await?;
+
+    let playbook_dir =
+        fs::canonicalize(&args.playbook_dir).unwrap_or_else(|_| args.playbook_dir.clone());
+    let mut inventory = InventoryManager::new(&playbook_dir);
+    inventory.init_cache(args.flush_cache).await?;
+
+    if args.lint {
+        return lint(&mut inventory, &args);
+    }
+
+    inventory.parse_sources(args.inventory.as_deref())?;
+
+    let mut variable_manager = VariableManager::new(&playbook_dir);
+    if let Some((value, _)) = ConfigManager::instance()
+        .lock()
+        .await
+        .get_config_value::<String>("DEFAULT_HASH_BEHAVIOUR")?
+    {
+        variable_manager.set_hash_behaviour(value.parse::<ConflictResolution>()?);
+    }
+
+    let exporter = InventoryExporter::new(&inventory, &variable_manager).export(args.export);
+    let format = args.format();
+
+    let output = if let Some(group) = &args.graph {
+        exporter.graph(group, args.vars)?
+    } else if let Some(host) = &args.host {
+        render(&exporter.host(host)?, format)?
+    } else {
+        let value = match format {
+            ExportFormat::Json => exporter.list()?,
+            ExportFormat::Yaml => exporter.inventory()?,
+            ExportFormat::Toml => exporter.flat_inventory()?,
+        };
+        render(&value, format)?
+    };
+
+    write_output(&output, &args)
+}
+
+fn lint(inventory: &mut InventoryManager, args: &InventoryArgs) -> Result<()> {
+    let sources = args.inventory.clone().unwrap_or_default();
+    let messages = InventoryLinter::new(inventory).lint(&sources)?;
+
+    let errors = messages
+        .iter()
+        .filter(|message| message.severity == Severity::Error)
+        .count();
+    let output = match messages.is_empty() {
+        true => "No problems found in the inventory".to_string(),
+        false => messages
+            .iter()
+            .map(|message| message.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    write_output(&output, args)?;
+
+    if errors > 0 {
+        bail!(
+            "The inventory has {errors} error(s) and {} warning(s)",
+            messages.len() - errors
+        );
+    }
+
+    Ok(())
+}
+
+fn write_output(output: &str, args: &InventoryArgs) -> Result<()> {
+    match &args.output {
+        Some(path) => {
+            fs::write(path, format!("{}\n", output.trim_end()))?;
+            eprintln!("Inventory written to {}", path.display());
+        }
+        None => println!("{}", output.trim_end()),
+    }
+
+    Ok(())
+}
@@ -1 +1,3 @@
 pub mod cli;
+pub mod inventory;
+pub mod vault;
@@ -1,8 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
-use cogrs::cli::Cli;
+use cogrs::cli::{Cli, Command};
+use cogrs::{inventory, vault};
 use cogrs_core::adhoc::{AdHoc, AdHocOptions};
+use cogrs_core::cli::Cli as _;
 use cogrs_core::inventory::manager;
+use cogrs_core::vault::secrets::{set_vault_secrets, VaultSecrets};
 use log::error;
 
 #[tokio::main]
@@ -18,37 +21,59 @@ async fn main() -> Result<()> {
 
 async fn run() -> Result<()> {
     let cli = Cli::parse();
+
+    match cli.command {
+        Some(Command::Vault(args)) => return vault::run(args),
+        Some(Command::Inventory(args)) => return inventory::run(args).await,
+        None => {}
+    }
+
+    // secrets are needed before parsing, inventories can be vault encrypted
+    set_vault_secrets(VaultSecrets::from_options(
+        &cli.vault_id,
+        &cli.vault_password_file,
+        cli.ask_vault_password,
+    )?);
+
+    // inventory scripts read their timeout from the configuration, and inventory
+    // plugins are found through the configured plugin paths
+    AdHoc::init().await?;
+
     let inventory = cli.inventory.as_deref();
     let playbook_dir = cli.resolved_playbook_dir();
 
     let mut manager = manager::InventoryManager::new(&playbook_dir);
+    manager.init_cache(cli.flush_cache).await?;
     manager.parse_sources(inventory)?;
-    let pattern = cli.pattern.as_str();
+    let pattern = cli.pattern.as_deref().unwrap_or_default();
+
+    let options = AdHocOptions {
+        forks: cli.forks,
+        connection: cli.connection,
+        poll_interval: Some(cli.poll_interval),
+        task_timeout: cli.task_timeout,
+        async_val: cli.async_val,
+        one_line: cli.one_line,
+        connection_timeout: cli.connection_timeout,
+        private_key_file: cli.private_key_file,
+        extra_vars: cli.extra_vars,
+        check: cli.check,
+        limit_expr: cli.limit_expr,
+    };
 
     if cli.list_hosts {
-        let hosts = manager.filter_hosts(cli.pattern.as_str(), cli.limit.as_deref())?;
+        let hosts = AdHoc::list_hosts(pattern, cli.limit.as_deref(), &manager, &options).await?;
         // ansible seems to ignore everything else if --list-hosts is specified?
         for host in hosts {
             println!("{}", host.name());
         }
     } else if let Some(module_name) = cli.module_name {
-        let options = AdHocOptions {
-            forks: cli.forks,
-            connection: cli.connection,
-            poll_interval: Some(cli.poll_interval),
-            task_timeout: cli.task_timeout,
-            async_val: cli.async_val,
-            one_line: cli.one_line,
-            connection_timeout: cli.connection_timeout,
-            private_key_file: cli.private_key_file,
-        };
-
         let result = AdHoc::run(
             pattern,
             cli.limit.as_deref(),
             &module_name,
             cli.args,
-            &manager,
+            &mut manager,
             &options,
         )
         .await;
@@ -0,0 +1,229 @@
+use anyhow::{bail, Result};
+use clap::{Args, Subcommand};
+use cogrs_core::vault::editor::VaultEditor;
+use cogrs_core::vault::secrets::{VaultSecret, VaultSecrets};
+use cogrs_core::vault::{
+    prompt_new_vault_password, prompt_vault_password, read_vault_password_file,
+};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Args, Debug)]
+pub struct VaultArgs {
+    #[arg(long, value_name = "VAULT_PASSWORD_FILE", global = true)]
+    /// vault password file, can be repeated
+    pub vault_password_file: Vec<PathBuf>,
+
+    #[arg(long, value_name = "VAULT_ID", global = true)]
+    /// the vault identity to use as label@source, the source being `prompt`, a password
+    /// file or an executable script printing the password; can be repeated
+    pub vault_id: Vec<String>,
+
+    #[arg(short = 'J', long, action, global = true)]
+    /// ask for vault password
+    pub ask_vault_password: bool,
+
+    #[arg(long, value_name = "LABEL", global = true)]
+    /// the vault id label used for encryption, written into the 1.2 vault header
+    pub encrypt_vault_id: Option<String>,
+
+    #[command(subcommand)]
+    pub action: VaultAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VaultAction {
+    /// encrypt YAML files
+    Encrypt {
+        #[arg(long, value_name = "OUTPUT_FILE")]
+        /// output file name for encrypt or decrypt; use - for stdout
+        output: Option<PathBuf>,
+
+        #[arg(default_value = "-")]
+        /// files to encrypt, - reads from stdin
+        files: Vec<PathBuf>,
+    },
+    /// decrypt vault encrypted files
+    Decrypt {
+        #[arg(long, value_name = "OUTPUT_FILE")]
+        /// output file name for encrypt or decrypt; use - for stdout
+        output: Option<PathBuf>,
+
+        #[arg(default_value = "-")]
+        /// files to decrypt, - reads from stdin
+        files: Vec<PathBuf>,
+    },
+    /// view the content of vault encrypted files
+    View {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// edit a vault encrypted file with $EDITOR
+    Edit { file: PathBuf },
+    /// re-encrypt vault encrypted files with a new password
+    Rekey {
+        #[arg(long, value_name = "NEW_VAULT_PASSWORD_FILE")]
+        /// new vault password file for rekey
+        new_vault_password_file: Option<PathBuf>,
+
+        #[arg(long, value_name = "NEW_VAULT_ID")]
+        /// the new vault identity to use for rekey, as label@source
+        new_vault_id: Option<String>,
+
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// encrypt a string into an inline !vault YAML value
+    #[command(name = "encrypt_string")]
+    EncryptString {
+        #[arg(short = 'p', long = "prompt")]
+        /// prompt for the string to encrypt
+        prompt: bool,
+
+        #[arg(short = 'n', long = "name", value_name = "NAME")]
+        /// variable name for the encrypted string, can be repeated
+        names: Vec<String>,
+
+        #[arg(long, value_name = "NAME")]
+        /// read the string to encrypt from stdin and use this variable name
+        stdin_name: Option<String>,
+
+        /// strings to encrypt
+        strings: Vec<String>,
+    },
+}
+
+/// Runs a `cogrs vault` action.
+pub fn run(args: VaultArgs) -> Result<()> {
+    let editor = VaultEditor::new();
+    let encrypt_vault_id = args.encrypt_vault_id.as_deref();
+    let secrets = VaultSecrets::from_options(
+        &args.vault_id,
+        &args.vault_password_file,
+        args.ask_vault_password,
+    )?;
+
+    match &args.action {
+        VaultAction::Encrypt { output, files } => {
+            check_output(output.as_deref(), files)?;
+            let secrets = encrypt_secrets(secrets, encrypt_vault_id)?;
+            let secret = secrets.encrypt_secret(encrypt_vault_id)?;
+            for file in files {
+                editor.encrypt_file(file, secret, output.as_deref())?;
+            }
+            eprintln!("Encryption successful");
+        }
+        VaultAction::Decrypt { output, files } => {
+            check_output(output.as_deref(), files)?;
+            let secrets = decrypt_secrets(secrets)?;
+            for file in files {
+                editor.decrypt_file(file, &secrets, output.as_deref())?;
+            }
+            eprintln!("Decryption successful");
+        }
+        VaultAction::View { files } => {
+            let secrets = decrypt_secrets(secrets)?;
+            for file in files {
+                print!("{}", editor.plaintext(file, &secrets)?);
+            }
+        }
+        VaultAction::Edit { file } => {
+            let secrets = decrypt_secrets(secrets)?;
+            if file.exists() {
+                editor.edit_file(file, &secrets)?;
+            } else {
+                bail!(
+                    "{} does not exist, use `cogrs vault encrypt` first",
+                    file.display()
+                );
+            }
+        }
+        VaultAction::Rekey {
+            new_vault_password_file,
+            new_vault_id,
+            files,
+        } => {
+            let secrets = decrypt_secrets(secrets)?;
+            let new_secret = match (new_vault_id, new_vault_password_file) {
+                (Some(vault_id), _) => VaultSecret::load(vault_id)?,
+                (None, Some(path)) => VaultSecret::new("default", &read_vault_password_file(path)?),
+                (None, None) => VaultSecret::new(
+                    "default",
+                    &prompt_new_vault_password("New Vault password: ")?,
+                ),
+            };
+            for file in files {
+                editor.rekey_file(file, &secrets, &new_secret)?;
+            }
+            eprintln!("Rekey successful");
+        }
+        VaultAction::EncryptString {
+            prompt,
+            names,
+            stdin_name,
+            strings,
+        } => {
+            let mut plaintexts: Vec<(Option<&str>, String)> = Vec::new();
+
+            if let Some(name) = stdin_name {
+                let mut data = String::new();
+                std::io::stdin().read_to_string(&mut data)?;
+                plaintexts.push((Some(name), data));
+            } else if *prompt {
+                let name = names.first().map(String::as_str);
+                plaintexts.push((name, prompt_vault_password("String to encrypt: ")?));
+            } else {
+                if names.len() > strings.len() {
+                    bail!("Each --name must be paired with a string to encrypt");
+                }
+                for (i, plaintext) in strings.iter().enumerate() {
+                    plaintexts.push((names.get(i).map(String::as_str), plaintext.clone()));
+                }
+            }
+
+            if plaintexts.is_empty() {
+                bail!("No strings to encrypt, pass them as arguments, --prompt or --stdin-name");
+            }
+
+            let secrets = encrypt_secrets(secrets, encrypt_vault_id)?;
+            let secret = secrets.encrypt_secret(encrypt_vault_id)?;
+            for (name, plaintext) in plaintexts {
+                if plaintext.is_empty() {
+                    bail!("The plaintext provided was empty, not encrypting");
+                }
+                println!("{}", editor.encrypt_string(&plaintext, secret, name)?);
+            }
+            eprintln!("Encryption successful");
+        }
+    }
+
+    Ok(())
+}
+
+/// Several input files can not be written to the same output, unless it is stdout.
+fn check_output(output: Option<&Path>, files: &[PathBuf]) -> Result<()> {
+    if files.len() > 1 && output.is_some_and(|output| output != Path::new("-")) {
+        bail!("At most one input file may be used with the --output option");
+    }
+
+    Ok(())
+}
+
+/// Prompts for the vault password if no secret was given.
+fn decrypt_secrets(mut secrets: VaultSecrets) -> Result<VaultSecrets> {
+    if secrets.is_empty() {
+        secrets.add(VaultSecret::load("prompt")?);
+    }
+
+    Ok(secrets)
+}
+
+/// Prompts for a new vault password, labelled with `vault_id`, if no secret was given.
+fn encrypt_secrets(mut secrets: VaultSecrets, vault_id: Option<&str>) -> Result<VaultSecrets> {
+    if secrets.is_empty() {
+        let secret = prompt_new_vault_password("New Vault password: ")?;
+        secrets.add(VaultSecret::new(vault_id.unwrap_or("default"), &secret));
+    }
+
+    Ok(secrets)
+}
@@ -1,10 +1,11 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use cogrs_plugins::connection::{CommandOutput, ConnectionPlugin};
 use cogrs_plugins::create_connection_plugin;
 use cogrs_schema::define_schema;
-use openssh::{KnownHosts, Session};
+use openssh::{KnownHosts, SessionBuilder};
 use serde::{Deserialize, Serialize};
+use std::io::Read;
 
 #[derive(Debug, Deserialize, Serialize, Default)]
 struct Parameters {
@@ -14,6 +15,9 @@ struct Parameters {
     do_become: bool,
     become_user: Option<String>,
     remote_user: String,
+    port: Option<u16>,
+    private_key_file: Option<String>,
+    proxy_jump: Option<String>,
 }
 
 define_schema! {
@@ -28,7 +32,10 @@ define_schema! {
             "task_uuid": { "type": "string", "description": "Task UUID." },
             "become": { "type": "boolean", "description": "Whether to use sudo." },
             "become_user": { "type": "string", "description": "User to become." },
-            "remote_user": { "type": "string", "description": "User to connect as." }
+            "remote_user": { "type": "string", "description": "User to connect as." },
+            "port": { "type": "integer", "minimum": 1, "maximum": 65535, "description": "SSH port, the ssh default when not set." },
+            "private_key_file": { "type": "string", "description": "Private key file used to authenticate." },
+            "proxy_jump": { "type": "string", "description": "Comma separated jump hosts to connect through." }
         },
         "additionalProperties": true,
         "required": ["host", "task_uuid", "remote_user"]
@@ -67,8 +74,22 @@ impl ConnectionPlugin for Ssh {
         let do_become = self.do_become();
         let remote_user = self.parameters.remote_user.to_owned();
 
-        let connect_string = format!("{}@{}", remote_user, host);
-        let session = Session::connect(&connect_string, KnownHosts::Accept)
+        let mut builder = SessionBuilder::default();
+        builder
+            .user(remote_user)
+            .known_hosts_check(KnownHosts::Accept);
+        if let Some(port) = self.parameters.port {
+            builder.port(port);
+        }
+        if let Some(private_key_file) = &self.parameters.private_key_file {
+            builder.keyfile(private_key_file);
+        }
+        if let Some(proxy_jump) = &self.parameters.proxy_jump {
+            builder.jump_hosts(proxy_jump.split(',').map(str::trim));
+        }
+
+        let session = builder
+            .connect(&host)
             .await
             .with_context(|| format!("Failed to connect to {}.", host))?;
 
@@ -86,6 +107,10 @@ impl ConnectionPlugin for Ssh {
         todo!()
     }
 
+    fn put_data(&self, _data: &mut (dyn Read + Send), dest_path: &str) -> Result<()> {
+        bail!("Uploading {dest_path} is not supported by the ssh connection plugin yet")
+    }
+
     fn fetch_file(&self, source_path: &str, dest_path: &str) -> Result<()> {
         todo!()
     }